@@ -2,26 +2,39 @@ use std::borrow::Cow;
 use std::cmp::{max, min};
 use std::collections::BTreeMap;
 use std::ffi::OsStr;
-use std::ops::{RangeBounds, RangeInclusive};
-use std::path::Path;
+use std::io;
+use std::ops::{Range, RangeBounds, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use unicode_width::UnicodeWidthChar;
 
 use crate::compiler::CompileId;
+use crate::compiler::CompileProfile;
 use crate::compiler::CompileResult;
 use crate::compiler::Compiler;
+use crate::compiler::CompilerOutput;
+use crate::compiler::DiagnosticFilter;
+use crate::compiler::SpanLabel;
 use crate::config;
 use crate::config::types::keys;
+use crate::config::types::Command;
+use crate::config::types::FormatterConfig;
 use crate::core::Core;
 use crate::core::CoreBuffer;
 use crate::core::Cursor;
 use crate::core::Id;
+use crate::core::TextEdit;
+use crate::cpp_project::compile_commands_dir;
 use crate::draw;
 use crate::draw::{styles, CharStyle, LinenumView, TermView};
 use crate::draw_cache::DrawCache;
+use crate::file_format::FileFormat;
 use crate::formatter;
-use crate::lsp::LSPClient;
-use crate::storage::Storage;
+use crate::lsp::{LSPClient, LSPPool};
+use crate::recent;
+use crate::storage::{FileStamp, Storage};
+use crate::swap::{swap_dir, SwapFile};
 use crate::syntax;
 use crate::tabnine::TabNineClient;
 
@@ -39,6 +52,31 @@ impl Default for Yank {
     }
 }
 
+// Wait for typing to pause before requesting semantic tokens and inlay hints
+const LSP_DECORATION_DELAY: Duration = Duration::from_millis(300);
+// Interval to look for changes of the file by other programs
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// Interval to write unsaved changes to the swap file
+const SWAP_INTERVAL: Duration = Duration::from_secs(2);
+// Files larger than this are opened without highlighting, completion and swap files
+const LARGE_FILE_SIZE: usize = 32 * 1024 * 1024;
+
+pub enum DiskChange {
+    // The file changed and the buffer was reloaded as it had no modification
+    Reloaded,
+    // Both the file and the buffer are modified
+    Conflict,
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Compilers print paths relative to the working directory
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || canonicalize(a) == canonicalize(b)
+}
+
 fn get_rows(s: &str, width: usize) -> usize {
     let mut x = 0;
     let mut y = 1;
@@ -63,6 +101,8 @@ enum ShowCursor {
 
 pub struct Buffer<'a, B: CoreBuffer> {
     storage: Option<Box<dyn Storage<B>>>,
+    // Encoding and line endings to save with
+    pub format: FileFormat,
     pub core: Core<B>,
     pub search: Vec<char>,
     syntax_parent: &'a syntax::SyntaxParent,
@@ -71,11 +111,38 @@ pub struct Buffer<'a, B: CoreBuffer> {
     pub snippet: BTreeMap<String, String>,
     pub yank: Yank,
     last_save: Id,
+    // Format of the file on disk. Changing `format` is a modification.
+    saved_format: FileFormat,
+    // Stamp and content hash of the file when it was loaded or saved
+    disk_state: Option<(FileStamp, Option<u64>)>,
+    // Stamp of a conflicting change the user already decided on
+    dismissed_stamp: Option<FileStamp>,
+    last_disk_check: Instant,
+    // Snapshot of unsaved changes for crash recovery
+    swap: Option<SwapFile>,
+    swapped: Id,
+    last_swap: Instant,
+    large_file: bool,
     pub lsp: Option<LSPClient>,
+    lsp_pool: LSPPool,
     pub tabnine: Option<TabNineClient>,
     compiler: Option<Compiler<'a>>,
+    // Labels of LSP inlay hints drawn as virtual text
+    inlay_hints: BTreeMap<Cursor, String>,
+    // Buffer version whose semantic tokens and inlay hints are requested
+    lsp_decoration: Option<Id>,
+    lsp_decoration_changed: Option<(Id, Instant)>,
     row_offset: usize,
     last_compiler_result: Option<CompileResult>,
+    // Messages for this file from compilations of other buffers keyed by their paths
+    external_compiler_messages: BTreeMap<PathBuf, Vec<CompilerOutput>>,
+    // Messages of the last compilation for other files. BufferTab routes them.
+    foreign_compiler_messages: Option<(PathBuf, Vec<CompilerOutput>)>,
+    // Reports of the last run (e.g. sanitizers). Cleared by the next compilation.
+    runtime_messages: Vec<CompilerOutput>,
+    // Own, external and runtime messages
+    compiler_messages: Vec<CompilerOutput>,
+    pub diagnostic_filter: DiagnosticFilter,
     cache: DrawCache<'a>,
     buffer_update: Id,
     last_compiler_submit: CompileId,
@@ -87,11 +154,21 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
     pub fn new(
         syntax_parent: &'a syntax::SyntaxParent,
         config: &'a config::ConfigWithDefault,
+    ) -> Self {
+        Self::with_lsp_pool(syntax_parent, config, LSPPool::default())
+    }
+
+    /// Create a buffer sharing language servers in `lsp_pool`
+    pub fn with_lsp_pool(
+        syntax_parent: &'a syntax::SyntaxParent,
+        config: &'a config::ConfigWithDefault,
+        lsp_pool: LSPPool,
     ) -> Self {
         let syntax = syntax_parent.load_syntax_or_txt("txt", None);
 
         let mut res = Self {
             storage: None,
+            format: FileFormat::default(),
             core: Core::default(),
             search: Vec::new(),
             cache: DrawCache::new(&syntax),
@@ -99,11 +176,30 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
             snippet: BTreeMap::new(),
             yank: Yank::default(),
             last_save: Id::default(),
+            saved_format: FileFormat::default(),
+            disk_state: None,
+            dismissed_stamp: None,
+            last_disk_check: Instant::now(),
+            swap: None,
+            swapped: Id::default(),
+            last_swap: Instant::now(),
+            large_file: false,
             lsp: None,
+            lsp_pool,
             tabnine: None,
-            compiler: config.get::<keys::Compiler>(None).map(Compiler::new),
+            compiler: config
+                .get::<keys::Compiler>(None)
+                .map(|c| Compiler::new(c, None)),
+            inlay_hints: BTreeMap::new(),
+            lsp_decoration: None,
+            lsp_decoration_changed: None,
             row_offset: 0,
             last_compiler_result: None,
+            external_compiler_messages: BTreeMap::new(),
+            foreign_compiler_messages: None,
+            runtime_messages: Vec::new(),
+            compiler_messages: Vec::new(),
+            diagnostic_filter: DiagnosticFilter::default(),
             syntax_parent,
             config,
             buffer_update: Id::default(),
@@ -138,6 +234,10 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
     }
 
     pub fn extend_cache_duration(&mut self, duration: std::time::Duration) {
+        // Lines in view are highlighted on demand
+        if self.large_file {
+            return;
+        }
         let highlighter = syntect::highlighting::Highlighter::new(&self.syntax.theme);
         self.cache
             .extend_cache_duration(self.core.core_buffer(), duration, &highlighter);
@@ -152,20 +252,47 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
     }
 
     pub fn restart_completer(&mut self) {
+        if self.large_file {
+            self.lsp = None;
+            self.tabnine = None;
+            return;
+        }
         let ext = self
             .extension()
             .unwrap_or_default()
             .to_string_lossy()
             .into_owned();
-        self.lsp = self
-            .get_config::<keys::LSP>()
-            .and_then(|c| LSPClient::start(c.command(), ext).ok());
+        // Replace the old client after connecting so that the shared server is not shut down
+        let lsp = self.get_config::<keys::LSP>().and_then(|c| {
+            let mut command = c.clone();
+            // Let clangd find the compilation database out of the source tree (e.g. build/)
+            if Path::new(&command.program).file_name() == Some(OsStr::new("clangd")) {
+                if let Some(dir) = self
+                    .path()
+                    .and_then(|p| p.canonicalize().ok())
+                    .and_then(|p| compile_commands_dir(&p))
+                {
+                    command
+                        .args
+                        .push(format!("--compile-commands-dir={}", dir.display()));
+                }
+            }
+            self.lsp_pool.client(&command, ext, self.path()).ok()
+        });
+        self.lsp = lsp;
+        self.lsp_decoration = None;
+        self.inlay_hints.clear();
         self.tabnine = self
             .get_config::<keys::TabNineCommand>()
             .and_then(|c| TabNineClient::new(c.command()).ok());
     }
 
     fn reset_syntax(&mut self) {
+        if self.large_file {
+            self.syntax = self.syntax_parent.load_syntax_or_txt("txt", None);
+            self.cache = DrawCache::new(&self.syntax);
+            return;
+        }
         let syntax_extension = self
             .get_config::<keys::SyntaxExtension>()
             .cloned()
@@ -187,7 +314,10 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
     }
 
     pub fn set_language(&mut self) {
-        self.compiler = self.get_config::<keys::Compiler>().map(Compiler::new);
+        let path = self.path().map(Path::to_path_buf);
+        self.compiler = self
+            .get_config::<keys::Compiler>()
+            .map(|c| Compiler::new(c, path.as_deref()));
         self.restart_completer();
         self.reset_snippet();
         self.reset_syntax();
@@ -199,29 +329,234 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
 
     pub fn set_storage<T: Storage<B> + 'static>(&mut self, storage: T) {
         self.storage = Some(Box::new(storage));
+        self.disk_state = None;
+        self.dismissed_stamp = None;
+        self.discard_swap();
         self.set_language();
     }
 
-    pub fn open<S: Storage<B> + 'static>(&mut self, mut storage: S) {
-        self.core = storage.load();
+    pub fn open<S: Storage<B> + 'static>(&mut self, mut storage: S) -> io::Result<()> {
+        // Known before loading so that the text is not hashed
+        let large_on_disk = storage
+            .stamp()
+            .is_some_and(|stamp| stamp.size() > LARGE_FILE_SIZE as u64);
+        let (core, format) = storage.load()?;
+        let read_only = self.core.is_read_only();
+        self.core = core;
+        // Saving would replace the invalid bytes with U+FFFD
+        self.core.set_read_only(read_only || format.invalid_bytes);
+        self.format = format;
+        self.large_file = large_on_disk || self.core.core_buffer().len_bytes() > LARGE_FILE_SIZE;
         self.set_storage(storage);
 
         self.row_offset = 0;
-        self.last_save = self.core.buffer_changed();
+        self.mark_saved();
         self.cache = DrawCache::new(&self.syntax);
-        self.compile(false);
+        self.compile(CompileProfile::Normal);
+        if let Some(cursor) = self.local_path().and_then(recent::last_cursor) {
+            self.jump_to(cursor);
+        }
+        self.remember_position();
+        Ok(())
     }
 
-    pub fn save(&mut self, is_optimize: bool) -> bool {
-        let saved = if let Some(storage) = self.storage.as_mut() {
-            storage.save(&self.core)
+    /// Save unless another program changed the file since it was loaded or saved
+    pub fn save(&mut self, profile: CompileProfile) -> io::Result<()> {
+        if self.is_changed_on_disk() {
+            return Err(io::Error::other("The file is changed on disk"));
+        }
+        self.overwrite(profile)
+    }
+
+    /// Save even if the file is changed on disk
+    pub fn overwrite(&mut self, profile: CompileProfile) -> io::Result<()> {
+        if self.core.is_read_only() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "The buffer is read-only",
+            ));
+        }
+        self.storage
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No file name"))?
+            .save(&self.core, &self.format)?;
+        // U+FFFD is in the file now
+        self.format.invalid_bytes = false;
+        self.mark_saved();
+        self.remember_position();
+        self.compile(profile);
+        Ok(())
+    }
+
+    /// Load the file again. The reload can be undone unless the file is large.
+    pub fn reload(&mut self) -> io::Result<()> {
+        let (core, format) = self
+            .storage
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No file name"))?
+            .load()?;
+        // Reloading is not an edit of the user
+        let read_only = self.core.is_read_only();
+        self.core.set_read_only(false);
+        if self.large_file {
+            // Diffing is too slow. Don't keep another copy of the text in the history.
+            let cursor = self.core.cursor();
+            self.core.set_string(core.get_string(), true);
+            self.jump_to(cursor);
         } else {
-            false
+            let edits = formatter::diff_edits(&self.core.get_string(), &core.get_string());
+            if !edits.is_empty() {
+                self.core.apply_edits(edits);
+            }
+        }
+        self.core.commit();
+        self.core.set_read_only(read_only || format.invalid_bytes);
+        self.format = format;
+        self.mark_saved();
+        self.compile(CompileProfile::Normal);
+        Ok(())
+    }
+
+    /// Large files are shown without highlighting, completion and swap files
+    pub fn is_large_file(&self) -> bool {
+        self.large_file
+    }
+
+    /// Has the buffer changed since it was loaded or saved?
+    pub fn is_modified(&self) -> bool {
+        self.last_save != self.core.buffer_changed() || self.format != self.saved_format
+    }
+
+    // The buffer is the same as the file now
+    fn mark_saved(&mut self) {
+        self.last_save = self.core.buffer_changed();
+        self.saved_format = self.format;
+        let large_file = self.large_file;
+        // Large files are compared by the stamp only
+        self.disk_state = self.storage.as_ref().and_then(|s| {
+            let hash = if large_file {
+                None
+            } else {
+                Some(s.content_hash()?)
+            };
+            Some((s.stamp()?, hash))
+        });
+        self.dismissed_stamp = None;
+        self.discard_swap();
+    }
+
+    /// Replace the text with a recovered `base` and apply `steps` of edits to it.
+    /// Each of them can be undone.
+    pub fn recover(&mut self, base: &str, steps: Vec<Vec<TextEdit>>) {
+        // Recovering is not an edit of the user
+        let read_only = self.core.is_read_only();
+        self.core.set_read_only(false);
+        let edits = formatter::diff_edits(&self.core.get_string(), base);
+        for edits in std::iter::once(edits).chain(steps) {
+            if !edits.is_empty() {
+                self.core.apply_edits(edits);
+                self.core.commit();
+            }
+        }
+        self.core.set_read_only(read_only);
+    }
+
+    /// Write unsaved changes to the swap file once in a while
+    pub fn poll_swap(&mut self) {
+        if self.large_file
+            || self.swapped == self.core.buffer_changed()
+            || self.last_swap.elapsed() < SWAP_INTERVAL
+        {
+            return;
+        }
+        self.last_swap = Instant::now();
+        self.swapped = self.core.buffer_changed();
+        if !self.is_modified() {
+            self.discard_swap();
+            return;
+        }
+        let path = self.path().map(canonicalize);
+        let text = self.core.get_string();
+        if let Some(swap) = self.swap.as_mut() {
+            if swap.update(text).is_err() {
+                self.discard_swap();
+            }
+        } else {
+            self.swap =
+                swap_dir().and_then(|dir| SwapFile::create(&dir, path.as_deref(), text).ok());
+        }
+    }
+
+    // Path of a file on this machine
+    fn local_path(&self) -> Option<&Path> {
+        self.storage
+            .as_ref()
+            .filter(|s| s.stamp().is_some())
+            .map(|s| s.path())
+    }
+
+    // Put the file on top of the recent files with the cursor position
+    fn remember_position(&self) {
+        if let Some(path) = self.local_path() {
+            recent::record(path, self.core.cursor());
+        }
+    }
+
+    /// Call this when the buffer is closed
+    pub fn close(&mut self) {
+        self.remember_position();
+        self.discard_swap();
+    }
+
+    /// Remove the swap file
+    pub fn discard_swap(&mut self) {
+        if let Some(swap) = self.swap.take() {
+            swap.remove();
+        }
+        self.swapped = Id::default();
+    }
+
+    /// Whether another program changed the file since it was loaded or saved
+    pub fn is_changed_on_disk(&mut self) -> bool {
+        let (storage, (stamp, hash)) = match (self.storage.as_ref(), self.disk_state) {
+            (Some(storage), Some(state)) => (storage, state),
+            _ => return false,
         };
-        if saved {
-            self.compile(is_optimize);
+        match storage.stamp() {
+            // Saving a deleted file loses nothing
+            None => false,
+            Some(current) if current == stamp => false,
+            Some(current) => {
+                if hash.is_some() && storage.content_hash() == hash {
+                    // Only touched
+                    self.disk_state = Some((current, hash));
+                    false
+                } else {
+                    true
+                }
+            }
         }
-        saved
+    }
+
+    /// Look for changes on disk once in a while. A buffer without modification is reloaded.
+    pub fn poll_disk_change(&mut self) -> Option<DiskChange> {
+        if self.last_disk_check.elapsed() < DISK_CHECK_INTERVAL {
+            return None;
+        }
+        self.last_disk_check = Instant::now();
+        // The user chose to keep the buffer for this version on disk. Don't hash it again.
+        let stamp = self.storage.as_ref().and_then(|s| s.stamp());
+        if stamp.is_some() && stamp == self.dismissed_stamp {
+            return None;
+        }
+        if !self.is_changed_on_disk() {
+            return None;
+        }
+        if !self.is_modified() && self.reload().is_ok() {
+            return Some(DiskChange::Reloaded);
+        }
+        self.dismissed_stamp = stamp;
+        Some(DiskChange::Conflict)
     }
 
     pub fn show_cursor(&mut self) {
@@ -240,42 +575,44 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
                 return;
             }
             let cols = cols - LinenumView::prefix_width(self.core.core_buffer().len_lines());
+            let labels = self.diagnostic_labels();
+            let display_rows = |row: usize| {
+                get_rows(self.display_line(row).as_str(), cols)
+                    + labels.get(&row).map(Vec::len).unwrap_or(0)
+            };
             let mut i = self.core.cursor().row + 1;
             let mut sum = 0;
-            while i > 0
-                && sum
-                    + get_rows(
-                        self.core
-                            .core_buffer()
-                            .get_range(
-                                Cursor { row: i - 1, col: 0 }..Cursor {
-                                    row: i - 1,
-                                    col: self.core.core_buffer().len_line(i - 1),
-                                },
-                            )
-                            .as_str(),
-                        cols,
-                    )
-                    <= rows
-            {
-                sum += get_rows(
-                    self.core
-                        .core_buffer()
-                        .get_range(
-                            Cursor { row: i - 1, col: 0 }..Cursor {
-                                row: i - 1,
-                                col: self.core.core_buffer().len_line(i - 1),
-                            },
-                        )
-                        .as_str(),
-                    cols,
-                );
+            while i > 0 && sum + display_rows(i - 1) <= rows {
+                sum += display_rows(i - 1);
                 i -= 1;
             }
             self.row_offset = max(i, self.row_offset);
         }
     }
 
+    // Line content with inlay hints
+    fn display_line(&self, row: usize) -> String {
+        let mut line = self.core.get_string_range(
+            Cursor { row, col: 0 }..Cursor {
+                row,
+                col: self.core.core_buffer().len_line(row),
+            },
+        );
+        for label in self
+            .inlay_hints
+            .range(
+                Cursor { row, col: 0 }..Cursor {
+                    row: row + 1,
+                    col: 0,
+                },
+            )
+            .map(|(_, label)| label)
+        {
+            line.push_str(label);
+        }
+        line
+    }
+
     fn show_cursor_middle_(&mut self, rows: usize) {
         if rows / 2 > self.core.cursor().row {
             self.row_offset = 0;
@@ -296,36 +633,69 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
         self.row_offset = min(self.row_offset + 3, self.core.core_buffer().len_lines() - 1);
     }
 
-    pub fn format(&mut self) -> Result<(), Cow<'static, str>> {
-        let src = self.core.get_string();
+    pub async fn format(&mut self) -> Result<(), Cow<'static, str>> {
         let formatter = self.config.get::<keys::Formatter>(self.path());
 
-        if let Some(formatter) = formatter {
-            if let Some(formatted) = formatter::system_format(formatter.command(), &src) {
-                if formatted != self.core.get_string() {
-                    self.core.set_string(formatted, false);
+        match formatter {
+            Some(FormatterConfig::LSP) => self.format_lsp(None).await,
+            Some(FormatterConfig::Command(command)) => {
+                let src = self.core.get_string();
+                if let Some(formatted) = formatter::system_format(command.command(), &src) {
+                    if formatted != src {
+                        self.core
+                            .apply_edits(formatter::diff_edits(&src, &formatted));
+                        self.core.commit();
+                    }
+                    Ok(())
+                } else {
+                    Err(Cow::Owned(format!("Failed to run {}", command)))
                 }
-                Ok(())
-            } else {
-                Err(Cow::Owned(format!("Failed to run {}", formatter)))
             }
-        } else {
-            Err(Cow::Borrowed("formatter is not defined"))
+            None => Err(Cow::Borrowed("formatter is not defined")),
+        }
+    }
+
+    pub async fn format_range(&mut self, range: Range<Cursor>) -> Result<(), Cow<'static, str>> {
+        match self.config.get::<keys::Formatter>(self.path()) {
+            Some(FormatterConfig::LSP) => self.format_lsp(Some(range)).await,
+            Some(FormatterConfig::Command(_)) => {
+                Err(Cow::Borrowed("Range formatting needs formatter = \"lsp\""))
+            }
+            None => Err(Cow::Borrowed("formatter is not defined")),
+        }
+    }
+
+    async fn format_lsp(&mut self, range: Option<Range<Cursor>>) -> Result<(), Cow<'static, str>> {
+        let src = self.core.get_string();
+        let tab_size = self.indent_width();
+        let insert_spaces = !self.hard_tab();
+        let lsp = self
+            .lsp
+            .as_mut()
+            .ok_or(Cow::Borrowed("LSP is not running"))?;
+        let edits = lsp
+            .format(src, tab_size, insert_spaces, range)
+            .await
+            .map_err(|err| Cow::Owned(format!("Failed to format with LSP: {}", err)))?;
+        if !edits.is_empty() {
+            self.core.apply_edits(edits);
+            self.core.commit();
         }
+        Ok(())
     }
 
-    pub fn compile(&mut self, is_optimize: bool) {
+    pub fn compile(&mut self, profile: CompileProfile) {
         if self.last_compiler_submit
             == (CompileId {
                 id: self.core.buffer_changed(),
-                is_optimize,
+                profile,
             })
         {
             return;
         }
         self.last_compiler_submit = CompileId {
             id: self.core.buffer_changed(),
-            is_optimize,
+            profile,
         };
 
         if let Some(path) = self.path() {
@@ -335,47 +705,346 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
         }
     }
 
+    /// Command to run the program in a project (e.g. `cargo run`) instead of `test_command`
+    pub fn project_run_command(&self, profile: CompileProfile) -> Option<Command> {
+        let path = self.path()?;
+        self.compiler
+            .as_ref()
+            .and_then(|c| c.run_command(path, profile))
+    }
+
     pub fn last_compile_success(&self) -> Option<bool> {
         self.last_compiler_result.as_ref().map(|res| res.success)
     }
 
     fn is_annotate(&self, cursor: Cursor) -> bool {
-        self.last_compiler_result
-            .as_ref()
-            .map(|res| res.messages.iter().any(|r| r.span.contains(&cursor)))
-            .unwrap_or(false)
+        self.compiler_messages
+            .iter()
+            .any(|r| r.span.contains(&cursor))
+    }
+
+    /// Number of compiler errors in this file
+    pub fn error_count(&self) -> usize {
+        self.compiler_messages
+            .iter()
+            .filter(|r| r.severity() >= 2)
+            .count()
+    }
+
+    // Compiler messages on the cursor line. The message under the cursor or the most severe one comes first.
+    fn messages_on_cursor(&self) -> Vec<&CompilerOutput> {
+        let cursor = self.core.cursor();
+        let mut messages = self
+            .compiler_messages
+            .iter()
+            .filter(|r| r.line == cursor.row)
+            .collect::<Vec<_>>();
+        if let Some(i) = messages
+            .iter()
+            .position(|r| r.span.contains(&cursor))
+            .or_else(|| (0..messages.len()).max_by_key(|&i| messages[i].severity()))
+        {
+            let message = messages.remove(i);
+            messages.insert(0, message);
+        }
+        messages
+    }
+
+    /// Labels of the message on the cursor line to draw under the source, keyed by rows
+    fn diagnostic_labels(&self) -> BTreeMap<usize, Vec<SpanLabel>> {
+        let mut res: BTreeMap<usize, Vec<SpanLabel>> = BTreeMap::new();
+        if let Some(message) = self.messages_on_cursor().first() {
+            for label in message.all_labels() {
+                res.entry(label.span.start().row).or_default().push(label);
+            }
+        }
+        for labels in res.values_mut() {
+            labels.sort_by_key(|l| l.span.start().col);
+        }
+        res
+    }
+
+    pub fn compiler_message_on_cursor(&self) -> Option<String> {
+        let messages = self.messages_on_cursor();
+        let message = messages.first()?;
+        if messages.len() > 1 {
+            Some(format!(
+                "{} (+{} more)",
+                message.message,
+                messages.len() - 1
+            ))
+        } else {
+            Some(message.message.clone())
+        }
+    }
+
+    /// Compiler messages passing `diagnostic_filter` in the order of position
+    pub fn diagnostics(&self) -> Vec<&CompilerOutput> {
+        let mut res = self
+            .compiler_messages
+            .iter()
+            .filter(|r| self.diagnostic_filter.accept(r))
+            .collect::<Vec<_>>();
+        res.sort_by_key(|r| *r.span.start());
+        res
     }
 
-    pub fn compiler_message_on_cursor(&self) -> Option<&str> {
-        let line = self.core.cursor().row;
-        self.last_compiler_result.as_ref().and_then(|res| {
-            res.messages
+    /// Move the cursor to the next match of `search` with wrapping around. Returns false if there is none.
+    pub fn find_next(&mut self) -> bool {
+        if self.search.is_empty() {
+            return false;
+        }
+        let search = self.search.iter().collect::<String>();
+        let ac = aho_corasick::AhoCorasick::new(vec![search]);
+
+        let pos = self
+            .core
+            .next_cursor(self.core.cursor())
+            .unwrap_or(Cursor { row: 0, col: 0 });
+
+        let pos_bytes = if let Some(Ok(m)) = ac
+            .stream_find_iter(iter_read::IterRead::new(
+                self.core.core_buffer().bytes_range(pos..),
+            ))
+            .next()
+        {
+            Some(self.core.core_buffer().cursor_to_bytes(pos) + m.start())
+        } else if let Some(Ok(m)) = ac
+            .stream_find_iter(iter_read::IterRead::new(
+                self.core.core_buffer().bytes_range(..pos),
+            ))
+            .next()
+        {
+            Some(m.start())
+        } else {
+            None
+        };
+
+        if let Some(start) = pos_bytes {
+            self.core
+                .set_cursor(self.core.core_buffer().bytes_to_cursor(start));
+            self.show_cursor();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn row_offset(&self) -> usize {
+        self.row_offset
+    }
+
+    /// Restore the cursor and the scroll position saved in a session
+    pub fn restore_view(&mut self, cursor: Cursor, row_offset: usize) {
+        let core_buffer = self.core.core_buffer();
+        let row = min(cursor.row, core_buffer.len_lines() - 1);
+        let col = min(cursor.col, core_buffer.len_line(row));
+        self.core.set_cursor(Cursor { row, col });
+        self.row_offset = min(row_offset, row);
+    }
+
+    /// Move the cursor to `cursor` clamped into the buffer
+    pub fn jump_to(&mut self, cursor: Cursor) {
+        let core_buffer = self.core.core_buffer();
+        let row = min(cursor.row, core_buffer.len_lines() - 1);
+        let col = min(cursor.col, core_buffer.len_line(row));
+        self.core.set_cursor(Cursor { row, col });
+        self.show_cursor();
+    }
+
+    /// Move to the next (or previous) diagnostic with wrapping around. Returns false if there is none.
+    pub fn jump_next_diagnostic(&mut self, forward: bool) -> bool {
+        let cursor = self.core.cursor();
+        let positions = self
+            .diagnostics()
+            .into_iter()
+            .map(|r| *r.span.start())
+            .collect::<Vec<_>>();
+        let target = if forward {
+            positions
+                .iter()
+                .find(|&&p| p > cursor)
+                .or_else(|| positions.first())
+        } else {
+            positions
                 .iter()
-                .find(|r| r.line == line)
-                .map(|r| r.message.as_str())
-        })
+                .rev()
+                .find(|&&p| p < cursor)
+                .or_else(|| positions.last())
+        };
+        if let Some(&target) = target {
+            self.jump_to(target);
+            true
+        } else {
+            false
+        }
     }
 
-    pub fn poll_compile_message(&mut self) {
-        if let Some(compiler) = self.compiler.as_mut() {
-            while let Some((id, res)) = compiler.try_recv_compile_result() {
-                self.last_compiler_compiled = id;
-                self.last_compiler_result = Some(res);
+    // Suggestions point to positions of the compiled text
+    fn fresh_compiler_result(&self) -> Result<&CompileResult, Cow<'static, str>> {
+        let res = self
+            .last_compiler_result
+            .as_ref()
+            .ok_or(Cow::Borrowed("No compiler messages"))?;
+        if self.last_compiler_compiled.id != self.core.buffer_changed() {
+            return Err(Cow::Borrowed(
+                "Compiler messages are outdated. Recompile first",
+            ));
+        }
+        Ok(res)
+    }
+
+    /// Apply the first suggestion of the compiler message under (or on the line of) the cursor
+    pub fn apply_suggestion_on_cursor(&mut self) -> Result<String, Cow<'static, str>> {
+        let cursor = self.core.cursor();
+        let res = self.fresh_compiler_result()?;
+        let candidates = res
+            .messages
+            .iter()
+            .filter(|r| !r.suggestions.is_empty())
+            .collect::<Vec<_>>();
+        let suggestion = candidates
+            .iter()
+            .find(|r| r.span.contains(&cursor))
+            .or_else(|| candidates.iter().find(|r| r.line == cursor.row))
+            .map(|r| r.suggestions[0].clone())
+            .ok_or(Cow::Borrowed("No suggestion on cursor"))?;
+        self.core.apply_edits(suggestion.edits);
+        self.core.commit();
+        Ok(suggestion.message)
+    }
+
+    /// Apply all machine applicable suggestions. Edits overlapping with another one are skipped.
+    pub fn apply_machine_applicable_suggestions(&mut self) -> Result<usize, Cow<'static, str>> {
+        let res = self.fresh_compiler_result()?;
+        let mut applied = 0;
+        let mut edits: Vec<TextEdit> = Vec::new();
+        for suggestion in res
+            .messages
+            .iter()
+            .flat_map(|r| r.suggestions.iter())
+            .filter(|s| s.machine_applicable)
+        {
+            let overlaps = suggestion.edits.iter().any(|e| {
+                edits
+                    .iter()
+                    .any(|x| e.range.start < x.range.end && x.range.start < e.range.end)
+                    || edits.iter().any(|x| x.range == e.range)
+            });
+            if !overlaps {
+                edits.extend(suggestion.edits.iter().cloned());
+                applied += 1;
             }
         }
+        if !edits.is_empty() {
+            self.core.apply_edits(edits);
+            self.core.commit();
+        }
+        Ok(applied)
+    }
+
+    fn is_own_file(&self, output: &CompilerOutput) -> bool {
+        match (output.file.as_ref(), self.path()) {
+            (None, _) => true,
+            (Some(file), Some(path)) => same_file(file, path),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Show the result of a compilation. Messages for other files are kept to pass to their buffers.
+    pub fn receive_compile_result(&mut self, id: CompileId, mut res: CompileResult) {
+        let (own, foreign) = res
+            .messages
+            .drain(..)
+            .partition::<Vec<_>, _>(|r| self.is_own_file(r));
+        res.messages = own;
+        if let Some(path) = self.path() {
+            // Pass an empty list too to clear messages of the previous compilation
+            self.foreign_compiler_messages = Some((canonicalize(path), foreign));
+        }
+        self.last_compiler_compiled = id;
+        self.last_compiler_result = Some(res);
+        self.runtime_messages.clear();
+        self.update_compiler_messages();
+    }
+
+    fn update_compiler_messages(&mut self) {
+        self.compiler_messages = self
+            .last_compiler_result
+            .iter()
+            .flat_map(|res| res.messages.iter())
+            .chain(self.external_compiler_messages.values().flatten())
+            .chain(self.runtime_messages.iter())
+            .cloned()
+            .collect();
+    }
+
+    /// Show reports of a run for this file (e.g. sanitizer errors) as diagnostics.
+    /// Returns the number of them.
+    pub fn set_runtime_messages(&mut self, messages: Vec<CompilerOutput>) -> usize {
+        self.runtime_messages = messages
+            .into_iter()
+            .filter(|r| r.file.is_some() && self.is_own_file(r))
+            .collect();
+        self.update_compiler_messages();
+        self.runtime_messages.len()
+    }
+
+    /// Messages of the last compilation for other files with the path of this buffer
+    pub fn take_foreign_compiler_messages(&mut self) -> Option<(PathBuf, Vec<CompilerOutput>)> {
+        self.foreign_compiler_messages.take()
+    }
+
+    /// Replace messages from the compilation of `source` with ones for this file in `messages`
+    pub fn set_external_compiler_messages(&mut self, source: &Path, messages: &[CompilerOutput]) {
+        if self.path().map(canonicalize).as_deref() == Some(source) {
+            return;
+        }
+        let messages = messages
+            .iter()
+            .filter(|r| r.file.is_some() && self.is_own_file(r))
+            .cloned()
+            .collect::<Vec<_>>();
+        let changed = if messages.is_empty() {
+            self.external_compiler_messages.remove(source).is_some()
+        } else {
+            self.external_compiler_messages
+                .insert(source.to_path_buf(), messages);
+            true
+        };
+        if changed {
+            self.update_compiler_messages();
+        }
+    }
+
+    pub fn poll_compile_message(&mut self) {
+        while let Some((id, res)) = self
+            .compiler
+            .as_mut()
+            .and_then(Compiler::try_recv_compile_result)
+        {
+            self.receive_compile_result(id, res);
+        }
     }
 
     pub async fn wait_compile_message(&mut self) {
         while self.is_compiling() {
             if let Some(compiler) = self.compiler.as_mut() {
                 if let Some((id, res)) = compiler.recv_compile_result().await {
-                    self.last_compiler_compiled = id;
-                    self.last_compiler_result = Some(res);
+                    self.receive_compile_result(id, res);
                 }
             }
         }
     }
 
+    /// Elapsed time of the running compilation
+    pub fn compile_elapsed(&self) -> Option<Duration> {
+        self.compiler
+            .as_ref()
+            .and_then(Compiler::running_since)
+            .map(|since| since.elapsed())
+    }
+
     pub fn is_compiling(&self) -> bool {
         self.compiler
             .as_ref()
@@ -383,8 +1052,40 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
             .unwrap_or(false)
     }
 
+    fn poll_lsp_decoration(&mut self) {
+        if let Some(lsp) = self.lsp.as_mut() {
+            if let Some(tokens) = lsp.poll_semantic_tokens() {
+                let highlighter = syntect::highlighting::Highlighter::new(&self.syntax.theme);
+                self.cache.set_semantic_tokens(&tokens, &highlighter);
+            }
+            if let Some(hints) = lsp.poll_inlay_hints() {
+                self.inlay_hints.clear();
+                for hint in hints {
+                    self.inlay_hints
+                        .entry(hint.cursor)
+                        .or_default()
+                        .push_str(&hint.label);
+                }
+            }
+
+            let changed = self.core.buffer_changed();
+            if self.lsp_decoration != Some(changed) {
+                match self.lsp_decoration_changed {
+                    Some((id, at)) if id == changed => {
+                        if at.elapsed() >= LSP_DECORATION_DELAY {
+                            lsp.request_decoration(self.core.get_string());
+                            self.lsp_decoration = Some(changed);
+                        }
+                    }
+                    _ => self.lsp_decoration_changed = Some((changed, Instant::now())),
+                }
+            }
+        }
+    }
+
     pub fn draw(&mut self, view: TermView) -> Option<Cursor> {
         self.poll_compile_message();
+        self.poll_lsp_decoration();
         self.draw_with_selected::<RangeInclusive<Cursor>>(view, None)
     }
 
@@ -405,20 +1106,31 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
         let highlighter = syntect::highlighting::Highlighter::new(&self.syntax.theme);
         self.show_cursor_on_draw = ShowCursor::None;
         view.bg = self.syntax.theme.settings.background.map(Into::into);
-        let v = Vec::new();
-        let compiler_outputs = self
-            .last_compiler_result
-            .as_ref()
-            .map(|res| &res.messages)
-            .unwrap_or_else(|| &v);
         let mut view = LinenumView::new(
             self.row_offset,
             self.core.core_buffer().len_lines(),
-            &compiler_outputs,
+            &self.compiler_messages,
             view,
         );
         let mut cursor = None;
         let tab_size = self.indent_width();
+        let inlay_style = CharStyle::fg_bg(
+            styles::UI.fg,
+            self.syntax
+                .theme
+                .settings
+                .background
+                .map(Into::into)
+                .unwrap_or(styles::UI.bg),
+        );
+        let bg_style = self
+            .syntax
+            .theme
+            .settings
+            .background
+            .map(|col| CharStyle::bg(col.into()))
+            .unwrap_or(styles::DEFAULT);
+        let labels = self.diagnostic_labels();
 
         if self.buffer_update != self.core.buffer_changed() {
             self.buffer_update = self.core.buffer_changed();
@@ -462,6 +1174,14 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
                     style
                 };
 
+                if let Some(label) = self.inlay_hints.get(&t) {
+                    for c in label.chars() {
+                        if view.put(c, inlay_style, None).is_none() {
+                            break 'outer;
+                        }
+                    }
+                }
+
                 if c == '\t' {
                     if self.core.cursor() == t {
                         cursor = view.put(' ', style, Some(t));
@@ -494,6 +1214,14 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
                 cursor = view.cursor();
             }
 
+            if let Some(label) = self.inlay_hints.get(&t) {
+                for c in label.chars() {
+                    if view.put(c, inlay_style, None).is_none() {
+                        break 'outer;
+                    }
+                }
+            }
+
             if self.core.core_buffer().len_line(i) == 0 {
                 if let Some(col) = self.syntax.theme.settings.background {
                     view.put(' ', CharStyle::bg(col.into()), Some(t));
@@ -502,6 +1230,54 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
                 }
             }
 
+            // Labels of compiler messages on virtual rows under the line
+            for label in labels.get(&i).into_iter().flatten() {
+                while !view.cause_newline(' ') {
+                    view.put(' ', bg_style, Some(t));
+                }
+                view.virtual_newline();
+                if view.cursor().is_none() {
+                    break 'outer;
+                }
+                let indent = self
+                    .core
+                    .get_string_range(
+                        Cursor { row: i, col: 0 }..Cursor {
+                            row: i,
+                            col: min(label.span.start().col, self.core.core_buffer().len_line(i)),
+                        },
+                    )
+                    .chars()
+                    .map(|c| {
+                        if c == '\t' {
+                            tab_size
+                        } else {
+                            c.width().unwrap_or(0)
+                        }
+                    })
+                    .sum::<usize>();
+                for _ in 0..indent {
+                    if view.cause_newline(' ') {
+                        break;
+                    }
+                    view.put(' ', bg_style, Some(t));
+                }
+                let (marker, style) = if label.is_primary {
+                    ('^', styles::HIGHLIGHT)
+                } else {
+                    ('-', inlay_style)
+                };
+                for c in std::iter::once(marker)
+                    .chain(std::iter::once(' '))
+                    .chain(label.label.chars())
+                {
+                    if view.cause_newline(c) {
+                        break;
+                    }
+                    view.put(c, style, Some(t));
+                }
+            }
+
             if i != self.core.core_buffer().len_lines() - 1 {
                 if let Some(col) = self.syntax.theme.settings.background {
                     while !view.cause_newline(' ') {
@@ -519,3 +1295,140 @@ impl<'a, B: CoreBuffer> Buffer<'a, B> {
         cursor
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Buffer, DiskChange, DISK_CHECK_INTERVAL};
+    use crate::compiler::{
+        CompileId, CompileProfile, CompileResult, CompilerOutput, DiagnosticFilter,
+    };
+    use crate::config::ConfigWithDefault;
+    use crate::core::buffer::RopeyCoreBuffer;
+    use crate::core::Cursor;
+    use crate::file_format::LineEnding;
+    use crate::syntax::SyntaxParent;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::time::Instant;
+
+    fn poll(buf: &mut Buffer<RopeyCoreBuffer>) -> Option<DiskChange> {
+        buf.last_disk_check = Instant::now() - DISK_CHECK_INTERVAL;
+        buf.poll_disk_change()
+    }
+
+    fn message(row: usize, level: &str) -> CompilerOutput {
+        CompilerOutput {
+            file: None,
+            message: level.to_string(),
+            line: row,
+            level: level.to_string(),
+            span: Cursor { row, col: 1 }..=Cursor { row, col: 1 },
+            suggestions: Vec::new(),
+            labels: Vec::new(),
+            children: Vec::new(),
+            rendered: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jump_next_diagnostic() {
+        let syntax_parent = SyntaxParent::default();
+        let config = ConfigWithDefault::default();
+        let mut buf: Buffer<RopeyCoreBuffer> = Buffer::new(&syntax_parent, &config);
+        buf.core.set_string("abc\n".repeat(10), true);
+        let messages = vec![
+            message(7, "error"),
+            message(1, "warning"),
+            message(5, "note"),
+            message(3, "error"),
+        ];
+        buf.receive_compile_result(
+            CompileId::default(),
+            CompileResult {
+                success: false,
+                messages,
+            },
+        );
+
+        use DiagnosticFilter::{All, Error, Warning};
+        // (filter, cursor, forward, row of the target)
+        let table = [
+            (All, (0, 0), true, Some(1)),
+            (All, (1, 1), true, Some(3)),
+            (All, (3, 1), true, Some(5)),
+            // Before the message on the same line
+            (All, (5, 0), true, Some(5)),
+            (All, (5, 0), false, Some(3)),
+            // Wrapping around
+            (All, (8, 0), true, Some(1)),
+            (All, (1, 1), false, Some(7)),
+            (Warning, (3, 1), true, Some(7)),
+            (Warning, (7, 1), true, Some(1)),
+            (Error, (0, 0), true, Some(3)),
+            (Error, (3, 1), false, Some(7)),
+            (Error, (9, 0), false, Some(7)),
+        ];
+        for &(filter, (row, col), forward, expected) in table.iter() {
+            buf.diagnostic_filter = filter;
+            buf.core.set_cursor(Cursor { row, col });
+            let jumped = buf.jump_next_diagnostic(forward);
+            assert_eq!(
+                jumped.then(|| buf.core.cursor()),
+                expected.map(|row| Cursor { row, col: 1 }),
+                "{:?} {:?} {}",
+                filter,
+                (row, col),
+                forward
+            );
+        }
+
+        buf.receive_compile_result(CompileId::default(), CompileResult::default());
+        assert!(!buf.jump_next_diagnostic(true));
+    }
+
+    #[tokio::test]
+    async fn test_disk_change() {
+        let dir = TempDir::new("disk");
+        let path = dir.join("a.txt");
+        fs::write(&path, "a\n").unwrap();
+
+        let syntax_parent = SyntaxParent::default();
+        let config = ConfigWithDefault::default();
+        let mut buf: Buffer<RopeyCoreBuffer> = Buffer::new(&syntax_parent, &config);
+        buf.open(path.clone()).unwrap();
+        assert!(poll(&mut buf).is_none());
+
+        // Reloaded without modification
+        fs::write(&path, "bb\n").unwrap();
+        assert!(matches!(poll(&mut buf), Some(DiskChange::Reloaded)));
+        assert_eq!(buf.core.get_string(), "bb\n");
+        assert!(!buf.is_modified());
+
+        // Both are modified
+        buf.core.insert('x');
+        fs::write(&path, "ccc\n").unwrap();
+        assert!(matches!(poll(&mut buf), Some(DiskChange::Conflict)));
+        assert!(buf.save(CompileProfile::Normal).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ccc\n");
+
+        // Keep the buffer
+        assert!(poll(&mut buf).is_none());
+        buf.overwrite(CompileProfile::Normal).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "xbb\n");
+        assert!(poll(&mut buf).is_none());
+
+        // A pending conversion is a modification
+        buf.format.line_ending = LineEnding::CrLf;
+        assert!(buf.is_modified());
+        fs::write(&path, "d\n").unwrap();
+        assert!(matches!(poll(&mut buf), Some(DiskChange::Conflict)));
+        buf.format.line_ending = LineEnding::Lf;
+        assert!(!buf.is_modified());
+
+        // Invalid bytes
+        fs::write(&path, b"\xff\xfea\x00b").unwrap();
+        buf.open(path.clone()).unwrap();
+        assert!(buf.format.invalid_bytes);
+        assert!(buf.core.is_read_only());
+    }
+}
@@ -1,8 +1,12 @@
-use crate::buffer::Buffer;
+use crate::buffer::{Buffer, DiskChange};
 use crate::core::CoreBuffer;
+use crate::core::Cursor;
 use crate::draw;
-use crate::mode::{Mode, Normal, Transition, TransitionReturn};
+use crate::mode::{
+    DiskChangePrompt, FileTree, Mode, Normal, RecoveryPicker, Transition, TransitionReturn,
+};
 use futures::future::{FutureExt, LocalBoxFuture};
+use std::path::PathBuf;
 
 pub struct BufferMode<'a, B: CoreBuffer> {
     pub buf: Buffer<'a, B>,
@@ -15,9 +19,11 @@ pub struct BufferMode<'a, B: CoreBuffer> {
 pub enum TabOperation {
     Nothing,
     Close,
+    CloseAll,
     NewTab,
     ChangeTab(usize),
     StartRmate,
+    OpenFile(PathBuf, Option<Cursor>),
 }
 
 impl<'a, B: CoreBuffer> BufferMode<'a, B> {
@@ -36,10 +42,19 @@ impl<'a, B: CoreBuffer> BufferMode<'a, B> {
             if self.is_recording {
                 self.recording_macro.push(event.clone());
             }
-            match self.mode.event(&mut self.buf, event.clone()).await {
+            let transition = self.mode.event(&mut self.buf, event.clone()).await;
+            if self.buf.core.take_rejected() {
+                self.is_recording = false;
+                self.show_message("The buffer is read-only".to_string());
+                return TabOperation::Nothing;
+            }
+            match transition {
                 Transition::Exit => {
                     return TabOperation::Close;
                 }
+                Transition::ExitAll => {
+                    return TabOperation::CloseAll;
+                }
                 Transition::Trans(mut t) => {
                     t.init(&mut self.buf);
                     self.mode = t;
@@ -86,6 +101,10 @@ impl<'a, B: CoreBuffer> BufferMode<'a, B> {
                     self.mode = Box::new(Normal::default());
                     return TabOperation::StartRmate;
                 }
+                Transition::OpenFile(path, cursor) => {
+                    self.mode = Box::new(Normal::default());
+                    return TabOperation::OpenFile(path, cursor);
+                }
                 Transition::Nothing => {}
             }
             TabOperation::Nothing
@@ -93,6 +112,27 @@ impl<'a, B: CoreBuffer> BufferMode<'a, B> {
         .boxed_local()
     }
 
+    pub fn offer_recovery(&mut self) {
+        if let Some(mut picker) = RecoveryPicker::find() {
+            Mode::<B>::init(&mut picker, &mut self.buf);
+            self.mode = Box::new(picker);
+        }
+    }
+
+    /// Show the file tree of `dir`
+    pub fn browse(&mut self, dir: PathBuf) {
+        let mut t: Box<dyn Mode<B>> = Box::new(FileTree::new(dir));
+        t.init(&mut self.buf);
+        self.mode = t;
+    }
+
+    /// Show a message in the footer
+    pub fn show_message(&mut self, message: String) {
+        let mut t = Box::new(Normal::with_message(message));
+        t.init(&mut self.buf);
+        self.mode = t;
+    }
+
     pub fn draw(&mut self, view: draw::TermView) -> draw::CursorState {
         self.mode.draw(&mut self.buf, view)
     }
@@ -100,5 +140,21 @@ impl<'a, B: CoreBuffer> BufferMode<'a, B> {
     /// This method should be called every frame
     pub fn background_task_duration(&mut self, duration: std::time::Duration) {
         self.buf.extend_cache_duration(duration);
+        // Reloading or prompting in the middle of typing or selecting would hand the keys to the
+        // prompt. A change is noticed on the next return to Normal instead.
+        if !self.mode.is_normal() {
+            return;
+        }
+        match self.buf.poll_disk_change() {
+            Some(DiskChange::Reloaded) => {
+                self.show_message("Reloaded (changed on disk)".to_string());
+            }
+            Some(DiskChange::Conflict) => {
+                let mut t: Box<dyn Mode<B>> = Box::new(DiskChangePrompt::default());
+                t.init(&mut self.buf);
+                self.mode = t;
+            }
+            None => {}
+        }
     }
 }
@@ -1,22 +1,32 @@
+use crate::buffer::same_file;
 use crate::buffer::Buffer;
 use crate::buffer_mode::BufferMode;
 use crate::buffer_mode::TabOperation;
+use crate::compiler::CompilerOutput;
 use crate::config::ConfigWithDefault;
 use crate::core::CoreBuffer;
+use crate::core::Cursor;
 use crate::draw;
 use crate::draw::CharStyle;
+use crate::lsp::LSPPool;
 use crate::rmate::{start_server, RmateSave, RmateStorage};
+use crate::session::{self, Session};
 use crate::storage::Storage;
 use crate::syntax::SyntaxParent;
 use std::cmp::min;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
 use unicode_width::UnicodeWidthChar;
 
 use termion::event::Event;
 use termion::event::MouseButton;
 use termion::event::MouseEvent;
 
+const SESSION_INTERVAL: Duration = Duration::from_secs(5);
+
 struct TabLine {
     buf: Vec<Option<(char, CharStyle)>>,
     tab: Vec<Option<usize>>,
@@ -60,31 +70,210 @@ pub struct BufferTab<'a, B: CoreBuffer> {
     buffers: Vec<BufferMode<'a, B>>,
     index: usize,
     rmate: Option<mpsc::Receiver<RmateSave>>,
+    // Open new buffers read-only
+    read_only: bool,
+    // Saved as tabs change and now and then
+    session_path: Option<PathBuf>,
+    saved_session: Option<Session>,
+    last_session_save: Instant,
+    // Language servers shared among buffers
+    lsp_pool: LSPPool,
+    // Last compiler messages for other files of each compiled file. Applied to buffers opened later.
+    routed_compiler_messages: BTreeMap<PathBuf, Vec<CompilerOutput>>,
 }
 
 impl<'a, B: CoreBuffer> BufferTab<'a, B> {
     pub fn new(syntax_parent: &'a SyntaxParent, config: &'a ConfigWithDefault) -> Self {
+        let lsp_pool = LSPPool::default();
         Self {
             syntax_parent,
             config,
-            buffers: vec![BufferMode::new(Buffer::new(syntax_parent, config))],
+            buffers: vec![BufferMode::new(Buffer::with_lsp_pool(
+                syntax_parent,
+                config,
+                lsp_pool.clone(),
+            ))],
             index: 0,
             rmate: None,
+            read_only: false,
+            session_path: None,
+            saved_session: None,
+            last_session_save: Instant::now(),
+            lsp_pool,
+            routed_compiler_messages: BTreeMap::new(),
         }
     }
 
     fn new_buffer_mode(&self) -> BufferMode<'a, B> {
-        BufferMode::new(Buffer::new(self.syntax_parent, self.config))
+        BufferMode::new(Buffer::with_lsp_pool(
+            self.syntax_parent,
+            self.config,
+            self.lsp_pool.clone(),
+        ))
+    }
+
+    /// Open files read-only from now on
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Keep the tabs saved to `path`
+    pub fn set_session_path(&mut self, path: &Path) {
+        self.session_path = Some(path.to_path_buf());
+    }
+
+    // Write the session if it changed. Keep the last session if no file is open.
+    fn save_session(&mut self) {
+        if let Some(path) = self.session_path.as_ref() {
+            self.last_session_save = Instant::now();
+            let session = self.session();
+            if session.tabs.is_empty() || self.saved_session.as_ref() == Some(&session) {
+                return;
+            }
+            if session.save(path).is_ok() {
+                self.saved_session = Some(session);
+            }
+        }
     }
 
-    pub fn open<S: Storage<B> + 'static>(&mut self, s: S) {
+    /// Save cursor positions of the session now and then so that it survives a crash
+    pub fn poll_session(&mut self) {
+        if self.last_session_save.elapsed() >= SESSION_INTERVAL {
+            self.save_session();
+        }
+    }
+
+    /// Shut down language servers gracefully
+    pub async fn shutdown(&mut self) {
+        self.lsp_pool.shutdown().await;
+    }
+
+    /// Open a new tab. The reason of a failure is shown in the current tab.
+    pub fn open<S: Storage<B> + 'static>(&mut self, s: S) -> bool {
+        let path = s.path().display().to_string();
+        let mut buffer_mode = self.new_buffer_mode();
+        buffer_mode.buf.core.set_read_only(self.read_only);
+        if let Err(err) = buffer_mode.buf.open(s) {
+            self.buffer_mode_mut()
+                .show_message(format!("Failed to open {}: {}", path, err));
+            return false;
+        }
+        if buffer_mode.buf.format.invalid_bytes {
+            buffer_mode.show_message(format!(
+                "Opened read-only: {} has bytes invalid in {}. SPACE R to edit anyway.",
+                path,
+                buffer_mode.buf.format.encoding.name()
+            ));
+        }
+        for (source, messages) in &self.routed_compiler_messages {
+            buffer_mode
+                .buf
+                .set_external_compiler_messages(source, messages);
+        }
+
         if self.is_empty() {
             self.buffers.clear();
         }
+        self.buffers.push(buffer_mode);
+        true
+    }
+
+    /// Switch to the tab of the file or open it in a new tab
+    pub fn switch_or_open<S: Storage<B> + 'static>(&mut self, s: S) -> bool {
+        if let Some(i) = self
+            .buffers
+            .iter()
+            .position(|b| b.buf.path().map(|p| same_file(p, s.path())) == Some(true))
+        {
+            self.index = i;
+        } else if self.open(s) {
+            self.index = self.buffers.len() - 1;
+        } else {
+            return false;
+        }
+        true
+    }
 
+    /// Switch to the tab of the file or open it, and move the cursor to `cursor`
+    pub fn open_at<S: Storage<B> + 'static>(&mut self, s: S, cursor: Cursor) -> bool {
+        if !self.switch_or_open(s) {
+            return false;
+        }
+        self.buffer_mode_mut().buf.jump_to(cursor);
+        true
+    }
+
+    /// Open a new tab of an unnamed buffer with `text` and switch to it
+    pub fn open_text(&mut self, text: String, cursor: Option<Cursor>) {
         let mut buffer_mode = self.new_buffer_mode();
-        buffer_mode.buf.open(s);
+        buffer_mode.buf.core.set_string(text, true);
+        buffer_mode.buf.core.set_read_only(self.read_only);
+        if let Some(cursor) = cursor {
+            buffer_mode.buf.jump_to(cursor);
+        }
+        if self.is_empty() {
+            self.buffers.clear();
+        }
         self.buffers.push(buffer_mode);
+        self.index = self.buffers.len() - 1;
+    }
+
+    /// Tabs of files on disk with their cursors and scroll positions
+    pub fn session(&self) -> Session {
+        let mut session = Session::default();
+        for (i, buffer_mode) in self.buffers.iter().enumerate() {
+            let buf = &buffer_mode.buf;
+            let path = match buf.storage() {
+                Some(storage) if storage.stamp().is_some() => storage.path(),
+                _ => continue,
+            };
+            if i == self.index {
+                session.index = session.tabs.len();
+            }
+            let cursor = buf.core.cursor();
+            session.tabs.push(session::Tab {
+                path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+                cursor: (cursor.row, cursor.col),
+                row_offset: buf.row_offset(),
+                search: buf.search.iter().collect(),
+            });
+        }
+        session
+    }
+
+    /// Open the tabs of a session. Files that fail to open are skipped.
+    pub fn restore_session(&mut self, session: Session) {
+        let mut index = None;
+        for (i, tab) in session.tabs.into_iter().enumerate() {
+            if !self.open(tab.path) {
+                continue;
+            }
+            if i == session.index {
+                index = Some(self.buffers.len() - 1);
+            }
+            let buf = &mut self.buffers.last_mut().unwrap().buf;
+            buf.restore_view(
+                Cursor {
+                    row: tab.cursor.0,
+                    col: tab.cursor.1,
+                },
+                tab.row_offset,
+            );
+            buf.search = tab.search.chars().collect();
+        }
+        if let Some(index) = index {
+            self.index = index;
+        }
+    }
+
+    /// Show the file tree of `dir` in the current tab
+    pub fn browse(&mut self, dir: PathBuf) {
+        self.buffer_mode_mut().browse(dir);
+    }
+
+    /// Show the recovery picker if editors that didn't exit normally left swap files
+    pub fn offer_recovery(&mut self) {
+        self.buffer_mode_mut().offer_recovery();
     }
 
     pub fn buffer_mode(&self) -> &BufferMode<'a, B> {
@@ -103,6 +292,8 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
     }
 
     pub async fn event(&mut self, event: Event) -> bool {
+        let tab_count = self.buffers.len();
+        let index = self.index;
         if let Event::Mouse(MouseEvent::Press(button, col, row)) = event {
             let (width, height) = termion::terminal_size().unwrap();
             if row == height {
@@ -115,7 +306,10 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
                             self.index = i;
                         }
                         MouseButton::Middle => {
-                            self.buffers.remove(i);
+                            if self.buffers.len() <= 1 {
+                                self.save_session();
+                            }
+                            self.buffers.remove(i).buf.close();
                             if self.buffers.is_empty() {
                                 return true;
                             } else {
@@ -130,6 +324,10 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
 
         match self.buffer_mode_mut().event(event).await {
             TabOperation::Close => {
+                if self.buffers.len() <= 1 {
+                    self.save_session();
+                }
+                self.buffer_mode_mut().buf.close();
                 if self.buffers.len() <= 1 {
                     return true;
                 } else {
@@ -137,11 +335,15 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
                     self.index = min(self.buffers.len() - 1, self.index);
                 }
             }
+            TabOperation::CloseAll => {
+                self.save_session();
+                for buffer_mode in self.buffers.iter_mut() {
+                    buffer_mode.buf.close();
+                }
+                return true;
+            }
             TabOperation::NewTab => {
-                self.buffers.push(BufferMode::new(Buffer::new(
-                    self.syntax_parent,
-                    self.config,
-                )));
+                self.buffers.push(self.new_buffer_mode());
                 self.index = self.buffers.len() - 1;
             }
             TabOperation::ChangeTab(i) => {
@@ -156,12 +358,40 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
                 });
                 self.rmate = Some(rx);
             }
+            TabOperation::OpenFile(path, Some(cursor)) => {
+                self.open_at(path, cursor);
+            }
+            TabOperation::OpenFile(path, None) => {
+                self.switch_or_open(path);
+            }
             TabOperation::Nothing => {}
         }
 
+        if self.buffers.len() != tab_count || self.index != index {
+            self.save_session();
+        }
         false
     }
 
+    // Pass compiler messages for other files to their buffers
+    fn route_compiler_messages(&mut self) {
+        let routes = self
+            .buffers
+            .iter_mut()
+            .filter_map(|b| b.buf.take_foreign_compiler_messages())
+            .collect::<Vec<_>>();
+        for (source, messages) in routes {
+            for b in &mut self.buffers {
+                b.buf.set_external_compiler_messages(&source, &messages);
+            }
+            if messages.is_empty() {
+                self.routed_compiler_messages.remove(&source);
+            } else {
+                self.routed_compiler_messages.insert(source, messages);
+            }
+        }
+    }
+
     fn draw_tab_line(&self, width: usize) -> TabLine {
         const TITLE_LEN: usize = 5;
         let mut footer = TabLine::new(width);
@@ -181,12 +411,20 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
             }
         };
 
+        let put_badge = |footer: &mut TabLine, i: usize| {
+            let errors = self.buffers[i].buf.error_count();
+            if errors > 0 {
+                footer.puts(&format!("!{}", errors), draw::styles::HIGHLIGHT, Some(i));
+            }
+        };
+
         if self.buffers.len() == 1 {
             footer.puts(
                 &format!(" {} {} ", 1, get_title(self.buffers[0].buf.path())),
                 draw::styles::TAB_BAR,
                 Some(0),
             );
+            put_badge(&mut footer, 0);
         } else {
             for i in 0..self.buffers.len() {
                 let title = get_title(self.buffers[i].buf.path());
@@ -225,6 +463,7 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
                         Some(i),
                     );
                 }
+                put_badge(&mut footer, i);
             }
         }
 
@@ -239,8 +478,9 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
                 match rmate.try_recv() {
                     Ok(rmate) => {
                         let rmate: RmateStorage = rmate.into();
-                        self.open(rmate);
-                        self.index = self.buffers.len() - 1;
+                        if self.open(rmate) {
+                            self.index = self.buffers.len() - 1;
+                        }
                     }
                     Err(mpsc::TryRecvError::Disconnected) => {
                         self.rmate = None;
@@ -254,6 +494,10 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
         let cursor =
             self.buffer_mode_mut()
                 .draw(view.view((0, 0), view.height() - 1, view.width()));
+        self.route_compiler_messages();
+        for b in &mut self.buffers {
+            b.buf.poll_swap();
+        }
         let mut footer = view.view((view.height() - 1, 0), 1, view.width());
         let tab_line = self.draw_tab_line(width);
 
@@ -266,3 +510,107 @@ impl<'a, B: CoreBuffer> BufferTab<'a, B> {
         cursor
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::BufferTab;
+    use crate::buffer::same_file;
+    use crate::compiler::{CompileId, CompileResult, CompilerOutput};
+    use crate::config::ConfigWithDefault;
+    use crate::core::buffer::RopeyCoreBuffer;
+    use crate::core::Cursor;
+    use crate::session::Session;
+    use crate::syntax::SyntaxParent;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::path::Path;
+    use termion::event::{Event, Key};
+
+    fn error(file: &Path) -> CompilerOutput {
+        CompilerOutput {
+            file: Some(file.to_path_buf()),
+            message: "error".to_string(),
+            line: 0,
+            level: "error".to_string(),
+            span: Cursor { row: 0, col: 0 }..=Cursor { row: 0, col: 0 },
+            suggestions: Vec::new(),
+            labels: Vec::new(),
+            children: Vec::new(),
+            rendered: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_compiler_messages() {
+        let dir = TempDir::new("route");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let a = dir.join("a.c");
+        let b = dir.join("b.c");
+        fs::write(&a, "#include \"b.c\"\n").unwrap();
+        fs::write(&b, "x\n").unwrap();
+        assert!(same_file(&dir.join("sub/../b.c"), &b));
+        assert!(!same_file(&a, &b));
+
+        let syntax_parent = SyntaxParent::default();
+        let config = ConfigWithDefault::default();
+        let mut tab: BufferTab<RopeyCoreBuffer> = BufferTab::new(&syntax_parent, &config);
+        tab.open(a.clone());
+        tab.open(b.clone());
+        let result = CompileResult {
+            success: false,
+            messages: vec![error(&dir.join("sub/../b.c"))],
+        };
+        tab.buffers[0]
+            .buf
+            .receive_compile_result(CompileId::default(), result);
+        tab.route_compiler_messages();
+        assert_eq!(tab.buffers[0].buf.error_count(), 0);
+        assert_eq!(tab.buffers[1].buf.error_count(), 1);
+
+        // Reopened buffers get the messages too
+        tab.buffers.remove(1);
+        tab.index = 0;
+        tab.open(b.clone());
+        assert_eq!(tab.buffers[1].buf.error_count(), 1);
+
+        // Cleared by the next compilation
+        tab.buffers[0]
+            .buf
+            .receive_compile_result(CompileId::default(), CompileResult::default());
+        tab.route_compiler_messages();
+        assert_eq!(tab.buffers[1].buf.error_count(), 0);
+        tab.open(b);
+        assert_eq!(tab.buffers[2].buf.error_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_session_saved_on_tab_change() {
+        let dir = TempDir::new("tab-session");
+        let a = dir.join("a.txt");
+        let b = dir.join("b.txt");
+        fs::write(&a, "a\n").unwrap();
+        fs::write(&b, "b\n").unwrap();
+        let session_path = dir.join("session.json");
+
+        let syntax_parent = SyntaxParent::default();
+        let config = ConfigWithDefault::default();
+        let mut tab: BufferTab<RopeyCoreBuffer> = BufferTab::new(&syntax_parent, &config);
+        tab.set_session_path(&session_path);
+        tab.switch_or_open(a);
+        tab.switch_or_open(b);
+        assert!(!session_path.exists());
+
+        // Switching tabs
+        tab.event(Event::Key(Key::Char('1'))).await;
+        let session = Session::load(&session_path).unwrap();
+        assert_eq!(session.tabs.len(), 2);
+        assert_eq!(session.index, 0);
+
+        // Closing a tab
+        tab.event(Event::Key(Key::Char(' '))).await;
+        tab.event(Event::Key(Key::Char('q'))).await;
+        let session = Session::load(&session_path).unwrap();
+        assert_eq!(session.tabs.len(), 1);
+        assert!(session.tabs[0].path.ends_with("b.txt"));
+    }
+}
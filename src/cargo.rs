@@ -0,0 +1,242 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_derive::Deserialize;
+
+use crate::compiler::CompilerOutput;
+use crate::config::types::Command;
+use crate::rustc;
+
+/// Cargo package containing a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CargoTarget {
+    pub manifest_path: PathBuf,
+    // Cargo prints file names relative to this
+    pub workspace_root: PathBuf,
+    // Binary built from the file if any
+    pub bin: Option<String>,
+    // Binary `cargo run` runs without `--bin` if any
+    pub default_bin: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    package: Option<Package>,
+    bin: Option<Vec<Bin>>,
+    workspace: Option<toml::Value>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    #[serde(rename = "default-run")]
+    default_run: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Bin {
+    name: String,
+    path: Option<String>,
+}
+
+fn load_manifest(path: &Path) -> Option<Manifest> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+// Same as cargo's target auto-discovery and [[bin]] sections
+fn bin_name(manifest: &Manifest, package_root: &Path, file: &Path) -> Option<String> {
+    let relative = file.strip_prefix(package_root).ok()?;
+    if let Some(bin) = manifest
+        .bin
+        .iter()
+        .flatten()
+        .find(|b| b.path.as_ref().map(|p| Path::new(p) == relative) == Some(true))
+    {
+        return Some(bin.name.clone());
+    }
+    let components = relative
+        .iter()
+        .map(|s| s.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    let components = components.iter().map(String::as_str).collect::<Vec<_>>();
+    match components.as_slice() {
+        ["src", "main.rs"] => manifest.package.as_ref().map(|p| p.name.clone()),
+        ["src", "bin", name] => Path::new(name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned()),
+        ["src", "bin", name, "main.rs"] => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+// `default-run` or the only binary of the package
+fn default_bin(manifest: &Manifest, package_root: &Path) -> Option<String> {
+    let package = manifest.package.as_ref()?;
+    if package.default_run.is_some() {
+        return package.default_run.clone();
+    }
+    let mut bins = manifest
+        .bin
+        .iter()
+        .flatten()
+        .map(|b| b.name.clone())
+        .collect::<Vec<_>>();
+    if package_root.join("src").join("main.rs").is_file() {
+        bins.push(package.name.clone());
+    }
+    for entry in fs::read_dir(package_root.join("src").join("bin"))
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let path = entry.path();
+        if path.extension().map(|e| e == "rs") == Some(true) || path.join("main.rs").is_file() {
+            bins.extend(path.file_stem().map(|s| s.to_string_lossy().into_owned()));
+        }
+    }
+    bins.sort();
+    bins.dedup();
+    if bins.len() == 1 {
+        bins.pop()
+    } else {
+        None
+    }
+}
+
+impl CargoTarget {
+    /// Find the Cargo package `file` belongs to
+    pub fn find(file: &Path) -> Option<Self> {
+        let file = file.canonicalize().ok()?;
+        let package_root = file
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("Cargo.toml").is_file())?;
+        let manifest_path = package_root.join("Cargo.toml");
+        let manifest = load_manifest(&manifest_path)?;
+        let workspace_root = package_root
+            .ancestors()
+            .find(|dir| {
+                load_manifest(&dir.join("Cargo.toml"))
+                    .map(|m| m.workspace.is_some())
+                    .unwrap_or(false)
+            })
+            .unwrap_or(package_root)
+            .to_path_buf();
+        let bin = bin_name(&manifest, package_root, &file);
+        let default_bin = default_bin(&manifest, package_root);
+
+        Some(Self {
+            manifest_path,
+            workspace_root,
+            bin,
+            default_bin,
+        })
+    }
+
+    fn command(&self, subcommand: &str, bin: Option<&String>, is_optimize: bool) -> Command {
+        let mut args = vec![
+            subcommand.to_string(),
+            "--manifest-path".to_string(),
+            self.manifest_path.to_string_lossy().into_owned(),
+        ];
+        if let Some(bin) = bin {
+            args.push("--bin".to_string());
+            args.push(bin.clone());
+        }
+        if is_optimize {
+            args.push("--release".to_string());
+        }
+        Command {
+            program: "cargo".to_string(),
+            args,
+        }
+    }
+
+    pub fn check_command(&self, is_optimize: bool) -> Command {
+        let mut command = self.command("check", self.bin.as_ref(), is_optimize);
+        command.args.push("--message-format=json".to_string());
+        command
+    }
+
+    /// None if cargo can't tell which binary to run
+    pub fn run_command(&self, is_optimize: bool) -> Option<Command> {
+        let bin = self.bin.as_ref().or(self.default_bin.as_ref())?;
+        let mut command = self.command("run", Some(bin), is_optimize);
+        command.args.insert(1, "-q".to_string());
+        Some(command)
+    }
+}
+
+#[derive(Deserialize)]
+struct Record {
+    reason: String,
+    message: Option<serde_json::Value>,
+}
+
+/// Parse a `compiler-message` record of `cargo --message-format=json`
+pub fn parse_cargo_json(json: &str, workspace_root: &Path) -> Option<CompilerOutput> {
+    let record: Record = serde_json::from_str(json).ok()?;
+    if record.reason != "compiler-message" {
+        return None;
+    }
+    let mut output = rustc::parse_rustc_value(record.message?)?;
+    output.resolve_paths(workspace_root);
+    Some(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bin_name, default_bin, parse_cargo_json, Manifest};
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_bin_name() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+[package]
+name = "abc"
+
+[[bin]]
+name = "a"
+path = "src/problems/a.rs"
+"#,
+        )
+        .unwrap();
+        let root = Path::new("/w");
+        let bin = |p: &str| bin_name(&manifest, root, Path::new(p));
+        assert_eq!(bin("/w/src/problems/a.rs"), Some("a".to_string()));
+        assert_eq!(bin("/w/src/main.rs"), Some("abc".to_string()));
+        assert_eq!(bin("/w/src/bin/b.rs"), Some("b".to_string()));
+        assert_eq!(bin("/w/src/bin/c/main.rs"), Some("c".to_string()));
+        assert_eq!(bin("/w/src/lib.rs"), None);
+    }
+
+    #[test]
+    fn test_default_bin() {
+        let dir = TempDir::new("cargo");
+        fs::create_dir_all(dir.join("src").join("bin")).unwrap();
+        fs::write(dir.join("src").join("main.rs"), "").unwrap();
+        let manifest: Manifest = toml::from_str("[package]\nname = \"abc\"\n").unwrap();
+        assert_eq!(default_bin(&manifest, &dir), Some("abc".to_string()));
+        fs::write(dir.join("src").join("bin").join("b.rs"), "").unwrap();
+        assert_eq!(default_bin(&manifest, &dir), None);
+        let manifest: Manifest =
+            toml::from_str("[package]\nname = \"abc\"\ndefault-run = \"b\"\n").unwrap();
+        assert_eq!(default_bin(&manifest, &dir), Some("b".to_string()));
+    }
+
+    #[test]
+    fn test_parse_cargo_json() {
+        let json = r#"{"reason":"compiler-message","package_id":"abc 0.1.0","manifest_path":"/w/abc/Cargo.toml","target":{},"message":{"message":"unused variable: `x`","code":null,"level":"warning","spans":[{"file_name":"abc/src/main.rs","byte_start":16,"byte_end":17,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":null}}"#;
+        let output = parse_cargo_json(json, Path::new("/w")).unwrap();
+        assert_eq!(output.file, Some(PathBuf::from("/w/abc/src/main.rs")));
+        assert_eq!(output.line, 1);
+        assert!(parse_cargo_json(
+            r#"{"reason":"build-finished","success":true}"#,
+            Path::new("/w")
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::core::Cursor;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum Source {
+    Path(PathBuf),
+    Stdin,
+}
+
+/// A file given on the command line with the position to open it at
+#[derive(PartialEq, Eq, Debug)]
+pub struct FileArg {
+    pub source: Source,
+    pub cursor: Option<Cursor>,
+}
+
+/// What `-c` does after opening files
+#[derive(PartialEq, Eq, Debug)]
+pub enum Command {
+    Search(String),
+    Line(usize),
+}
+
+impl Command {
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(pattern) = s.strip_prefix('/') {
+            Some(Command::Search(pattern.to_string()))
+        } else {
+            s.trim_start_matches(':').parse().ok().map(Command::Line)
+        }
+    }
+}
+
+// 1-origin line and column as printed by compilers
+fn cursor(line: usize, col: usize) -> Cursor {
+    Cursor {
+        row: line.saturating_sub(1),
+        col: col.saturating_sub(1),
+    }
+}
+
+// `+N` in front of a file as in vi. `+` alone is the last line.
+fn parse_line_option(arg: &str) -> Option<Cursor> {
+    let line = arg.strip_prefix('+')?;
+    if line.is_empty() {
+        return Some(cursor(usize::MAX, 1));
+    }
+    line.parse().ok().map(|line| cursor(line, 1))
+}
+
+// `path:line[:col][:]` unless a file with the whole name exists
+fn split_position(arg: &str) -> (PathBuf, Option<Cursor>) {
+    if Path::new(arg).exists() {
+        return (PathBuf::from(arg), None);
+    }
+    let mut numbers = Vec::new();
+    let mut rest = arg.strip_suffix(':').unwrap_or(arg);
+    while numbers.len() < 2 {
+        match rest.rsplit_once(':') {
+            Some((path, n)) if !path.is_empty() && n.parse::<usize>().is_ok() => {
+                numbers.push(n.parse::<usize>().unwrap());
+                rest = path;
+            }
+            _ => break,
+        }
+    }
+    match numbers.as_slice() {
+        [line] => (PathBuf::from(rest), Some(cursor(*line, 1))),
+        [col, line] => (PathBuf::from(rest), Some(cursor(*line, *col))),
+        _ => (PathBuf::from(arg), None),
+    }
+}
+
+/// Parse the file arguments. `-` is the standard input.
+pub fn parse_files<'a>(args: impl IntoIterator<Item = &'a OsStr>) -> Vec<FileArg> {
+    let mut files = Vec::new();
+    let mut next_cursor = None;
+    for arg in args {
+        let arg_str = arg.to_str();
+        if let Some(cursor) = arg_str.and_then(parse_line_option) {
+            next_cursor = Some(cursor);
+            continue;
+        }
+        let (source, cursor) = match arg_str {
+            Some("-") => (Source::Stdin, None),
+            Some(s) => {
+                let (path, cursor) = split_position(s);
+                (Source::Path(path), cursor)
+            }
+            None => (Source::Path(PathBuf::from(arg)), None),
+        };
+        files.push(FileArg {
+            source,
+            cursor: next_cursor.take().or(cursor),
+        });
+    }
+    files
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_files, Command, FileArg, Source};
+    use crate::core::Cursor;
+    use crate::temp_dir::TempDir;
+    use std::ffi::OsStr;
+    use std::path::PathBuf;
+
+    fn parse(args: &[&str]) -> Vec<FileArg> {
+        parse_files(args.iter().map(OsStr::new))
+    }
+
+    fn file(path: &str, position: Option<(usize, usize)>) -> FileArg {
+        FileArg {
+            source: Source::Path(PathBuf::from(path)),
+            cursor: position.map(|(row, col)| Cursor { row, col }),
+        }
+    }
+
+    #[test]
+    fn test_parse_files() {
+        assert_eq!(
+            parse(&["src/main.rs:42:7", "a.py:3", "b.c:10:2:", "c.rs"]),
+            vec![
+                file("src/main.rs", Some((41, 6))),
+                file("a.py", Some((2, 0))),
+                file("b.c", Some((9, 1))),
+                file("c.rs", None),
+            ]
+        );
+        assert_eq!(
+            parse(&["+42", "a.rs", "b.rs", "+", "c.rs"]),
+            vec![
+                file("a.rs", Some((41, 0))),
+                file("b.rs", None),
+                file("c.rs", Some((usize::MAX - 1, 0))),
+            ]
+        );
+        assert_eq!(
+            parse(&["+3", "-"]),
+            vec![FileArg {
+                source: Source::Stdin,
+                cursor: Some(Cursor { row: 2, col: 0 })
+            }]
+        );
+        assert_eq!(
+            parse(&["-", "a:b"]),
+            vec![
+                FileArg {
+                    source: Source::Stdin,
+                    cursor: None
+                },
+                file("a:b", None),
+            ]
+        );
+        // An existing file with a colon in its name is opened as is
+        let dir = TempDir::new("cli");
+        let colon = dir.join("x:1");
+        std::fs::write(&colon, "").unwrap();
+        assert_eq!(
+            parse(&[colon.to_str().unwrap()]),
+            vec![file(colon.to_str().unwrap(), None)]
+        );
+
+        assert_eq!(
+            Command::parse("/fn main"),
+            Some(Command::Search("fn main".to_string()))
+        );
+        assert_eq!(Command::parse(":12"), Some(Command::Line(12)));
+        assert_eq!(Command::parse("wq"), None);
+    }
+}
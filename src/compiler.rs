@@ -1,55 +1,254 @@
 use std::io;
 use std::io::BufRead;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
 
 use regex;
 
+use crate::cargo;
+use crate::cargo::CargoTarget;
+use crate::config::types::Command;
 use crate::config::types::CompilerConfig;
 use crate::config::types::CompilerType;
 use crate::core::Cursor;
 use crate::core::Id;
+use crate::core::TextEdit;
+use crate::cpp_project::CppProject;
 use crate::job_queue::JobQueue;
 use crate::rustc;
+use std::cell::RefCell;
 use std::ffi::OsString;
+use std::fmt;
 use std::ops::RangeInclusive;
+use std::time::Instant;
 
 use async_trait::async_trait;
 
 use futures::prelude::*;
+
+mod parsers;
+
+pub use parsers::{compile_regex, parse_sanitizer, LocationParser};
+
+#[derive(Debug, Clone)]
 pub struct CompilerOutput {
+    // File name as the compiler printed. None if unknown.
+    pub file: Option<PathBuf>,
     pub message: String,
     pub line: usize,
     pub level: String,
     pub span: RangeInclusive<Cursor>,
+    pub suggestions: Vec<Suggestion>,
+    // Primary and secondary spans with their labels
+    pub labels: Vec<SpanLabel>,
+    // Notes and helps attached to this message
+    pub children: Vec<CompilerOutput>,
+    // Full text as the compiler would print it
+    pub rendered: Option<String>,
+}
+
+/// Location related to a compiler message
+#[derive(Debug, Clone)]
+pub struct SpanLabel {
+    pub span: RangeInclusive<Cursor>,
+    pub label: String,
+    pub is_primary: bool,
+}
+
+/// Fix suggested by a compiler
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub message: String,
+    pub edits: Vec<TextEdit>,
+    // Safe to apply without user's check
+    pub machine_applicable: bool,
+}
+
+impl CompilerOutput {
+    // 2: error, 1: warning, 0: others (note, help, ...)
+    pub fn severity(&self) -> usize {
+        if self.level.contains("error") {
+            2
+        } else if self.level.contains("warning") {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Make relative file names relative to `base` (working directory of the compiler)
+    pub fn resolve_paths(&mut self, base: &Path) {
+        if let Some(file) = self.file.as_mut() {
+            *file = base.join(&file);
+        }
+        for child in &mut self.children {
+            child.resolve_paths(base);
+        }
+    }
+
+    /// Full text of this message. Made up from children if the compiler doesn't render it.
+    pub fn rendered(&self) -> String {
+        if let Some(rendered) = self.rendered.as_ref() {
+            return rendered.clone();
+        }
+        let mut res = format!("{}: {}\n", self.level, self.message);
+        for child in &self.children {
+            res.push_str(&format!("  = {}: {}\n", child.level, child.message));
+        }
+        res
+    }
+
+    /// Labels to draw under the source including spans of children
+    pub fn all_labels(&self) -> Vec<SpanLabel> {
+        let mut res = self.labels.clone();
+        for child in &self.children {
+            if child.file != self.file || (child.labels.is_empty() && child.span == self.span) {
+                continue;
+            }
+            res.push(SpanLabel {
+                span: child.span.clone(),
+                label: format!("{}: {}", child.level, child.message),
+                is_primary: false,
+            });
+        }
+        res
+    }
+}
+
+/// Minimum level of compiler messages to list and jump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiagnosticFilter {
+    #[default]
+    All,
+    Warning,
+    Error,
+}
+
+impl DiagnosticFilter {
+    pub fn next(self) -> Self {
+        match self {
+            DiagnosticFilter::All => DiagnosticFilter::Warning,
+            DiagnosticFilter::Warning => DiagnosticFilter::Error,
+            DiagnosticFilter::Error => DiagnosticFilter::All,
+        }
+    }
+
+    pub fn accept(self, output: &CompilerOutput) -> bool {
+        match self {
+            DiagnosticFilter::All => true,
+            DiagnosticFilter::Warning => output.severity() >= 1,
+            DiagnosticFilter::Error => output.severity() >= 2,
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticFilter::All => write!(f, "All"),
+            DiagnosticFilter::Warning => write!(f, "Warnings"),
+            DiagnosticFilter::Error => write!(f, "Errors"),
+        }
+    }
 }
 
 pub struct Compiler<'a> {
     config: &'a CompilerConfig,
     worker: Box<dyn CompilerWorker>,
+    cargo: Option<CargoTarget>,
+    cpp: RefCell<Option<CppProject>>,
 }
 
 impl<'a> Compiler<'a> {
-    pub fn new(config: &'a CompilerConfig) -> Self {
-        let worker: Box<dyn CompilerWorker> = match config.output_type {
-            None => Box::new(Unknown::default()),
-            Some(CompilerType::Gcc) => Box::new(Cpp::default()),
-            Some(CompilerType::Rustc) => Box::new(Rust::default()),
+    pub fn new(config: &'a CompilerConfig, path: Option<&Path>) -> Self {
+        let cargo = match config.output_type {
+            Some(CompilerType::Rustc) if config.cargo.unwrap_or(true) => {
+                path.and_then(CargoTarget::find)
+            }
+            _ => None,
+        };
+        let worker: Box<dyn CompilerWorker> = match (&cargo, &config.output_type) {
+            (Some(target), _) => Box::new(Cargo::new(target.workspace_root.clone())),
+            (None, None) => Box::new(Unknown::default()),
+            (None, Some(CompilerType::Gcc)) => Box::new(Cpp::default()),
+            (None, Some(CompilerType::Rustc)) => Box::new(Rust::default()),
+            (None, Some(CompilerType::Python)) => Box::new(Generic::new(parsers::parse_python)),
+            (None, Some(CompilerType::Go)) => Box::new(Generic::with_regex(parsers::GO)),
+            (None, Some(CompilerType::Java)) => Box::new(Generic::with_regex(parsers::JAVA)),
+            (None, Some(CompilerType::Kotlin)) => Box::new(Generic::with_regex(parsers::KOTLIN)),
+            (None, Some(CompilerType::Haskell)) => Box::new(Generic::with_regex(parsers::HASKELL)),
+            // The pattern was checked when the config was loaded
+            (None, Some(CompilerType::Regex(pattern))) => match parsers::compile_regex(pattern) {
+                Ok(re) => Box::new(Generic::new(move |s: &str| parsers::parse_regex(&re, s))),
+                Err(_) => Box::new(Unknown::default()),
+            },
+        };
+
+        let cpp = match config.output_type {
+            Some(CompilerType::Gcc) if config.compile_commands.unwrap_or(true) => {
+                path.and_then(CppProject::find)
+            }
+            _ => None,
         };
 
-        Self { config, worker }
+        Self {
+            config,
+            worker,
+            cargo,
+            cpp: RefCell::new(cpp),
+        }
+    }
+
+    // Found again until CMake configures the project
+    fn cpp_project(&self, path: &Path) -> Option<CppProject> {
+        let mut cpp = self.cpp.borrow_mut();
+        if cpp.as_ref().is_some_and(|project| !project.is_configured()) {
+            *cpp = CppProject::find(path);
+        }
+        cpp.clone()
+    }
+
+    /// Command to run the program in a Cargo package or a C / C++ project
+    pub fn run_command(&self, path: &Path, profile: CompileProfile) -> Option<Command> {
+        if let Some(target) = self.cargo.as_ref() {
+            return target.run_command(profile == CompileProfile::Optimize);
+        }
+        self.cpp_project(path)
+            .and_then(|project| project.run_command(self.profile_options(profile)))
+    }
+
+    // Extra options of the profile
+    fn profile_options(&self, profile: CompileProfile) -> &[String] {
+        match profile {
+            CompileProfile::Normal => &[],
+            CompileProfile::Optimize => self.config.optimize_option.as_slice(),
+            CompileProfile::Debug => self.config.debug_option.as_slice(),
+        }
     }
 
     pub fn compile(&self, path: PathBuf, compile_id: CompileId) {
         crate::env::set_env(&path);
+        if let Some(target) = self.cargo.as_ref() {
+            // The dev profile of cargo already has debug assertions
+            let command = target
+                .check_command(compile_id.profile == CompileProfile::Optimize)
+                .command();
+            self.worker.compile(vec![command], compile_id);
+            return;
+        }
+        if let Some(project) = self.cpp_project(&path) {
+            let steps = project.build_steps(self.profile_options(compile_id.profile));
+            if !steps.is_empty() {
+                self.worker.compile(steps, compile_id);
+                return;
+            }
+        }
         if let Some((head, tail)) = self.config.command.split_first() {
             if let Ok(head) = shellexpand::full(head) {
                 let mut command = process::Command::new(OsString::from(head.as_ref()));
                 let mut args = tail.to_vec();
 
-                if compile_id.is_optimize {
-                    args.extend_from_slice(self.config.optimize_option.as_slice());
-                }
+                args.extend_from_slice(self.profile_options(compile_id.profile));
 
                 if let Ok(args) = args
                     .iter()
@@ -57,7 +256,7 @@ impl<'a> Compiler<'a> {
                     .collect::<Result<Vec<_>, _>>()
                 {
                     command.args(args.into_iter().map(|s| OsString::from(s.as_ref())));
-                    self.worker.compile(command, compile_id);
+                    self.worker.compile(vec![command], compile_id);
                 }
             }
         }
@@ -73,12 +272,27 @@ impl<'a> Compiler<'a> {
     pub fn is_compiling(&self) -> bool {
         self.worker.is_compiling()
     }
+    /// When the running compilation started
+    pub fn running_since(&self) -> Option<Instant> {
+        self.worker.running_since()
+    }
+}
+
+/// Set of extra compiler options
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum CompileProfile {
+    #[default]
+    Normal,
+    // `optimize_option`
+    Optimize,
+    // `debug_option` (e.g. sanitizers)
+    Debug,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
 pub struct CompileId {
     pub id: Id,
-    pub is_optimize: bool,
+    pub profile: CompileProfile,
 }
 
 #[derive(Default)]
@@ -89,8 +303,8 @@ pub struct CompileResult {
 
 #[async_trait]
 trait CompilerWorker: Sync + Send + 'static {
-    // Must be async
-    fn compile(&self, _command: process::Command, _compile_id: CompileId) {}
+    // Must be async. Steps run in order until one fails.
+    fn compile(&self, _steps: Vec<process::Command>, _compile_id: CompileId) {}
     // Do not Block
     fn try_recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
         None
@@ -102,35 +316,164 @@ trait CompilerWorker: Sync + Send + 'static {
     fn is_compiling(&self) -> bool {
         false
     }
+
+    fn running_since(&self) -> Option<Instant> {
+        None
+    }
 }
 
 pub struct Cpp {
-    job_queue: JobQueue<(process::Command, CompileId), (CompileId, CompileResult)>,
+    job_queue: JobQueue<(Vec<process::Command>, CompileId), (CompileId, CompileResult)>,
 }
 
 pub struct Rust {
-    job_queue: JobQueue<(process::Command, CompileId), (CompileId, CompileResult)>,
+    job_queue: JobQueue<(Vec<process::Command>, CompileId), (CompileId, CompileResult)>,
+}
+
+pub struct Cargo {
+    job_queue: JobQueue<(Vec<process::Command>, CompileId), (CompileId, CompileResult)>,
+}
+
+// Parse both of stdout and stderr with a function
+pub struct Generic {
+    job_queue: JobQueue<(Vec<process::Command>, CompileId), (CompileId, CompileResult)>,
 }
 
 pub struct Unknown {
-    job_queue: JobQueue<(process::Command, CompileId), (CompileId, CompileResult)>,
+    job_queue: JobQueue<(Vec<process::Command>, CompileId), (CompileId, CompileResult)>,
+}
+
+// Builds of other profiles are not cancelled (e.g. by a compilation on save while compiling to run)
+fn job_profile((_, req): &(Vec<process::Command>, CompileId)) -> CompileProfile {
+    req.profile
+}
+
+// Kills the process group of a step when dropped, including compilers spawned by build tools
+struct ProcessGroup(Option<u32>);
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+// Run steps in order until one fails. Returns whether all succeeded and concatenated stdout and stderr.
+// The running process group is killed if the future is dropped (i.e. cancelled by a newer compilation).
+async fn run_steps(steps: Vec<process::Command>) -> (bool, Vec<u8>, Vec<u8>) {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    for mut step in steps {
+        #[cfg(unix)]
+        unsafe {
+            use std::os::unix::process::CommandExt;
+            // Run in a new process group
+            step.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+        let child = tokio::process::Command::from(step)
+            .kill_on_drop(true)
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn();
+        let child = match child {
+            Ok(child) => child,
+            Err(_) => return (false, stdout, stderr),
+        };
+        let mut group = ProcessGroup(Some(child.id()));
+        let output = child.wait_with_output().await;
+        // The process has exited. Its id may be reused.
+        group.0 = None;
+        match output {
+            Ok(output) => {
+                stdout.extend(output.stdout);
+                stderr.extend(output.stderr);
+                if !output.status.success() {
+                    return (false, stdout, stderr);
+                }
+            }
+            Err(_) => return (false, stdout, stderr),
+        }
+    }
+    (true, stdout, stderr)
 }
 
 impl Default for Unknown {
     fn default() -> Self {
-        let job_queue = JobQueue::new(|(cmd, req): (process::Command, CompileId)| {
-            async move {
-                let mut cmd = tokio::process::Command::from(cmd);
-                let messages = Vec::new();
-                let mut success = false;
-
-                if let Ok(cmd) = cmd.stderr(process::Stdio::piped()).output().await {
-                    success = cmd.status.success();
+        let job_queue = JobQueue::with_key(
+            |(steps, req): (Vec<process::Command>, CompileId)| {
+                async move {
+                    let (success, _, _) = run_steps(steps).await;
+                    let messages = Vec::new();
+                    (req, CompileResult { messages, success })
                 }
-                (req, CompileResult { messages, success })
-            }
-            .boxed()
-        });
+                .boxed()
+            },
+            job_profile,
+        );
+
+        Self { job_queue }
+    }
+}
+
+impl Generic {
+    fn new<F: Fn(&str) -> Vec<CompilerOutput> + Send + Sync + 'static>(parse: F) -> Self {
+        let parse = std::sync::Arc::new(parse);
+        let job_queue = JobQueue::with_key(
+            move |(steps, req): (Vec<process::Command>, CompileId)| {
+                let parse = parse.clone();
+                let base = steps
+                    .last()
+                    .and_then(|step| step.get_current_dir())
+                    .map(Path::to_path_buf);
+                async move {
+                    let (success, stdout, stderr) = run_steps(steps).await;
+                    let mut messages = parse(&String::from_utf8_lossy(&stdout));
+                    messages.extend(parse(&String::from_utf8_lossy(&stderr)));
+                    if let Some(base) = base {
+                        for message in &mut messages {
+                            message.resolve_paths(&base);
+                        }
+                    }
+                    (req, CompileResult { messages, success })
+                }
+                .boxed()
+            },
+            job_profile,
+        );
+
+        Self { job_queue }
+    }
+
+    fn with_regex(pattern: &str) -> Self {
+        let re = regex::Regex::new(pattern).unwrap();
+        Self::new(move |s: &str| parsers::parse_regex(&re, s))
+    }
+}
+
+impl Cargo {
+    fn new(workspace_root: PathBuf) -> Self {
+        let job_queue = JobQueue::with_key(
+            move |(steps, req): (Vec<process::Command>, CompileId)| {
+                let workspace_root = workspace_root.clone();
+                async move {
+                    let (success, stdout, _) = run_steps(steps).await;
+                    let messages = String::from_utf8_lossy(&stdout)
+                        .lines()
+                        .filter_map(|line| cargo::parse_cargo_json(line, &workspace_root))
+                        .collect();
+                    (req, CompileResult { messages, success })
+                }
+                .boxed()
+            },
+            job_profile,
+        );
 
         Self { job_queue }
     }
@@ -138,16 +481,12 @@ impl Default for Unknown {
 
 impl Default for Rust {
     fn default() -> Self {
-        let job_queue = JobQueue::new(|(rustc, req): (process::Command, CompileId)| {
-            async move {
-                let mut rustc = tokio::process::Command::from(rustc);
-                let mut messages = Vec::new();
-                let mut success = false;
-
-                if let Ok(rustc) = rustc.stderr(process::Stdio::piped()).output().await {
-                    success = rustc.status.success();
-                    let buf = rustc.stderr;
-                    let mut reader = io::Cursor::new(buf);
+        let job_queue = JobQueue::with_key(
+            |(steps, req): (Vec<process::Command>, CompileId)| {
+                async move {
+                    let (success, _, stderr) = run_steps(steps).await;
+                    let mut messages = Vec::new();
+                    let mut reader = io::Cursor::new(stderr);
                     let mut line = String::new();
 
                     while {
@@ -158,11 +497,12 @@ impl Default for Rust {
                             messages.push(rustc_output);
                         }
                     }
+                    (req, CompileResult { messages, success })
                 }
-                (req, CompileResult { messages, success })
-            }
-            .boxed()
-        });
+                .boxed()
+            },
+            job_profile,
+        );
 
         Self { job_queue }
     }
@@ -170,54 +510,168 @@ impl Default for Rust {
 
 impl Default for Cpp {
     fn default() -> Self {
-        let job_queue = JobQueue::new(|(clang, req): (process::Command, CompileId)| {
-            async move {
-                let mut clang = tokio::process::Command::from(clang);
-                let mut messages = Vec::new();
-                let mut success = false;
-
-                if let Ok(clang) = clang.stderr(process::Stdio::piped()).output().await {
-                    success = clang.status.success();
-                    let buf = clang.stderr;
-                    let mut reader = io::Cursor::new(buf);
-                    let mut line = String::new();
-
-                    let re = regex::Regex::new(
-                        r"^[^:]*:(?P<line>\d*):(?P<col>\d*): (?P<level>[^:]*): (?P<msg>.*)",
-                    )
-                    .unwrap();
-
-                    while {
-                        line.clear();
-                        reader.read_line(&mut line).is_ok() && !line.is_empty()
-                    } {
-                        if let Some(caps) = re.captures(&line) {
-                            let line = caps["line"].parse::<usize>().unwrap() - 1;
-                            let col = caps["col"].parse::<usize>().unwrap() - 1;
-                            let out = CompilerOutput {
-                                message: caps["msg"].into(),
-                                line,
-                                level: caps["level"].into(),
-                                span: Cursor { row: line, col }..=Cursor { row: line, col },
-                            };
-
-                            messages.push(out);
+        let job_queue = JobQueue::with_key(
+            |(steps, req): (Vec<process::Command>, CompileId)| {
+                let base = steps
+                    .last()
+                    .and_then(|step| step.get_current_dir())
+                    .map(Path::to_path_buf);
+                async move {
+                    let (success, _, stderr) = run_steps(steps).await;
+                    let mut messages = parse_gcc(&stderr);
+                    if let Some(base) = base {
+                        for message in &mut messages {
+                            message.resolve_paths(&base);
                         }
                     }
+                    (req, CompileResult { success, messages })
                 }
-                (req, CompileResult { success, messages })
-            }
-            .boxed()
-        });
+                .boxed()
+            },
+            job_profile,
+        );
 
         Self { job_queue }
     }
 }
 
+// Parse `file:line:col: level: msg` and fix-it hints of -fdiagnostics-parseable-fixits.
+// Notes become children of the preceding message and
+// include stacks / instantiation contexts are kept in the rendered text of the following one.
+// Fix-its of each diagnostic line become a suggestion of the message.
+// Clang puts only confident fix-its on errors and warnings, so the ones on notes are not machine applicable.
+fn parse_gcc(stderr: &[u8]) -> Vec<CompilerOutput> {
+    let mut messages: Vec<CompilerOutput> = Vec::new();
+    let mut reader = io::Cursor::new(stderr);
+    let mut line = String::new();
+    let mut context = String::new();
+    // Message and level of the diagnostic line which following fix-its belong to
+    let mut fixit_owner: Option<(String, bool)> = None;
+    // A fix-it of the current suggestion is in another file
+    let mut skip_fixits = false;
+
+    let re = regex::Regex::new(
+        r"^(?P<file>[^:]*):(?P<line>\d+):(?P<col>\d+): (?P<level>[a-z][a-z ]*): (?P<msg>.*)",
+    )
+    .unwrap();
+    let context_re = regex::Regex::new(
+        r"^(In file included from |\s+from |[^:]*: In |[^:]*:\d+:\d+:\s+(required|instantiated|in) )",
+    )
+    .unwrap();
+    let fixit = regex::Regex::new(
+        r#"^fix-it:"(?P<file>[^"]*)":\{(?P<l1>\d+):(?P<c1>\d+)-(?P<l2>\d+):(?P<c2>\d+)\}:"(?P<text>.*)"$"#,
+    )
+    .unwrap();
+
+    while {
+        line.clear();
+        reader.read_line(&mut line).is_ok() && !line.is_empty()
+    } {
+        if let Some(caps) = fixit.captures(line.trim_end()) {
+            let cursor = |l: &str, c: &str| Cursor {
+                row: caps[l].parse::<usize>().unwrap_or(1).saturating_sub(1),
+                col: caps[c].parse::<usize>().unwrap_or(1).saturating_sub(1),
+            };
+            let edit = TextEdit {
+                range: cursor("l1", "c1")..cursor("l2", "c2"),
+                text: unescape_c(&caps["text"]),
+            };
+            let last = match messages.last_mut() {
+                Some(last) if !skip_fixits => last,
+                _ => continue,
+            };
+            // Edits only apply to the file of the message
+            if last.file.as_deref() != Some(Path::new(&caps["file"])) {
+                if fixit_owner.is_none() {
+                    last.suggestions.pop();
+                }
+                skip_fixits = true;
+                continue;
+            }
+            if let Some((message, is_note)) = fixit_owner.take() {
+                last.suggestions.push(Suggestion {
+                    message,
+                    edits: Vec::new(),
+                    machine_applicable: !is_note,
+                });
+            }
+            last.suggestions.last_mut().unwrap().edits.push(edit);
+        } else if let Some(caps) = re.captures(&line) {
+            fixit_owner = Some((caps["msg"].to_string(), &caps["level"] == "note"));
+            skip_fixits = false;
+            let row = caps["line"].parse::<usize>().unwrap_or(1).saturating_sub(1);
+            let col = caps["col"].parse::<usize>().unwrap_or(1).saturating_sub(1);
+            let out = CompilerOutput {
+                file: Some(PathBuf::from(&caps["file"])),
+                message: caps["msg"].into(),
+                line: row,
+                level: caps["level"].into(),
+                span: Cursor { row, col }..=Cursor { row, col },
+                suggestions: Vec::new(),
+                labels: Vec::new(),
+                children: Vec::new(),
+                rendered: None,
+            };
+
+            match messages.last_mut() {
+                Some(last) if out.level == "note" && context.is_empty() => {
+                    last.rendered
+                        .get_or_insert_with(String::new)
+                        .push_str(&line);
+                    last.children.push(out);
+                }
+                _ => {
+                    let mut out = out;
+                    context.push_str(&line);
+                    out.rendered = Some(std::mem::take(&mut context));
+                    messages.push(out);
+                }
+            }
+        } else if context_re.is_match(&line) {
+            context.push_str(&line);
+        } else if let Some(rendered) = messages.last_mut().and_then(|m| m.rendered.as_mut()) {
+            // Source snippet and caret lines
+            rendered.push_str(&line);
+        }
+    }
+    messages
+}
+
+// Replacement text of fix-it hints is escaped as a C string. Octal escapes are bytes of UTF-8.
+fn unescape_c(s: &str) -> String {
+    let mut res = Vec::new();
+    let mut chars = s.chars().peekable();
+    let push =
+        |res: &mut Vec<u8>, c: char| res.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            push(&mut res, c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push(b'\n'),
+            Some('t') => res.push(b'\t'),
+            Some(d) if d.is_digit(8) => {
+                let mut code = d.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    if let Some(d) = chars.peek().and_then(|d| d.to_digit(8)) {
+                        code = code * 8 + d;
+                        chars.next();
+                    }
+                }
+                res.push(code as u8);
+            }
+            Some(c) => push(&mut res, c),
+            None => res.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
+
 #[async_trait]
 impl CompilerWorker for Unknown {
-    fn compile(&self, command: process::Command, compile_id: CompileId) {
-        self.job_queue.send((command, compile_id)).unwrap();
+    fn compile(&self, steps: Vec<process::Command>, compile_id: CompileId) {
+        self.job_queue.send((steps, compile_id)).unwrap();
     }
     fn try_recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
         self.job_queue.rx().try_recv().ok()
@@ -228,12 +682,15 @@ impl CompilerWorker for Unknown {
     fn is_compiling(&self) -> bool {
         self.job_queue.is_running()
     }
+    fn running_since(&self) -> Option<Instant> {
+        self.job_queue.running_since()
+    }
 }
 
 #[async_trait]
 impl CompilerWorker for Cpp {
-    fn compile(&self, command: process::Command, compile_id: CompileId) {
-        self.job_queue.send((command, compile_id)).unwrap();
+    fn compile(&self, steps: Vec<process::Command>, compile_id: CompileId) {
+        self.job_queue.send((steps, compile_id)).unwrap();
     }
     fn try_recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
         self.job_queue.rx().try_recv().ok()
@@ -244,12 +701,55 @@ impl CompilerWorker for Cpp {
     fn is_compiling(&self) -> bool {
         self.job_queue.is_running()
     }
+    fn running_since(&self) -> Option<Instant> {
+        self.job_queue.running_since()
+    }
 }
 
 #[async_trait]
 impl CompilerWorker for Rust {
-    fn compile(&self, command: process::Command, compile_id: CompileId) {
-        self.job_queue.send((command, compile_id)).unwrap();
+    fn compile(&self, steps: Vec<process::Command>, compile_id: CompileId) {
+        self.job_queue.send((steps, compile_id)).unwrap();
+    }
+    fn try_recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
+        self.job_queue.rx().try_recv().ok()
+    }
+
+    async fn recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
+        self.job_queue.rx().recv().await
+    }
+    fn is_compiling(&self) -> bool {
+        self.job_queue.is_running()
+    }
+    fn running_since(&self) -> Option<Instant> {
+        self.job_queue.running_since()
+    }
+}
+
+#[async_trait]
+impl CompilerWorker for Cargo {
+    fn compile(&self, steps: Vec<process::Command>, compile_id: CompileId) {
+        self.job_queue.send((steps, compile_id)).unwrap();
+    }
+    fn try_recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
+        self.job_queue.rx().try_recv().ok()
+    }
+
+    async fn recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
+        self.job_queue.rx().recv().await
+    }
+    fn is_compiling(&self) -> bool {
+        self.job_queue.is_running()
+    }
+    fn running_since(&self) -> Option<Instant> {
+        self.job_queue.running_since()
+    }
+}
+
+#[async_trait]
+impl CompilerWorker for Generic {
+    fn compile(&self, steps: Vec<process::Command>, compile_id: CompileId) {
+        self.job_queue.send((steps, compile_id)).unwrap();
     }
     fn try_recv_compile_result(&mut self) -> Option<(CompileId, CompileResult)> {
         self.job_queue.rx().try_recv().ok()
@@ -261,4 +761,90 @@ impl CompilerWorker for Rust {
     fn is_compiling(&self) -> bool {
         self.job_queue.is_running()
     }
+    fn running_since(&self) -> Option<Instant> {
+        self.job_queue.running_since()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_gcc, unescape_c};
+    use crate::core::Cursor;
+
+    #[test]
+    fn test_parse_gcc_fixit() {
+        let stderr = br#"a.cpp:3:11: error: expected ';' after expression
+  int x = 1
+          ^
+          ;
+fix-it:"a.cpp":{3:12-3:12}:";"
+a.cpp:5:3: warning: unused variable 'y'
+fix-it:"a.cpp":{5:1-5:4}:"\"q\"\n"
+"#;
+        let messages = parse_gcc(stderr);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].level, "error");
+        let edits = &messages[0].suggestions[0].edits;
+        assert_eq!(
+            edits[0].range,
+            Cursor { row: 2, col: 11 }..Cursor { row: 2, col: 11 }
+        );
+        assert_eq!(edits[0].text, ";");
+        assert_eq!(messages[1].suggestions[0].edits[0].text, "\"q\"\n");
+        assert!(messages[0].suggestions[0].machine_applicable);
+    }
+
+    #[test]
+    fn test_parse_gcc_fixit_files() {
+        let stderr = br#"a.cpp:3:11: error: no matching function for call to 'f'
+fix-it:"a.h":{1:1-1:1}:"const "
+a.cpp:2:6: note: did you mean 'g'?
+fix-it:"a.cpp":{3:3-3:4}:"g"
+a.cpp:5:3: warning: unused variable 'y'
+fix-it:"a.cpp":{5:1-5:1}:"[[maybe_unused]] "
+fix-it:"a.h":{2:1-2:1}:"x"
+"#;
+        let messages = parse_gcc(stderr);
+        assert_eq!(messages.len(), 2);
+        // The fix-it in a.h is dropped and the one of the note is not applied automatically
+        assert_eq!(messages[0].suggestions.len(), 1);
+        assert_eq!(messages[0].suggestions[0].message, "did you mean 'g'?");
+        assert!(!messages[0].suggestions[0].machine_applicable);
+        // A suggestion is applied as a whole
+        assert!(messages[1].suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_unescape_c() {
+        assert_eq!(unescape_c(r"a\tb\n"), "a\tb\n");
+        assert_eq!(unescape_c(r"\303\251t\303\251"), "été");
+        assert_eq!(unescape_c(r#"\"\\"#), "\"\\");
+    }
+
+    #[test]
+    fn test_parse_gcc_notes() {
+        let stderr = br#"In file included from a.cpp:1:
+a.h: In instantiation of 'void f(T) [with T = std::vector<int>]':
+a.cpp:9:6:   required from here
+a.h:3:5: error: no member named 'foo'
+    3 |   x.foo();
+      |     ^~~
+a.h:1:6: note: candidate: 'void g()'
+a.cpp:12:3: warning: unused variable 'y'
+"#;
+        let messages = parse_gcc(stderr);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].level, "error");
+        assert_eq!(messages[0].children.len(), 1);
+        assert_eq!(messages[0].children[0].level, "note");
+        assert_eq!(messages[0].children[0].line, 0);
+        let rendered = messages[0].rendered.as_ref().unwrap();
+        assert!(rendered.starts_with("In file included from a.cpp:1:\n"));
+        assert!(rendered.contains("^~~"));
+        assert!(rendered.ends_with("note: candidate: 'void g()'\n"));
+        assert_eq!(messages[0].file, Some("a.h".into()));
+        assert_eq!(messages[1].line, 11);
+        assert_eq!(messages[1].file, Some("a.cpp".into()));
+        assert!(messages[1].children.is_empty());
+    }
 }
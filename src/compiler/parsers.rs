@@ -0,0 +1,411 @@
+// Parsers of compiler outputs other than rustc and gcc
+
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use super::CompilerOutput;
+use crate::core::Cursor;
+
+pub const GO: &str = r"^(?P<file>[^:\s#][^:]*\.go):(?P<line>\d+):(?:(?P<col>\d+):)? (?P<msg>.*)$";
+pub const JAVA: &str =
+    r"^(?P<file>[^:\s][^:]*\.java):(?P<line>\d+): (?P<level>error|warning): (?P<msg>.*)$";
+pub const KOTLIN: &str = r"^(?P<file>[^:\s][^:]*\.kts?):(?P<line>\d+):(?P<col>\d+): (?P<level>error|warning|info): (?P<msg>.*)$";
+pub const HASKELL: &str = r"^(?P<file>[^:\s][^:]*\.l?hs):(?P<line>\d+):(?P<col>\d+)(?:-\d+)?: (?P<level>error|warning)[^:]*:\s*(?P<msg>.*)$";
+
+/// Compile a user defined pattern. `line` group is required.
+pub fn compile_regex(pattern: &str) -> Result<Regex, String> {
+    let re = Regex::new(pattern).map_err(|err| err.to_string())?;
+    if re.capture_names().any(|name| name == Some("line")) {
+        Ok(re)
+    } else {
+        Err(format!("No `line` group in {}", pattern))
+    }
+}
+
+fn output(file: Option<&str>, line: usize, col: usize, level: &str, msg: &str) -> CompilerOutput {
+    let row = line.saturating_sub(1);
+    let col = col.saturating_sub(1);
+    CompilerOutput {
+        file: file.map(PathBuf::from),
+        message: msg.to_string(),
+        line: row,
+        level: level.to_string(),
+        span: Cursor { row, col }..=Cursor { row, col },
+        suggestions: Vec::new(),
+        labels: Vec::new(),
+        children: Vec::new(),
+        rendered: None,
+    }
+}
+
+/// Messages of lines matching `re`. Following indented lines are kept as the rendered text and
+/// the first of them becomes the message if the matched line has none (e.g. ghc).
+pub fn parse_regex(re: &Regex, output_text: &str) -> Vec<CompilerOutput> {
+    let code_only = Regex::new(r"^\[[^\]]*\]$").unwrap();
+    let mut messages: Vec<CompilerOutput> = Vec::new();
+    let mut continued = false;
+
+    for line in output_text.lines() {
+        if let Some(caps) = re.captures(line) {
+            let get = |name: &str| caps.name(name).map(|m| m.as_str());
+            let line_number = match get("line").and_then(|l| l.parse().ok()) {
+                Some(line_number) => line_number,
+                None => continue,
+            };
+            let col = get("col").and_then(|c| c.parse().ok()).unwrap_or(1);
+            let mut out = output(
+                get("file"),
+                line_number,
+                col,
+                get("level").unwrap_or("error"),
+                get("msg").unwrap_or_default().trim(),
+            );
+            out.rendered = Some(format!("{}\n", line));
+            messages.push(out);
+            continued = true;
+        } else if continued && line.starts_with(char::is_whitespace) {
+            let last = messages.last_mut().unwrap();
+            if !line.trim().is_empty()
+                && (last.message.is_empty() || code_only.is_match(&last.message))
+            {
+                last.message = if last.message.is_empty() {
+                    line.trim().to_string()
+                } else {
+                    format!("{} {}", line.trim(), last.message)
+                };
+            }
+            if let Some(rendered) = last.rendered.as_mut() {
+                rendered.push_str(line);
+                rendered.push('\n');
+            }
+        } else {
+            continued = false;
+        }
+    }
+    messages
+}
+
+/// Python tracebacks and `python -m py_compile` output.
+/// A message is put on the innermost frame and outer frames become its children.
+pub fn parse_python(output_text: &str) -> Vec<CompilerOutput> {
+    let frame = Regex::new(r#"^\s*File "(?P<file>[^"]+)", line (?P<line>\d+)"#).unwrap();
+    let exception = Regex::new(
+        r"^(?P<type>[A-Za-z_][\w.]*(Error|Exception|Warning|Exit|Interrupt))(: (?P<msg>.*))?$",
+    )
+    .unwrap();
+    let mut messages = Vec::new();
+    let mut frames: Vec<CompilerOutput> = Vec::new();
+    let mut rendered = String::new();
+
+    for line in output_text.lines() {
+        if let Some(caps) = frame.captures(line) {
+            let line_number = caps["line"].parse().unwrap_or(1);
+            frames.push(output(
+                Some(&caps["file"]),
+                line_number,
+                1,
+                "note",
+                line.trim(),
+            ));
+        } else if let Some(caps) = exception.captures(line) {
+            rendered.push_str(line);
+            rendered.push('\n');
+            if let Some(mut out) = frames.pop() {
+                let ty = &caps["type"];
+                out.level = if ty.ends_with("Warning") {
+                    "warning"
+                } else {
+                    "error"
+                }
+                .to_string();
+                out.message = match caps.name("msg") {
+                    Some(msg) => format!("{}: {}", ty, msg.as_str()),
+                    None => ty.to_string(),
+                };
+                frames.reverse();
+                out.children = std::mem::take(&mut frames);
+                out.rendered = Some(std::mem::take(&mut rendered));
+                messages.push(out);
+            }
+            rendered.clear();
+            continue;
+        }
+        rendered.push_str(line);
+        rendered.push('\n');
+    }
+    messages
+}
+
+// Frames in libraries and sanitizer runtimes
+fn is_system_frame(file: &str) -> bool {
+    file.starts_with("/usr/")
+        || file.starts_with("/lib")
+        || file.contains("libsanitizer/")
+        || file.contains("compiler-rt/")
+}
+
+// Put a sanitizer report on its innermost frame in user code
+fn finish_report(
+    message: String,
+    rendered: String,
+    mut frames: Vec<CompilerOutput>,
+) -> Option<CompilerOutput> {
+    let is_user_frame = |f: &CompilerOutput| {
+        f.file
+            .as_ref()
+            .map(|file| !is_system_frame(&file.to_string_lossy()))
+            .unwrap_or(false)
+    };
+    let i = frames.iter().position(is_user_frame)?;
+    let mut out = frames.remove(i);
+    out.level = "error".to_string();
+    out.message = message;
+    out.children = frames.into_iter().filter(is_user_frame).collect();
+    out.rendered = Some(rendered);
+    Some(out)
+}
+
+/// Reports of sanitizers (ASan, UBSan, LSan, ...) in stderr of a program.
+/// A report is put on its innermost frame in user code and other frames become its children.
+pub fn parse_sanitizer(stderr: &str) -> Vec<CompilerOutput> {
+    let runtime_error = Regex::new(
+        r"^(?P<file>[^:\s][^:]*):(?P<line>\d+):(?:(?P<col>\d+):)? runtime error: (?P<msg>.*)$",
+    )
+    .unwrap();
+    let header = Regex::new(r"^==\d+==\s*ERROR: (?P<msg>\w+Sanitizer: .*)$").unwrap();
+    let frame = Regex::new(
+        r"^\s*#\d+ 0x[0-9a-f]+ in .+? (?P<file>[^\s()]+?):(?P<line>\d+)(?::(?P<col>\d+))?$",
+    )
+    .unwrap();
+    let mut messages = Vec::new();
+    // Message and rendered text of the current report with its frames
+    let mut report: Option<(String, String, Vec<CompilerOutput>)> = None;
+
+    for line in stderr.lines() {
+        if let Some((_, rendered, frames)) = report.as_mut() {
+            if line.starts_with("SUMMARY:") || line.starts_with("==") {
+                let (message, rendered, frames) = report.take().unwrap();
+                messages.extend(finish_report(message, rendered, frames));
+            } else {
+                rendered.push_str(line);
+                rendered.push('\n');
+                if let Some(caps) = frame.captures(line) {
+                    let col = caps
+                        .name("col")
+                        .and_then(|c| c.as_str().parse().ok())
+                        .unwrap_or(1);
+                    frames.push(output(
+                        Some(&caps["file"]),
+                        caps["line"].parse().unwrap_or(1),
+                        col,
+                        "note",
+                        line.trim(),
+                    ));
+                }
+                continue;
+            }
+        }
+        if let Some(caps) = runtime_error.captures(line) {
+            let col = caps
+                .name("col")
+                .and_then(|c| c.as_str().parse().ok())
+                .unwrap_or(1);
+            let mut out = output(
+                Some(&caps["file"]),
+                caps["line"].parse().unwrap_or(1),
+                col,
+                "error",
+                &format!("runtime error: {}", &caps["msg"]),
+            );
+            out.rendered = Some(format!("{}\n", line));
+            messages.push(out);
+        } else if let Some(caps) = header.captures(line) {
+            report = Some((caps["msg"].to_string(), format!("{}\n", line), Vec::new()));
+        }
+    }
+    if let Some((message, rendered, frames)) = report {
+        messages.extend(finish_report(message, rendered, frames));
+    }
+    messages
+}
+
+/// Finds source locations in output of programs: panics, backtraces, sanitizer frames and
+/// Python tracebacks
+pub struct LocationParser {
+    location: Regex,
+    python: Regex,
+}
+
+impl Default for LocationParser {
+    fn default() -> Self {
+        Self {
+            location: Regex::new(r#"(?P<file>[^\s:'"`()\[\]<>]+):(?P<line>\d+)(?::(?P<col>\d+))?"#)
+                .unwrap(),
+            python: Regex::new(r#"File "(?P<file>[^"]+)", line (?P<line>\d+)"#).unwrap(),
+        }
+    }
+}
+
+impl LocationParser {
+    /// The first location in `line` whose file is found by `resolve`
+    pub fn parse(
+        &self,
+        line: &str,
+        mut resolve: impl FnMut(&Path) -> Option<PathBuf>,
+    ) -> Option<(PathBuf, Cursor)> {
+        self.python
+            .captures_iter(line)
+            .chain(self.location.captures_iter(line))
+            .find_map(|caps| {
+                let file = resolve(Path::new(&caps["file"]))?;
+                let row = caps["line"].parse::<usize>().ok()?.saturating_sub(1);
+                let col = caps
+                    .name("col")
+                    .and_then(|c| c.as_str().parse::<usize>().ok())
+                    .unwrap_or(1)
+                    .saturating_sub(1);
+                Some((file, Cursor { row, col }))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_python() {
+        let traceback = r#"Traceback (most recent call last):
+  File "a.py", line 5, in <module>
+    f()
+  File "a.py", line 2, in f
+    return y
+NameError: name 'y' is not defined
+"#;
+        let messages = parse_python(traceback);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].line, 1);
+        assert_eq!(messages[0].level, "error");
+        assert_eq!(messages[0].message, "NameError: name 'y' is not defined");
+        assert_eq!(messages[0].children.len(), 1);
+        assert_eq!(messages[0].children[0].line, 4);
+        assert!(messages[0]
+            .rendered
+            .as_ref()
+            .unwrap()
+            .starts_with("Traceback"));
+
+        let syntax_error = r#"  File "b.py", line 3
+    x = = 1
+        ^
+SyntaxError: invalid syntax
+"#;
+        let messages = parse_python(syntax_error);
+        assert_eq!(messages[0].line, 2);
+        assert_eq!(messages[0].file, Some("b.py".into()));
+    }
+
+    #[test]
+    fn test_parse_builtin() {
+        let go = "# command-line-arguments\n./main.go:5:2: undefined: x\n";
+        let messages = parse_regex(&Regex::new(GO).unwrap(), go);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].level, "error");
+        assert_eq!(*messages[0].span.start(), Cursor { row: 4, col: 1 });
+
+        let java = "Main.java:3: error: cannot find symbol\n        y = 1;\n        ^\n";
+        let messages = parse_regex(&Regex::new(JAVA).unwrap(), java);
+        assert_eq!(messages[0].line, 2);
+        assert_eq!(messages[0].message, "cannot find symbol");
+
+        let kotlin = "main.kt:2:13: warning: variable 'x' is never used\n";
+        let messages = parse_regex(&Regex::new(KOTLIN).unwrap(), kotlin);
+        assert_eq!(messages[0].level, "warning");
+
+        let ghc = "Main.hs:3:7: error: [GHC-88464]\n    Variable not in scope: y\n  |\n3 | f = y\n";
+        let messages = parse_regex(&Regex::new(HASKELL).unwrap(), ghc);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message, "Variable not in scope: y [GHC-88464]");
+        assert_eq!(*messages[0].span.start(), Cursor { row: 2, col: 6 });
+    }
+
+    #[test]
+    fn test_parse_sanitizer() {
+        let stderr = r"a.cpp:5:7: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+=================================================================
+==19146==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000020 at pc 0x5613a2c85297 bp 0x7fff5deef2b0 sp 0x7fff5deef2a8
+READ of size 4 at 0x602000000020 thread T0
+    #0 0x5613a2c85296 in main /tmp/san/a.cpp:7
+    #1 0x7f2dd6e45249  (/lib/x86_64-linux-gnu/libc.so.6+0x27249)
+    #2 0x5613a2c85100 in _start (/tmp/san/a+0x1100)
+
+allocated by thread T0 here:
+    #0 0x7f2dd7cb9628 in operator new[](unsigned long) ../../../../src/libsanitizer/asan/asan_new_delete.cpp:98
+    #1 0x5613a2c8520b in main /tmp/san/a.cpp:6:14
+
+SUMMARY: AddressSanitizer: heap-buffer-overflow /tmp/san/a.cpp:7 in main
+==19146==ABORTING
+";
+        let messages = parse_sanitizer(stderr);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(*messages[0].span.start(), Cursor { row: 4, col: 6 });
+        assert!(messages[0]
+            .message
+            .starts_with("runtime error: signed integer overflow"));
+
+        assert_eq!(messages[1].file, Some("/tmp/san/a.cpp".into()));
+        assert_eq!(messages[1].line, 6);
+        assert_eq!(
+            messages[1].message,
+            "AddressSanitizer: heap-buffer-overflow on address 0x602000000020 at pc 0x5613a2c85297 bp 0x7fff5deef2b0 sp 0x7fff5deef2a8"
+        );
+        assert_eq!(messages[1].children.len(), 1);
+        assert_eq!(
+            *messages[1].children[0].span.start(),
+            Cursor { row: 5, col: 13 }
+        );
+    }
+
+    #[test]
+    fn test_location_parser() {
+        let parser = LocationParser::default();
+        let resolve = |p: &Path| {
+            if p.ends_with("src/main.rs") || p.ends_with("a.py") {
+                Some(p.to_path_buf())
+            } else {
+                None
+            }
+        };
+        assert_eq!(
+            parser.parse("thread 'main' panicked at src/main.rs:12:5:", resolve),
+            Some((PathBuf::from("src/main.rs"), Cursor { row: 11, col: 4 }))
+        );
+        assert_eq!(
+            parser.parse("             at ./src/main.rs:4:5", resolve),
+            Some((PathBuf::from("./src/main.rs"), Cursor { row: 3, col: 4 }))
+        );
+        assert_eq!(
+            parser.parse(
+                "   3: std::rt::lang_start at /rustc/abc/library/std/src/rt.rs:159:18",
+                resolve
+            ),
+            None
+        );
+        assert_eq!(
+            parser.parse("    #0 0x5613a2c85296 in main /w/src/main.rs:7", resolve),
+            Some((PathBuf::from("/w/src/main.rs"), Cursor { row: 6, col: 0 }))
+        );
+        assert_eq!(
+            parser.parse(r#"  File "a.py", line 5, in <module>"#, resolve),
+            Some((PathBuf::from("a.py"), Cursor { row: 4, col: 0 }))
+        );
+    }
+
+    #[test]
+    fn test_compile_regex() {
+        assert!(compile_regex(r"^(?P<file>[^:]+):(?P<line>\d+): (?P<msg>.*)$").is_ok());
+        assert!(compile_regex(r"^(?P<file>[^:]+): (?P<msg>.*)$").is_err());
+        assert!(compile_regex(r"(").is_err());
+    }
+}
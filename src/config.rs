@@ -9,6 +9,7 @@ use crate::config::snippet::load_snippet;
 use crate::config::types::keys;
 use crate::config::types::Command;
 use crate::config::types::CompilerConfig;
+use crate::config::types::FormatterConfig;
 
 mod snippet;
 pub mod types;
@@ -27,7 +28,7 @@ struct LanguageConfigToml {
     snippets: Option<Vec<String>>,
     indent_width: Option<usize>,
     lsp: Option<Vec<String>>,
-    formatter: Option<Vec<String>>,
+    formatter: Option<FormatterToml>,
     syntax: Option<String>,
     compiler: Option<CompilerConfig>,
     test_command: Option<Vec<String>>,
@@ -36,6 +37,29 @@ struct LanguageConfigToml {
     hard_tab: Option<bool>,
 }
 
+// `formatter = "lsp"` or `formatter = ["rustfmt"]`
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum FormatterToml {
+    Name(String),
+    Command(Vec<String>),
+}
+
+impl FormatterToml {
+    fn into_config(self) -> Option<FormatterConfig> {
+        match self {
+            FormatterToml::Name(name) if name == "lsp" => Some(FormatterConfig::LSP),
+            FormatterToml::Name(name) => Some(FormatterConfig::Command(Command {
+                program: name,
+                args: Vec::new(),
+            })),
+            FormatterToml::Command(command) => {
+                Command::new(command.as_slice()).map(FormatterConfig::Command)
+            }
+        }
+    }
+}
+
 pub struct LanguageConfig(typemap::TypeMap);
 
 impl Default for LanguageConfig {
@@ -85,12 +109,8 @@ impl Into<LanguageConfig> for LanguageConfigToml {
         language_config.insert_option::<keys::LSP>(
             self.lsp.as_ref().map(Vec::as_slice).and_then(Command::new),
         );
-        language_config.insert_option::<keys::Formatter>(
-            self.formatter
-                .as_ref()
-                .map(Vec::as_slice)
-                .and_then(Command::new),
-        );
+        language_config
+            .insert_option::<keys::Formatter>(self.formatter.and_then(FormatterToml::into_config));
         language_config.insert_option::<keys::SyntaxExtension>(self.syntax);
         language_config.insert_option::<keys::Compiler>(self.compiler);
         language_config.insert_option::<keys::TestCommand>(
@@ -220,3 +240,22 @@ impl ConfigWithDefault {
         self.config.set::<A>(value);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::parse_config;
+
+    #[test]
+    fn test_compiler_regex() {
+        let config = |pattern: &str| {
+            format!(
+                "[file.txt.compiler]\ncommand = [\"true\"]\ntype = {{ regex = '{}' }}\n",
+                pattern
+            )
+        };
+        assert!(parse_config(&config(r"^(?P<file>[^:]+):(?P<line>\d+): (?P<msg>.*)$")).is_ok());
+        let err = parse_config(&config(r"^(?P<file>[^:]+): (?P<msg>.*)$")).err();
+        assert!(err.is_some_and(|err| err.to_string().contains("No `line` group")));
+        assert!(parse_config(&config("(")).is_err());
+    }
+}
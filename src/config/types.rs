@@ -1,8 +1,12 @@
 use std::fmt;
 use std::process;
 
+use serde::de::{self, Deserializer};
+use serde::Deserialize as _;
 use serde_derive::Deserialize;
 
+use crate::compiler;
+
 #[derive(Debug, Clone)]
 pub struct Command {
     pub program: String,
@@ -47,20 +51,64 @@ impl fmt::Display for Command {
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum FormatterConfig {
+    // Use textDocument/formatting of the language server
+    LSP,
+    Command(Command),
+}
+
+impl fmt::Display for FormatterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatterConfig::LSP => write!(f, "lsp"),
+            FormatterConfig::Command(command) => write!(f, "{}", command),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub enum CompilerType {
     #[serde(rename = "rustc")]
     Rustc,
     #[serde(rename = "gcc")]
     Gcc,
+    #[serde(rename = "python")]
+    Python,
+    #[serde(rename = "go")]
+    Go,
+    #[serde(rename = "java")]
+    Java,
+    #[serde(rename = "kotlin")]
+    Kotlin,
+    #[serde(rename = "haskell")]
+    Haskell,
+    // `type = { regex = "..." }` with named groups `file`, `line`, `col`, `level` and `msg`
+    #[serde(rename = "regex", deserialize_with = "deserialize_regex")]
+    Regex(String),
+}
+
+// Report an invalid pattern as a config error instead of ignoring the output
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    compiler::compile_regex(&pattern).map_err(de::Error::custom)?;
+    Ok(pattern)
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct CompilerConfig {
     pub command: Vec<String>,
+    #[serde(default)]
     pub optimize_option: Vec<String>,
+    // Options of the debug profile (e.g. sanitizers)
+    #[serde(default)]
+    pub debug_option: Vec<String>,
     #[serde(rename = "type")]
     pub output_type: Option<CompilerType>,
+    // Run `cargo check` instead of `command` for rustc in a Cargo package. Defaults to true.
+    pub cargo: Option<bool>,
+    // Build gcc type files with CMake or flags in compile_commands.json if found. Defaults to true.
+    pub compile_commands: Option<bool>,
 }
 
 pub mod keys {
@@ -70,6 +118,7 @@ pub mod keys {
 
     use crate::config::types::Command;
     use crate::config::types::CompilerConfig;
+    use crate::config::types::FormatterConfig;
 
     // TODO Those generate impls from macro
 
@@ -100,7 +149,7 @@ pub mod keys {
     pub struct Formatter;
 
     impl Key for Formatter {
-        type Value = Command;
+        type Value = FormatterConfig;
     }
 
     pub struct SyntaxExtension;
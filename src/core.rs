@@ -4,7 +4,7 @@ use std::cmp::Ordering;
 use std::io;
 use std::io::Read;
 use std::num::Wrapping;
-use std::ops::RangeBounds;
+use std::ops::{Range, RangeBounds};
 
 use crate::indent;
 use crate::parenthesis;
@@ -49,6 +49,56 @@ impl Ord for Cursor {
     }
 }
 
+/// Replace `range` with `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<Cursor>,
+    pub text: String,
+}
+
+impl TextEdit {
+    // Cursor at the end of inserted text
+    fn inserted_end(&self) -> Cursor {
+        let mut end = self.range.start;
+        for c in self.text.chars() {
+            if c == '\n' {
+                end.row += 1;
+                end.col = 0;
+            } else {
+                end.col += 1;
+            }
+        }
+        end
+    }
+
+    // Where `cursor` goes after this edit is applied
+    fn map_cursor(&self, cursor: Cursor) -> Cursor {
+        if cursor < self.range.start {
+            cursor
+        } else if cursor >= self.range.end {
+            let end = self.inserted_end();
+            if cursor.row == self.range.end.row {
+                Cursor {
+                    row: end.row,
+                    col: end.col + cursor.col - self.range.end.col,
+                }
+            } else {
+                Cursor {
+                    row: cursor.row + end.row - self.range.end.row,
+                    col: cursor.col,
+                }
+            }
+        } else {
+            // Inside of the edit. Keep the row as possible.
+            let end = self.inserted_end();
+            Cursor {
+                row: min(end.row, cursor.row),
+                col: cursor.col,
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Core<B: buffer::CoreBuffer> {
     core_buffer: B,
@@ -58,6 +108,9 @@ pub struct Core<B: buffer::CoreBuffer> {
     redo: Vec<Vec<Box<dyn Operation<B>>>>,
     buffer_changed: Id,
     pub dirty_from: usize,
+    read_only: bool,
+    // An operation was blocked because of `read_only`
+    rejected: bool,
 }
 
 impl<B: buffer::CoreBuffer> Default for Core<B> {
@@ -71,6 +124,8 @@ impl<B: buffer::CoreBuffer> Default for Core<B> {
             buffer_changed: Id(Wrapping(1)),
             /// Lines after this are modified
             dirty_from: 0,
+            read_only: false,
+            rejected: false,
         }
     }
 }
@@ -85,6 +140,8 @@ impl<B: buffer::CoreBuffer> Core<B> {
             redo: Vec::new(),
             buffer_changed: Id(Wrapping(1)),
             dirty_from: 0,
+            read_only: false,
+            rejected: false,
         })
     }
 
@@ -92,6 +149,28 @@ impl<B: buffer::CoreBuffer> Core<B> {
         self.buffer_changed
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Block all operations including undo and redo
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Was an operation blocked since the last call?
+    pub fn take_rejected(&mut self) -> bool {
+        std::mem::replace(&mut self.rejected, false)
+    }
+
+    // Returns true if operations are blocked
+    fn reject(&mut self) -> bool {
+        if self.read_only {
+            self.rejected = true;
+        }
+        self.read_only
+    }
+
     pub fn char_at_cursor(&self) -> Option<char> {
         self.core_buffer.char_at(self.cursor)
     }
@@ -366,6 +445,33 @@ impl<B: buffer::CoreBuffer> Core<B> {
         }
     }
 
+    /// Apply non-overlapping edits as minimal operations and keep the cursor on the same text
+    pub fn apply_edits(&mut self, mut edits: Vec<TextEdit>) {
+        let end = self.core_buffer.end_cursor();
+        for edit in &mut edits {
+            edit.range.start = min(edit.range.start, end);
+            edit.range.end = min(edit.range.end, end);
+        }
+        edits.sort_by_key(|e| e.range.start);
+        let mut cursor = self.cursor;
+        // Apply from the end not to invalidate positions of rest edits
+        for edit in edits.into_iter().rev() {
+            cursor = edit.map_cursor(cursor);
+            if edit.range.start != edit.range.end {
+                self.perform(operation::DeleteRange::new(edit.range.clone()));
+            }
+            if !edit.text.is_empty() {
+                self.perform(operation::InsertString {
+                    cursor: edit.range.start,
+                    s: edit.text,
+                });
+            }
+        }
+        let row = min(cursor.row, self.core_buffer.len_lines() - 1);
+        let col = min(cursor.col, self.core_buffer.len_line(row));
+        self.cursor = Cursor { row, col };
+    }
+
     pub fn core_buffer(&self) -> &B {
         &self.core_buffer
     }
@@ -388,6 +494,9 @@ impl<B: buffer::CoreBuffer> Core<B> {
     }
 
     fn perform<T: Operation<B> + 'static>(&mut self, mut op: T) {
+        if self.reject() {
+            return;
+        }
         if let Some(l) = op.perform(self.arg()) {
             self.dirty_from = min(self.dirty_from, l);
         }
@@ -405,6 +514,9 @@ impl<B: buffer::CoreBuffer> Core<B> {
     }
 
     pub fn undo(&mut self) {
+        if self.reject() {
+            return;
+        }
         self.commit();
         if let Some(mut ops) = self.history.pop() {
             for op in ops.iter_mut().rev() {
@@ -418,6 +530,9 @@ impl<B: buffer::CoreBuffer> Core<B> {
     }
 
     pub fn redo(&mut self) {
+        if self.reject() {
+            return;
+        }
         if let Some(mut ops) = self.redo.pop() {
             for op in &mut ops {
                 if let Some(l) = op.perform(self.arg()) {
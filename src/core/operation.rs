@@ -21,6 +21,27 @@ pub struct InsertChar {
     pub c: char,
 }
 
+#[derive(Debug)]
+pub struct InsertString {
+    pub cursor: Cursor,
+    pub s: String,
+}
+
+impl InsertString {
+    fn end(&self) -> Cursor {
+        let mut end = self.cursor;
+        for c in self.s.chars() {
+            if c == '\n' {
+                end.row += 1;
+                end.col = 0;
+            } else {
+                end.col += 1;
+            }
+        }
+        end
+    }
+}
+
 #[derive(Debug)]
 pub struct DeleteRange {
     range: (Bound<Cursor>, Bound<Cursor>),
@@ -72,6 +93,20 @@ impl<B: CoreBuffer> Operation<B> for InsertChar {
     }
 }
 
+impl<B: CoreBuffer> Operation<B> for InsertString {
+    fn perform(&mut self, arg: OperationArg<B>) -> Option<usize> {
+        arg.core_buffer.insert(self.cursor, self.s.as_str());
+        *arg.cursor = self.end();
+        Some(self.cursor.row)
+    }
+
+    fn undo(&mut self, arg: OperationArg<B>) -> Option<usize> {
+        arg.core_buffer.delete_range(self.cursor..self.end());
+        *arg.cursor = self.cursor;
+        Some(self.cursor.row)
+    }
+}
+
 impl<B: CoreBuffer> Operation<B> for DeleteRange {
     fn perform(&mut self, arg: OperationArg<B>) -> Option<usize> {
         self.orig = Some(arg.core_buffer.get_range(self.range));
@@ -132,7 +167,7 @@ impl<B: CoreBuffer> Operation<B> for Set {
 mod test {
     use super::InsertChar;
     use crate::core::buffer::RopeyCoreBuffer;
-    use crate::core::operation::{DeleteRange, Set};
+    use crate::core::operation::{DeleteRange, InsertString, Set};
     use crate::core::Cursor;
     use crate::core::{Core, CoreBuffer};
 
@@ -159,6 +194,22 @@ mod test {
         assert_eq!(core.core_buffer.len_lines(), 2);
     }
 
+    #[test]
+    fn test_operation_insert_string() {
+        let mut core = Core::<RopeyCoreBuffer>::from_reader("14".as_bytes()).unwrap();
+
+        core.perform(InsertString {
+            cursor: Cursor { row: 0, col: 1 },
+            s: "2\n3".to_string(),
+        });
+        assert_eq!(core.get_string(), "12\n34".to_string());
+        assert_eq!(core.cursor(), Cursor { row: 1, col: 1 });
+        core.commit();
+        core.undo();
+        assert_eq!(core.get_string(), "14".to_string());
+        assert_eq!(core.cursor(), Cursor { row: 0, col: 1 });
+    }
+
     #[test]
     fn test_operation_delete_range() {
         let mut core = Core::<RopeyCoreBuffer>::from_reader("12345678".as_bytes()).unwrap();
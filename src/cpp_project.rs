@@ -0,0 +1,364 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use serde_derive::Deserialize;
+
+use crate::config::types::Command;
+
+const COMPILE_COMMANDS: &str = "compile_commands.json";
+
+/// Entry of compile_commands.json
+#[derive(Deserialize, Debug, Clone)]
+struct CompileCommand {
+    directory: PathBuf,
+    file: PathBuf,
+    command: Option<String>,
+    arguments: Option<Vec<String>>,
+    output: Option<String>,
+}
+
+impl CompileCommand {
+    fn arguments(&self) -> Vec<String> {
+        self.arguments
+            .clone()
+            .unwrap_or_else(|| split_command(self.command.as_deref().unwrap_or_default()))
+    }
+
+    // CMake puts objects of a target in `CMakeFiles/<target>.dir/`
+    fn cmake_target(&self) -> Option<String> {
+        let arguments = self.arguments();
+        let output = self.output.clone().or_else(|| {
+            arguments
+                .iter()
+                .position(|a| a == "-o")
+                .and_then(|i| arguments.get(i + 1).cloned())
+        })?;
+        let dir = Path::new(&output)
+            .ancestors()
+            .filter_map(|p| p.file_name())
+            .find(|name| name.to_string_lossy().ends_with(".dir"))?
+            .to_string_lossy()
+            .into_owned();
+        Some(dir.trim_end_matches(".dir").to_string())
+    }
+
+    // Arguments to compile and link the file alone into `output`
+    fn link_arguments(&self, output: &Path) -> Vec<String> {
+        let mut res = Vec::new();
+        let mut arguments = self.arguments().into_iter();
+        while let Some(a) = arguments.next() {
+            match a.as_str() {
+                "-c" => {}
+                "-o" => {
+                    arguments.next();
+                }
+                _ if a.starts_with("-o") => {}
+                _ => res.push(a),
+            }
+        }
+        res.push("-o".to_string());
+        res.push(output.to_string_lossy().into_owned());
+        res
+    }
+}
+
+// Split a shell command line with quotes and backslashes
+fn split_command(command: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(c) = chars.next() {
+                    current.get_or_insert_with(String::new).push(c);
+                }
+            }
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => res.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    res.extend(current);
+    res
+}
+
+/// C / C++ project built with CMake or described by a compilation database
+#[derive(Debug, Clone)]
+pub struct CppProject {
+    file: PathBuf,
+    // Directory containing compile_commands.json
+    database_dir: Option<PathBuf>,
+    // Top level CMakeLists.txt
+    cmake_root: Option<PathBuf>,
+    // Entry for the file
+    entry: Option<CompileCommand>,
+}
+
+/// Find the directory of compile_commands.json for `file` (`build/` is also searched)
+pub fn compile_commands_dir(file: &Path) -> Option<PathBuf> {
+    file.ancestors().skip(1).find_map(|dir| {
+        [dir.to_path_buf(), dir.join("build")]
+            .iter()
+            .find(|d| d.join(COMPILE_COMMANDS).is_file())
+            .cloned()
+    })
+}
+
+impl CppProject {
+    pub fn find(file: &Path) -> Option<Self> {
+        let file = file.canonicalize().ok()?;
+        let database_dir = compile_commands_dir(&file);
+        let cmake_root = file
+            .ancestors()
+            .skip(1)
+            .filter(|dir| dir.join("CMakeLists.txt").is_file())
+            .last()
+            .map(Path::to_path_buf);
+        if database_dir.is_none() && cmake_root.is_none() {
+            return None;
+        }
+        let entry = database_dir.as_ref().and_then(|dir| {
+            let database: Vec<CompileCommand> =
+                serde_json::from_str(&fs::read_to_string(dir.join(COMPILE_COMMANDS)).ok()?).ok()?;
+            database.into_iter().find(|e| {
+                e.directory
+                    .join(&e.file)
+                    .canonicalize()
+                    .map(|p| p == file)
+                    .unwrap_or(false)
+            })
+        });
+
+        Some(Self {
+            file,
+            database_dir,
+            cmake_root,
+            entry,
+        })
+    }
+
+    // Build directory configured by CMake
+    fn cmake_build_dir(&self) -> Option<&Path> {
+        self.database_dir
+            .as_deref()
+            .filter(|dir| dir.join("CMakeCache.txt").is_file())
+    }
+
+    /// false until CMake creates compile_commands.json of the project
+    pub fn is_configured(&self) -> bool {
+        self.database_dir.is_some()
+    }
+
+    fn target(&self) -> Option<String> {
+        self.entry.as_ref().and_then(CompileCommand::cmake_target)
+    }
+
+    // Build directory of CMake for the options. Options other than the configured ones get their own directory.
+    // None if the file is compiled alone with flags from the database.
+    fn cmake_dir(&self, extra_options: &[String]) -> Option<PathBuf> {
+        let build_dir = match (self.cmake_build_dir(), &self.entry, &self.cmake_root) {
+            (Some(dir), _, _) => dir.to_path_buf(),
+            (None, None, Some(root)) => root.join("build"),
+            _ => return None,
+        };
+        if extra_options.is_empty() {
+            return Some(build_dir);
+        }
+        self.cmake_root.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        extra_options.hash(&mut hasher);
+        Some(build_dir.join(format!("acc-{:016x}", hasher.finish())))
+    }
+
+    // Executable linked from the file alone, as `$FILE_STEM` next to it
+    fn executable(&self) -> PathBuf {
+        self.file.with_extension("")
+    }
+
+    /// Steps to build the file. Empty if this project doesn't know how to build it.
+    pub fn build_steps(&self, extra_options: &[String]) -> Vec<process::Command> {
+        if let Some(build_dir) = self.cmake_dir(extra_options) {
+            let mut steps = Vec::new();
+            // Configure first
+            if !build_dir.join("CMakeCache.txt").is_file() {
+                let root = match self.cmake_root.as_ref() {
+                    Some(root) => root,
+                    None => return Vec::new(),
+                };
+                let mut configure = process::Command::new("cmake");
+                configure
+                    .arg("-S")
+                    .arg(root)
+                    .arg("-B")
+                    .arg(&build_dir)
+                    .arg("-DCMAKE_EXPORT_COMPILE_COMMANDS=ON");
+                if !extra_options.is_empty() {
+                    let flags = extra_options.join(" ");
+                    configure
+                        .arg(format!("-DCMAKE_C_FLAGS={}", flags))
+                        .arg(format!("-DCMAKE_CXX_FLAGS={}", flags));
+                }
+                steps.push(configure);
+            }
+            let mut build = process::Command::new("cmake");
+            build.arg("--build").arg(&build_dir);
+            if let Some(target) = self.target() {
+                build.arg("--target").arg(target);
+            }
+            steps.push(build);
+            steps
+        } else if let Some(entry) = self.entry.as_ref() {
+            // Compile the file alone with flags from the database
+            let arguments = entry.link_arguments(&self.executable());
+            if let Some((program, args)) = arguments.split_first() {
+                let mut command = process::Command::new(program);
+                command
+                    .args(args)
+                    .args(extra_options)
+                    .current_dir(&entry.directory);
+                vec![command]
+            } else {
+                Vec::new()
+            }
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Command to run the executable of the CMake target or the one linked from the file
+    pub fn run_command(&self, extra_options: &[String]) -> Option<Command> {
+        let executable = match self.cmake_dir(extra_options) {
+            Some(build_dir) => {
+                let entry = self.entry.as_ref()?;
+                // Targets in subdirectories are built in the same subdirectories of the build directory
+                let dir = match self.cmake_build_dir() {
+                    Some(configured) => entry
+                        .directory
+                        .strip_prefix(configured)
+                        .map(|sub| build_dir.join(sub))
+                        .unwrap_or_else(|_| entry.directory.clone()),
+                    None => entry.directory.clone(),
+                };
+                dir.join(self.target()?)
+            }
+            None => {
+                self.entry.as_ref()?;
+                self.executable()
+            }
+        };
+        Some(Command {
+            program: executable.to_string_lossy().into_owned(),
+            args: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_command, CompileCommand, CppProject};
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(r#"/usr/bin/c++  -DNAME=\"x\" -I"/a b" -o 'out file.o'  -c main.cpp"#),
+            vec![
+                "/usr/bin/c++",
+                "-DNAME=\"x\"",
+                "-I/a b",
+                "-o",
+                "out file.o",
+                "-c",
+                "main.cpp"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cmake_target() {
+        let entry: CompileCommand = serde_json::from_str(
+            r#"{"directory":"/p/build","command":"/usr/bin/c++ -o CMakeFiles/solve.dir/src/main.cpp.o -c /p/src/main.cpp","file":"/p/src/main.cpp"}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.cmake_target(), Some("solve".to_string()));
+    }
+
+    #[test]
+    fn test_link_arguments() {
+        let entry: CompileCommand = serde_json::from_str(
+            r#"{"directory":"/p","arguments":["cc","-Iinc","-c","a.c","-oa.o"],"file":"a.c"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            entry.link_arguments(Path::new("/p/a")),
+            vec!["cc", "-Iinc", "a.c", "-o", "/p/a"]
+        );
+    }
+
+    #[test]
+    fn test_run_command() {
+        let dir = TempDir::new("cpp");
+        let build_dir = dir.join("build");
+        fs::create_dir_all(&build_dir).unwrap();
+        fs::write(build_dir.join("CMakeCache.txt"), "").unwrap();
+        let entry = CompileCommand {
+            directory: build_dir.join("sub"),
+            file: dir.join("sub").join("main.cpp"),
+            command: None,
+            arguments: Some(
+                [
+                    "c++",
+                    "-o",
+                    "CMakeFiles/solve.dir/main.cpp.o",
+                    "-c",
+                    "main.cpp",
+                ]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            ),
+            output: None,
+        };
+        let project = CppProject {
+            file: entry.file.clone(),
+            database_dir: Some(build_dir.clone()),
+            cmake_root: Some(dir.to_path_buf()),
+            entry: Some(entry),
+        };
+        let program = |project: &CppProject, options: &[String]| {
+            project.run_command(options).map(|c| c.program)
+        };
+        let solve = build_dir.join("sub").join("solve");
+        assert_eq!(program(&project, &[]), Some(solve.display().to_string()));
+        // Options are built in another directory
+        let steps = project.build_steps(&["-O2".to_string()]);
+        assert_eq!(steps.len(), 2);
+        let optimized = program(&project, &["-O2".to_string()]).unwrap();
+        assert_ne!(optimized, solve.display().to_string());
+        assert!(optimized.ends_with("sub/solve"));
+        assert!(optimized.starts_with(&build_dir.display().to_string()));
+
+        // Linked alone without CMake
+        let project = CppProject {
+            database_dir: Some(dir.to_path_buf()),
+            cmake_root: None,
+            ..project
+        };
+        assert_eq!(
+            program(&project, &[]),
+            Some(dir.join("sub").join("main").display().to_string())
+        );
+    }
+}
@@ -130,6 +130,12 @@ impl<'a> LinenumView<'a> {
         self.view.newline();
         self.put_linenum();
     }
+
+    // Continue to the next row without a line number
+    pub fn virtual_newline(&mut self) {
+        self.view.newline();
+        self.put_space();
+    }
 }
 
 impl Default for Term {
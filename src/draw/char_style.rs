@@ -126,6 +126,15 @@ pub mod styles {
         bg: Color::Rgb { r: 0, g: 0, b: 0 },
         modification: CharModification::Default,
     };
+    pub const LINK: CharStyle = CharStyle {
+        fg: Color::Rgb {
+            r: 255,
+            g: 255,
+            b: 255,
+        },
+        bg: Color::Reset,
+        modification: CharModification::UnderLine,
+    };
     pub const UI: CharStyle = CharStyle {
         fg: Color::Rgb {
             r: 128,
@@ -1,14 +1,16 @@
 use std::cmp::min;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::time::{Duration, Instant};
 
 use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter};
 use syntect::parsing::SyntaxSet;
-use syntect::parsing::{ParseState, ScopeStack, ScopeStackOp};
+use syntect::parsing::{ParseState, Scope, ScopeStack, ScopeStackOp};
 
 use crate::core::{CoreBuffer, Cursor};
 use crate::draw::CharStyle;
 use crate::draw::Color;
+use crate::lsp::LSPSemanticToken;
 use crate::parenthesis;
 use crate::syntax;
 
@@ -152,6 +154,28 @@ impl DrawState {
     }
 }
 
+// TextMate scope used to color a semantic token type
+fn semantic_token_scope(token_type: &str) -> Option<&'static str> {
+    Some(match token_type {
+        "namespace" => "entity.name.namespace",
+        "type" | "class" | "enum" | "interface" | "struct" | "typeParameter" | "typeAlias"
+        | "builtinType" => "entity.name.type",
+        "function" | "method" => "entity.name.function",
+        "macro" => "entity.name.function.macro",
+        "variable" => "variable",
+        "parameter" => "variable.parameter",
+        "property" | "member" | "field" => "variable.other.member",
+        "enumMember" => "constant.other",
+        "keyword" => "keyword",
+        "comment" => "comment",
+        "string" => "string",
+        "number" => "constant.numeric",
+        "operator" => "keyword.operator",
+        "lifetime" => "storage.modifier.lifetime",
+        _ => return None,
+    })
+}
+
 pub struct DrawCache<'a> {
     syntax: &'a syntect::parsing::SyntaxReference,
     syntax_set: &'a syntect::parsing::SyntaxSet,
@@ -159,6 +183,8 @@ pub struct DrawCache<'a> {
     state_cache: Vec<DrawState>,
     draw_cache: HashMap<usize, Vec<(char, CharStyle)>>,
     draw_cache_pseudo: HashMap<usize, Vec<(char, CharStyle)>>,
+    // Foreground colors from LSP semantic tokens per line
+    semantic: HashMap<usize, Vec<(Range<usize>, Color)>>,
 }
 
 impl<'a> DrawCache<'a> {
@@ -172,10 +198,49 @@ impl<'a> DrawCache<'a> {
             state_cache: Vec::new(),
             draw_cache: HashMap::new(),
             draw_cache_pseudo: HashMap::new(),
+            semantic: HashMap::new(),
             bg,
         }
     }
 
+    /// Blend semantic tokens into syntect styles. Token types the theme has no color for are ignored.
+    pub fn set_semantic_tokens(
+        &mut self,
+        tokens: &[LSPSemanticToken],
+        highlighter: &syntect::highlighting::Highlighter,
+    ) {
+        let default = highlighter.style_for_stack(&[]).foreground;
+        let mut colors = HashMap::new();
+        self.semantic.clear();
+        for token in tokens {
+            let color = colors.entry(token.token_type.as_str()).or_insert_with(|| {
+                semantic_token_scope(&token.token_type)
+                    .and_then(|scope| Scope::new(scope).ok())
+                    .map(|scope| highlighter.style_for_stack(&[scope]).foreground)
+                    .filter(|&fg| fg != default)
+            });
+            if let Some(color) = *color {
+                self.semantic
+                    .entry(token.cursor.row)
+                    .or_default()
+                    .push((token.cursor.col..token.cursor.col + token.len, color.into()));
+            }
+        }
+        self.draw_cache.clear();
+        self.draw_cache_pseudo.clear();
+    }
+
+    fn blend_semantic(&self, i: usize, draw: &mut [(char, CharStyle)]) {
+        if let Some(tokens) = self.semantic.get(&i) {
+            let len = draw.len();
+            for (range, color) in tokens {
+                for (_, style) in draw[min(range.start, len)..min(range.end, len)].iter_mut() {
+                    style.fg = *color;
+                }
+            }
+        }
+    }
+
     fn start_state(&self, highlighter: &syntect::highlighting::Highlighter) -> DrawState {
         DrawState::new(self.syntax, highlighter)
     }
@@ -246,7 +311,7 @@ impl<'a> DrawCache<'a> {
                         i - (i % Self::CACHE_WIDTH) + Self::CACHE_WIDTH,
                     )
                 {
-                    let draw = state.highlight(
+                    let mut draw = state.highlight(
                         buffer
                             .get_range(
                                 Cursor { row: i, col: 0 }..Cursor {
@@ -259,6 +324,7 @@ impl<'a> DrawCache<'a> {
                         &highlighter,
                         self.bg,
                     );
+                    self.blend_semantic(i, &mut draw);
 
                     self.draw_cache.insert(i, draw);
                 }
@@ -273,7 +339,7 @@ impl<'a> DrawCache<'a> {
                     i - (i % Self::CACHE_WIDTH) + Self::CACHE_WIDTH,
                 )
             {
-                let draw = state.highlight(
+                let mut draw = state.highlight(
                     buffer
                         .get_range(
                             Cursor { row: i, col: 0 }..Cursor {
@@ -286,6 +352,7 @@ impl<'a> DrawCache<'a> {
                     &highlighter,
                     self.bg,
                 );
+                self.blend_semantic(i, &mut draw);
 
                 self.draw_cache_pseudo.insert(i, draw);
             }
@@ -307,3 +374,29 @@ impl<'a> DrawCache<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::semantic_token_scope;
+
+    #[test]
+    fn test_semantic_token_scope() {
+        let table = [
+            ("namespace", Some("entity.name.namespace")),
+            ("struct", Some("entity.name.type")),
+            ("typeParameter", Some("entity.name.type")),
+            ("method", Some("entity.name.function")),
+            ("macro", Some("entity.name.function.macro")),
+            ("parameter", Some("variable.parameter")),
+            ("field", Some("variable.other.member")),
+            ("enumMember", Some("constant.other")),
+            ("lifetime", Some("storage.modifier.lifetime")),
+            // Left to the syntax highlighting
+            ("unknownType", None),
+            ("", None),
+        ];
+        for (token_type, scope) in table.iter() {
+            assert_eq!(semantic_token_scope(token_type), *scope, "{}", token_type);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LineEnding::Lf => write!(f, "LF"),
+            LineEnding::CrLf => write!(f, "CRLF"),
+        }
+    }
+}
+
+/// Encoding and line endings of a file. Buffers always hold UTF-8 text with `\n`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FileFormat {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+    // Some bytes were invalid in the encoding and decoded as U+FFFD. Saving writes U+FFFD back.
+    pub invalid_bytes: bool,
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+            line_ending: LineEnding::Lf,
+            invalid_bytes: false,
+        }
+    }
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.encoding.name())?;
+        if self.bom {
+            write!(f, " BOM")?;
+        }
+        write!(f, " {}", self.line_ending)
+    }
+}
+
+// The line ending used by most lines
+fn detect_line_ending(text: &str) -> LineEnding {
+    let lf = text.matches('\n').count();
+    let crlf = text.matches("\r\n").count();
+    if crlf > lf - crlf {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    }
+}
+
+impl FileFormat {
+    /// Detect the format of `bytes` and decode them into text with `\n` line endings
+    pub fn decode(bytes: &[u8]) -> (String, Self) {
+        let (encoding, bom_len) = match Encoding::for_bom(bytes) {
+            Some((encoding, bom_len)) => (encoding, bom_len),
+            None if std::str::from_utf8(bytes).is_ok() => (UTF_8, 0),
+            None => {
+                let mut detector = chardetng::EncodingDetector::new();
+                detector.feed(bytes, true);
+                (detector.guess(None, true), 0)
+            }
+        };
+        let (text, invalid_bytes) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        let line_ending = detect_line_ending(&text);
+        let text = match line_ending {
+            LineEnding::CrLf => text.replace("\r\n", "\n"),
+            LineEnding::Lf => text.into_owned(),
+        };
+        (
+            text,
+            Self {
+                encoding,
+                bom: bom_len > 0,
+                line_ending,
+                invalid_bytes,
+            },
+        )
+    }
+
+    /// Encode text with `\n` line endings into this format
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        let text = match self.line_ending {
+            LineEnding::CrLf => Cow::Owned(text.replace('\n', "\r\n")),
+            LineEnding::Lf => Cow::Borrowed(text),
+        };
+        // encoding_rs encodes UTF-16 into UTF-8
+        let mut bytes = if self.encoding == UTF_16LE {
+            let mut bytes = if self.bom {
+                vec![0xff, 0xfe]
+            } else {
+                Vec::new()
+            };
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        } else if self.encoding == UTF_16BE {
+            let mut bytes = if self.bom {
+                vec![0xfe, 0xff]
+            } else {
+                Vec::new()
+            };
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            bytes
+        } else {
+            let (encoded, _, had_errors) = self.encoding.encode(&text);
+            if had_errors {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Some characters cannot be encoded in {}",
+                        self.encoding.name()
+                    ),
+                ));
+            }
+            encoded.into_owned()
+        };
+        if self.bom && self.encoding == UTF_8 {
+            bytes.splice(0..0, [0xef, 0xbb, 0xbf].iter().cloned());
+        }
+        Ok(bytes)
+    }
+
+    /// Plain UTF-8 needs no conversion
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FileFormat, LineEnding};
+    use encoding_rs::{SHIFT_JIS, UTF_16LE, UTF_8};
+
+    #[test]
+    fn test_round_trip() {
+        let crlf = b"a\r\nb\r\n";
+        let (text, format) = FileFormat::decode(crlf);
+        assert_eq!(text, "a\nb\n");
+        assert_eq!(format.line_ending, LineEnding::CrLf);
+        assert_eq!(format.encoding, UTF_8);
+        assert_eq!(format.encode(&text).unwrap(), crlf);
+
+        let bom = b"\xef\xbb\xbfa\n";
+        let (text, format) = FileFormat::decode(bom);
+        assert_eq!(text, "a\n");
+        assert!(format.bom);
+        assert_eq!(format.to_string(), "UTF-8 BOM LF");
+        assert_eq!(format.encode(&text).unwrap(), bom);
+
+        let utf16 = b"\xff\xfea\x00\n\x00";
+        let (text, format) = FileFormat::decode(utf16);
+        assert_eq!(text, "a\n");
+        assert_eq!(format.encoding, UTF_16LE);
+        assert_eq!(format.encode(&text).unwrap(), utf16);
+
+        let (sjis, _, _) = SHIFT_JIS.encode("こんにちは、世界。日本語のテキストです。\n");
+        let (text, format) = FileFormat::decode(&sjis);
+        assert_eq!(format.encoding, SHIFT_JIS);
+        assert_eq!(text, "こんにちは、世界。日本語のテキストです。\n");
+        assert_eq!(format.encode(&text).unwrap(), sjis.into_owned());
+        assert!(format.encode("😀").is_err());
+        assert!(!format.invalid_bytes);
+
+        // A broken trailing code unit
+        let (text, format) = FileFormat::decode(b"\xff\xfea\x00b");
+        assert_eq!(text, "a\u{fffd}");
+        assert!(format.invalid_bytes);
+    }
+}
@@ -1,6 +1,11 @@
 use std::io::Write;
 use std::process;
 
+use crate::core::{Cursor, TextEdit};
+
+// Give up line diff and replace whole changed region if the DP table is larger than this
+const DIFF_LIMIT: usize = 4_000_000;
+
 pub fn system_format(mut command: process::Command, src: &str) -> Option<String> {
     let mut command = command
         .stdin(process::Stdio::piped())
@@ -22,3 +27,129 @@ pub fn system_format(mut command: process::Command, src: &str) -> Option<String>
     let out = String::from_utf8(stdout).ok()?;
     Some(out.replace("\r\n", "\n"))
 }
+
+/// Line based diff from `old` to `new` as edits which can be passed to `Core::apply_edits`
+pub fn diff_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let n = a_mid.len();
+    let m = b_mid.len();
+
+    // (old_start, old_end, new_start, new_end) in a_mid and b_mid
+    let mut hunks = Vec::new();
+    if (n + 1) * (m + 1) <= DIFF_LIMIT {
+        // lcs[i][j] = LCS length of a_mid[i..] and b_mid[j..]
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if a_mid[i] == b_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        let mut start = None;
+        while i < n && j < m {
+            if a_mid[i] == b_mid[j] {
+                if let Some((si, sj)) = start.take() {
+                    hunks.push((si, i, sj, j));
+                }
+                i += 1;
+                j += 1;
+            } else {
+                if start.is_none() {
+                    start = Some((i, j));
+                }
+                if lcs[i + 1][j] >= lcs[i][j + 1] {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+        }
+        if i < n || j < m || start.is_some() {
+            let (si, sj) = start.unwrap_or((i, j));
+            hunks.push((si, n, sj, m));
+        }
+    } else if n > 0 || m > 0 {
+        hunks.push((0, n, 0, m));
+    }
+
+    let line_start = |row: usize| {
+        if row < a.len() || a.last().map(|l| l.ends_with('\n')).unwrap_or(true) {
+            Cursor { row, col: 0 }
+        } else {
+            // End of the last line which has no newline
+            Cursor {
+                row: a.len() - 1,
+                col: a[a.len() - 1].chars().count(),
+            }
+        }
+    };
+
+    hunks
+        .into_iter()
+        .map(|(i1, i2, j1, j2)| TextEdit {
+            range: line_start(prefix + i1)..line_start(prefix + i2),
+            text: b_mid[j1..j2].concat(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::diff_edits;
+    use crate::core::buffer::RopeyCoreBuffer;
+    use crate::core::{Core, Cursor};
+
+    fn apply(old: &str, new: &str, cursor: Cursor) -> Core<RopeyCoreBuffer> {
+        let mut core = Core::<RopeyCoreBuffer>::from_reader(old.as_bytes()).unwrap();
+        core.set_cursor(cursor);
+        core.apply_edits(diff_edits(old, new));
+        assert_eq!(core.get_string(), new);
+        core
+    }
+
+    #[test]
+    fn test_diff_edits() {
+        assert!(diff_edits("a\nb\n", "a\nb\n").is_empty());
+        assert_eq!(diff_edits("a\nb\nc", "a\nx\nc").len(), 1);
+        assert_eq!(diff_edits("a\nb\nc\nd\ne", "x\nb\nc\nd\ny").len(), 2);
+
+        apply("", "abc", Cursor { row: 0, col: 0 });
+        apply("abc", "", Cursor { row: 0, col: 0 });
+        apply("a\nb", "a\nb\n", Cursor { row: 0, col: 0 });
+        apply("a\nb\n", "a\nb", Cursor { row: 0, col: 0 });
+        apply("a\nb\nc", "c\nb\na", Cursor { row: 0, col: 0 });
+    }
+
+    #[test]
+    fn test_diff_edits_keep_cursor() {
+        let core = apply(
+            "fn main(){\nlet x=1;\n    let y = 2;\n}",
+            "fn main() {\n    let x = 1;\n    let y = 2;\n}\n",
+            Cursor { row: 2, col: 8 },
+        );
+        assert_eq!(core.cursor(), Cursor { row: 2, col: 8 });
+
+        let core = apply("a\nb\nc\n", "a\n\nb\nc\n", Cursor { row: 2, col: 1 });
+        assert_eq!(core.cursor(), Cursor { row: 3, col: 1 });
+
+        let core = apply("abc\n", "abcd\n", Cursor { row: 0, col: 1 });
+        assert_eq!(core.cursor(), Cursor { row: 0, col: 1 });
+    }
+}
@@ -1,30 +1,101 @@
-use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc};
+use std::collections::VecDeque;
+use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc, Mutex};
+use std::time::Instant;
 
+// Runs only the latest job of each key in order. Pending jobs of the same key are coalesced and
+// a running job is cancelled (its future is dropped) when a newer one of the same key is sent.
 pub struct JobQueue<S, T> {
     jobs: Arc<AtomicUsize>,
+    running_since: Arc<Mutex<Option<Instant>>>,
     s_tx: tokio::sync::mpsc::UnboundedSender<S>,
     t_rx: tokio::sync::mpsc::UnboundedReceiver<T>,
 }
 
+// Replace the pending job of the same key or queue a new one
+fn push_job<S, K: PartialEq>(
+    pending: &mut VecDeque<S>,
+    s: S,
+    key: &impl Fn(&S) -> K,
+    jobs: &AtomicUsize,
+) {
+    let k = key(&s);
+    if let Some(old) = pending.iter_mut().find(|old| key(old) == k) {
+        jobs.fetch_sub(1, Ordering::Relaxed);
+        *old = s;
+    } else {
+        pending.push_back(s);
+    }
+}
+
 impl<S: Send + 'static, T: Send + 'static> JobQueue<S, T> {
-    pub fn new<F: FnMut(S) -> futures::future::BoxFuture<'static, T> + Send + Sync + 'static>(
+    /// A job supersedes only the previous ones with the same `key`
+    pub fn with_key<
+        F: FnMut(S) -> futures::future::BoxFuture<'static, T> + Send + Sync + 'static,
+        K: PartialEq + Send + 'static,
+        G: Fn(&S) -> K + Send + Sync + 'static,
+    >(
         mut func: F,
+        key: G,
     ) -> Self {
         let jobs = Arc::new(AtomicUsize::new(0));
+        let running_since = Arc::new(Mutex::new(None));
         let (s_tx, mut s_rx) = tokio::sync::mpsc::unbounded_channel();
         let (t_tx, t_rx) = tokio::sync::mpsc::unbounded_channel();
 
         let j = jobs.clone();
+        let since = running_since.clone();
         tokio::spawn(async move {
-            while let Some(s) = s_rx.recv().await {
-                if t_tx.send(func(s).await).is_err() {
-                    return;
+            let mut pending = VecDeque::new();
+            loop {
+                if pending.is_empty() {
+                    match s_rx.recv().await {
+                        Some(s) => pending.push_back(s),
+                        None => return,
+                    }
+                }
+                while let Ok(newer) = s_rx.try_recv() {
+                    push_job(&mut pending, newer, &key, &j);
+                }
+                let s = pending.pop_front().unwrap();
+                let running_key = key(&s);
+
+                *since.lock().unwrap() = Some(Instant::now());
+                let mut job = func(s);
+                loop {
+                    tokio::select! {
+                        t = &mut job => {
+                            *since.lock().unwrap() = None;
+                            if t_tx.send(t).is_err() {
+                                return;
+                            }
+                            j.fetch_sub(1, Ordering::Relaxed);
+                            break;
+                        }
+                        newer = s_rx.recv() => {
+                            let newer = match newer {
+                                Some(s) => s,
+                                None => return,
+                            };
+                            let is_stale = key(&newer) == running_key;
+                            push_job(&mut pending, newer, &key, &j);
+                            if is_stale {
+                                // Cancel the stale job
+                                *since.lock().unwrap() = None;
+                                j.fetch_sub(1, Ordering::Relaxed);
+                                break;
+                            }
+                        }
+                    }
                 }
-                j.fetch_sub(1, Ordering::Relaxed);
             }
         });
 
-        Self { jobs, s_tx, t_rx }
+        Self {
+            jobs,
+            running_since,
+            s_tx,
+            t_rx,
+        }
     }
 
     pub fn rx(&mut self) -> &mut tokio::sync::mpsc::UnboundedReceiver<T> {
@@ -39,4 +110,62 @@ impl<S: Send + 'static, T: Send + 'static> JobQueue<S, T> {
     pub fn is_running(&self) -> bool {
         self.jobs.load(Ordering::Relaxed) != 0
     }
+
+    /// When the current job started
+    pub fn running_since(&self) -> Option<Instant> {
+        *self.running_since.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::JobQueue;
+    use futures::FutureExt;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_stale_jobs() {
+        let mut queue = JobQueue::with_key(
+            |n: u64| {
+                async move {
+                    tokio::time::delay_for(Duration::from_millis(n)).await;
+                    n
+                }
+                .boxed()
+            },
+            |_| (),
+        );
+        queue.send(1000).unwrap();
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+        assert!(queue.running_since().is_some());
+        queue.send(900).unwrap();
+        queue.send(10).unwrap();
+        assert_eq!(queue.rx().recv().await, Some(10));
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+        assert!(!queue.is_running());
+        assert!(queue.running_since().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_keep_jobs_of_other_keys() {
+        // (key, delay)
+        let mut queue = JobQueue::with_key(
+            |(key, n): (char, u64)| {
+                async move {
+                    tokio::time::delay_for(Duration::from_millis(n)).await;
+                    (key, n)
+                }
+                .boxed()
+            },
+            |&(key, _)| key,
+        );
+        queue.send(('a', 300)).unwrap();
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+        queue.send(('b', 100)).unwrap();
+        queue.send(('b', 10)).unwrap();
+        assert_eq!(queue.rx().recv().await, Some(('a', 300)));
+        assert_eq!(queue.rx().recv().await, Some(('b', 10)));
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+        assert!(!queue.is_running());
+    }
 }
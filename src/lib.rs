@@ -3,25 +3,36 @@
 pub mod buffer;
 pub mod buffer_mode;
 pub mod buffer_tab;
+mod cargo;
+pub mod cli;
 mod clipboard;
 mod compiler;
 pub mod config;
 pub mod core;
+mod cpp_project;
 mod cursor;
 pub mod draw;
 mod draw_cache;
 mod env;
+pub mod file_format;
 mod formatter;
 mod indent;
 mod job_queue;
 mod lsp;
 mod mode;
 pub mod parenthesis;
+mod recent;
 mod rmate;
 mod rustc;
+pub mod session;
+mod snippet;
+pub mod ssh;
 pub mod storage;
+mod swap;
 pub mod syntax;
 mod tabnine;
+#[cfg(test)]
+mod temp_dir;
 mod text_object;
 pub mod theme;
 
@@ -1,6 +1,13 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use jsonrpc_core;
@@ -9,149 +16,952 @@ use lsp_types;
 use serde;
 use serde_json;
 use tokio::prelude::*;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
 
+use crate::config::types::Command;
 use crate::core::Cursor;
+use crate::core::TextEdit;
 
-#[derive(Debug)]
+mod proposed;
+
+#[derive(Debug, Clone)]
 pub struct LSPCompletion {
     pub keyword: String,
     pub doc: String,
+    pub kind: Option<lsp_types::CompletionItemKind>,
+    pub filter_text: String,
+    pub sort_text: String,
+    pub insert_text: String,
+    pub is_snippet: bool,
+    // Range to be replaced by `insert_text`
+    pub text_edit: Option<Range<Cursor>>,
+    pub additional_edits: Vec<TextEdit>,
+    pub documentation: Option<String>,
+    item: lsp_types::CompletionItem,
+}
+
+impl LSPCompletion {
+    pub fn kind_icon(&self) -> char {
+        use lsp_types::CompletionItemKind::*;
+        match self.kind {
+            Some(Method) | Some(Function) | Some(Constructor) => 'f',
+            Some(Field) | Some(Property) => 'm',
+            Some(Variable) | Some(Value) | Some(Constant) | Some(Unit) => 'v',
+            Some(Class) | Some(Interface) | Some(Struct) | Some(Enum) | Some(TypeParameter) => 't',
+            Some(EnumMember) => 'e',
+            Some(Module) | Some(File) | Some(Folder) | Some(Reference) => 'M',
+            Some(Keyword) => 'k',
+            Some(Snippet) => 's',
+            Some(Operator) => 'o',
+            _ => ' ',
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LSPCompletionList {
+    pub items: Vec<LSPCompletion>,
+    // Further typing should result in recomputing this list
+    pub is_incomplete: bool,
+}
+
+#[derive(Debug)]
+pub struct LSPSemanticToken {
+    pub cursor: Cursor,
+    pub len: usize,
+    pub token_type: String,
+}
+
+#[derive(Debug)]
+pub struct LSPInlayHint {
+    pub cursor: Cursor,
+    // Including padding
+    pub label: String,
+}
+
+#[derive(Debug)]
+pub struct LSPSymbol {
+    pub name: String,
+    pub kind: lsp_types::SymbolKind,
+    pub container: Option<String>,
+    // None if the symbol is in the current document
+    pub path: Option<PathBuf>,
+    pub cursor: Cursor,
+}
+
+enum LSPRequest {
+    Completion(String, Cursor),
+    DocumentSymbol(String),
+    WorkspaceSymbol(String),
+    Formatting(String, lsp_types::FormattingOptions, Option<Range<Cursor>>),
+    Resolve(Box<lsp_types::CompletionItem>),
+    // Semantic tokens and inlay hints
+    Decoration(String),
+}
+
+// Receivers of responses are owned by each LSPClient
+#[derive(Clone)]
+struct ClientSender {
+    completion: UnboundedSender<LSPCompletionList>,
+    resolve: UnboundedSender<LSPCompletion>,
+    symbol: UnboundedSender<Vec<LSPSymbol>>,
+    format: UnboundedSender<Result<Vec<TextEdit>, String>>,
+    semantic_tokens: UnboundedSender<Vec<LSPSemanticToken>>,
+    inlay_hints: UnboundedSender<Vec<LSPInlayHint>>,
+}
+
+struct Document {
+    url: lsp_types::Url,
+    language_id: String,
+    // None if not opened in the current process
+    version: Option<i64>,
+    sender: ClientSender,
+}
+
+enum ServerMessage {
+    Open(usize, Document),
+    Close(usize),
+    Request(usize, LSPRequest),
+    Restart,
+    Shutdown(oneshot::Sender<()>),
+}
+
+enum ResponseKind {
+    Completion,
+    Resolve,
+    DocumentSymbol,
+    WorkspaceSymbol,
+    Formatting,
+    // With token types of the legend
+    SemanticTokens(Arc<Vec<String>>),
+    InlayHint,
+}
+
+// Optional features reported in the initialize result
+#[derive(Default)]
+struct ServerFeatures {
+    semantic_tokens_legend: Option<Arc<Vec<String>>>,
+    inlay_hint: bool,
+}
+
+enum Pending {
+    Init(oneshot::Sender<serde_json::Value>),
+    Shutdown(oneshot::Sender<()>),
+    Client {
+        kind: ResponseKind,
+        sender: ClientSender,
+        url: lsp_types::Url,
+    },
+}
+
+type PendingMap = Arc<Mutex<HashMap<u64, Pending>>>;
+
+enum ProcessExit {
+    Closed,
+    Restart,
+    Crashed,
+}
+
+// Server processes are shared among documents with the same command and workspace root
+#[derive(PartialEq, Eq, Hash)]
+struct ServerKey {
+    program: String,
+    args: Vec<String>,
+    root: lsp_types::Url,
+}
+
+struct ServerHandle {
+    tx: UnboundedSender<ServerMessage>,
+}
+
+#[derive(Clone, Default)]
+pub struct LSPPool {
+    servers: Rc<RefCell<HashMap<ServerKey, Weak<ServerHandle>>>>,
 }
 
 pub struct LSPClient {
-    process: tokio::process::Child,
-    completion_req: tokio::sync::mpsc::UnboundedSender<(String, Cursor)>,
-    completion_recv: tokio::sync::mpsc::UnboundedReceiver<Vec<LSPCompletion>>,
+    id: usize,
+    server: Rc<ServerHandle>,
+    completion_recv: UnboundedReceiver<LSPCompletionList>,
+    resolve_recv: UnboundedReceiver<LSPCompletion>,
+    symbol_recv: UnboundedReceiver<Vec<LSPSymbol>>,
+    format_recv: UnboundedReceiver<Result<Vec<TextEdit>, String>>,
+    semantic_tokens_recv: UnboundedReceiver<Vec<LSPSemanticToken>>,
+    inlay_hints_recv: UnboundedReceiver<Vec<LSPInlayHint>>,
 }
 
 impl Drop for LSPClient {
     fn drop(&mut self) {
-        let _ = self.process.kill();
+        let _ = self.server.tx.send(ServerMessage::Close(self.id));
     }
 }
 
-const ID_INIT: u64 = 0;
-const ID_COMPLETION: u64 = 1;
+static NEXT_CLIENT_ID: AtomicUsize = AtomicUsize::new(0);
 
-impl LSPClient {
-    pub fn start(lsp_command: process::Command, extension: String) -> anyhow::Result<Self> {
-        let mut lsp = tokio::process::Command::from(lsp_command)
-            .stdin(process::Stdio::piped())
-            .stdout(process::Stdio::piped())
-            .stderr(process::Stdio::piped())
-            .kill_on_drop(true)
-            .spawn()?;
-
-        #[allow(deprecated)]
-        let init = lsp_types::InitializeParams {
-            process_id: Some(u64::from(process::id())),
-            root_path: None,
-            root_uri: Some(lsp_types::Url::parse("file://localhost/")?),
-            initialization_options: None,
-            capabilities: lsp_types::ClientCapabilities::default(),
-            trace: None,
-            workspace_folders: None,
-            client_info: None,
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(5);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+const RESTART_BACKOFF_MIN: Duration = Duration::from_millis(500);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(30);
+// A server running longer than this is considered healthy again
+const RESTART_BACKOFF_RESET: Duration = Duration::from_secs(60);
+const MAX_RESTART: usize = 5;
+
+// Files marking the root of a workspace
+const ROOT_MARKERS: &[&str] = &[
+    ".git",
+    "Cargo.toml",
+    "compile_commands.json",
+    "CMakeLists.txt",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+];
+
+fn workspace_root(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| ROOT_MARKERS.iter().any(|marker| dir.join(marker).exists()))
+        .or_else(|| path.parent())
+}
+
+// Returns (root_uri, file_uri)
+fn document_url(
+    path: Option<&Path>,
+    extension: &str,
+    id: usize,
+) -> anyhow::Result<(lsp_types::Url, lsp_types::Url)> {
+    if let Some(path) = path {
+        let path = std::env::current_dir()?.join(path);
+        if let Some(root) = workspace_root(&path) {
+            if let (Ok(root), Ok(file)) = (
+                lsp_types::Url::from_directory_path(root),
+                lsp_types::Url::from_file_path(&path),
+            ) {
+                return Ok((root, file));
+            }
+        }
+    }
+    Ok((
+        lsp_types::Url::parse("file://localhost/")?,
+        lsp_types::Url::parse(&format!("file://localhost/main{}.{}", id, extension))?,
+    ))
+}
+
+fn spawn_server(command: &Command) -> anyhow::Result<tokio::process::Child> {
+    Ok(tokio::process::Command::from(command.command())
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?)
+}
+
+fn initialize_params(root_url: lsp_types::Url) -> lsp_types::InitializeParams {
+    #[allow(deprecated)]
+    lsp_types::InitializeParams {
+        process_id: Some(u64::from(process::id())),
+        root_path: None,
+        root_uri: Some(root_url),
+        initialization_options: None,
+        capabilities: lsp_types::ClientCapabilities {
+            text_document: Some(lsp_types::TextDocumentClientCapabilities {
+                completion: Some(lsp_types::CompletionCapability {
+                    completion_item: Some(lsp_types::CompletionItemCapability {
+                        snippet_support: Some(true),
+                        documentation_format: Some(vec![lsp_types::MarkupKind::PlainText]),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        trace: None,
+        workspace_folders: None,
+        client_info: None,
+    }
+}
+
+impl LSPPool {
+    /// Connect a document to the server for `command`. The server is started if it is not running.
+    pub fn client(
+        &self,
+        command: &Command,
+        extension: String,
+        path: Option<&Path>,
+    ) -> anyhow::Result<LSPClient> {
+        let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        let (root_url, file_url) = document_url(path, &extension, id)?;
+
+        let mut servers = self.servers.borrow_mut();
+        servers.retain(|_, server| server.upgrade().is_some());
+        let key = ServerKey {
+            program: command.program.clone(),
+            args: command.args.clone(),
+            root: root_url.clone(),
+        };
+        let server = if let Some(server) = servers.get(&key).and_then(Weak::upgrade) {
+            server
+        } else {
+            let child = spawn_server(command)?;
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(serve(command.clone(), root_url, child, rx));
+            let server = Rc::new(ServerHandle { tx });
+            servers.insert(key, Rc::downgrade(&server));
+            server
+        };
+
+        let (completion_tx, completion_recv) = tokio::sync::mpsc::unbounded_channel();
+        let (resolve_tx, resolve_recv) = tokio::sync::mpsc::unbounded_channel();
+        let (symbol_tx, symbol_recv) = tokio::sync::mpsc::unbounded_channel();
+        let (format_tx, format_recv) = tokio::sync::mpsc::unbounded_channel();
+        let (semantic_tokens_tx, semantic_tokens_recv) = tokio::sync::mpsc::unbounded_channel();
+        let (inlay_hints_tx, inlay_hints_recv) = tokio::sync::mpsc::unbounded_channel();
+        let document = Document {
+            url: file_url,
+            language_id: extension,
+            version: None,
+            sender: ClientSender {
+                completion: completion_tx,
+                resolve: resolve_tx,
+                symbol: symbol_tx,
+                format: format_tx,
+                semantic_tokens: semantic_tokens_tx,
+                inlay_hints: inlay_hints_tx,
+            },
+        };
+        server
+            .tx
+            .send(ServerMessage::Open(id, document))
+            .map_err(|_| anyhow::anyhow!("LSP is not running"))?;
+
+        Ok(LSPClient {
+            id,
+            server,
+            completion_recv,
+            resolve_recv,
+            symbol_recv,
+            format_recv,
+            semantic_tokens_recv,
+            inlay_hints_recv,
+        })
+    }
+
+    /// Send shutdown and exit to all running servers and wait for them
+    pub async fn shutdown(&self) {
+        let mut waits = Vec::new();
+        for server in self.servers.borrow().values().filter_map(Weak::upgrade) {
+            let (tx, rx) = oneshot::channel();
+            if server.tx.send(ServerMessage::Shutdown(tx)).is_ok() {
+                waits.push(rx);
+            }
+        }
+        for rx in waits {
+            let _ = tokio::time::timeout(SHUTDOWN_TIMEOUT * 2, rx).await;
+        }
+    }
+}
+
+// Supervise a server process and restart it with backoff when it crashes
+async fn serve(
+    command: Command,
+    root_url: lsp_types::Url,
+    child: tokio::process::Child,
+    mut rx: UnboundedReceiver<ServerMessage>,
+) {
+    let mut documents = HashMap::new();
+    let mut child = Some(child);
+    let mut backoff = RESTART_BACKOFF_MIN;
+    let mut restart = 0;
+
+    loop {
+        let started = Instant::now();
+        let exit = match child
+            .take()
+            .map(Ok)
+            .unwrap_or_else(|| spawn_server(&command))
+        {
+            Ok(child) => run_process(child, root_url.clone(), &mut rx, &mut documents).await,
+            Err(err) => Err(err),
+        };
+
+        match exit {
+            Ok(ProcessExit::Closed) => return,
+            Ok(ProcessExit::Restart) => {
+                backoff = RESTART_BACKOFF_MIN;
+                restart = 0;
+            }
+            Ok(ProcessExit::Crashed) | Err(_) => {
+                if started.elapsed() > RESTART_BACKOFF_RESET {
+                    backoff = RESTART_BACKOFF_MIN;
+                    restart = 0;
+                }
+                restart += 1;
+                if restart > MAX_RESTART {
+                    return;
+                }
+                tokio::time::delay_for(backoff).await;
+                backoff = std::cmp::min(backoff * 2, RESTART_BACKOFF_MAX);
+            }
+        }
+
+        // Documents are opened again in the new process
+        for document in documents.values_mut() {
+            document.version = None;
+        }
+    }
+}
+
+async fn run_process(
+    mut child: tokio::process::Child,
+    root_url: lsp_types::Url,
+    rx: &mut UnboundedReceiver<ServerMessage>,
+    documents: &mut HashMap<usize, Document>,
+) -> anyhow::Result<ProcessExit> {
+    let mut stdin = child.stdin.take().context("take stdin")?;
+    let reader = tokio::io::BufReader::new(child.stdout.take().context("take stdout")?);
+
+    let pending = PendingMap::default();
+    let (dead_tx, mut dead_rx) = oneshot::channel::<()>();
+    let reader_pending = pending.clone();
+    tokio::spawn(async move {
+        let _ = read_responses(reader, reader_pending).await;
+        let _ = dead_tx.send(());
+    });
+
+    let mut next_id = 0;
+    let (init_tx, init_rx) = oneshot::channel();
+    pending
+        .lock()
+        .unwrap()
+        .insert(next_id, Pending::Init(init_tx));
+    send_request_async::<_, proposed::Initialize>(
+        &mut stdin,
+        next_id,
+        proposed::initialize_params(initialize_params(root_url))?,
+    )
+    .await?;
+    next_id += 1;
+
+    // Wait initialize
+    let init_result = tokio::select! {
+        res = init_rx => res?,
+        _ = &mut dead_rx => return Ok(ProcessExit::Crashed),
+    };
+    let features = ServerFeatures {
+        semantic_tokens_legend: proposed::semantic_tokens_legend(&init_result).map(Arc::new),
+        inlay_hint: proposed::has_inlay_hint(&init_result),
+    };
+    send_notify_async::<_, lsp_types::notification::Initialized>(
+        &mut stdin,
+        lsp_types::InitializedParams {},
+    )
+    .await?;
+
+    loop {
+        let msg = tokio::select! {
+            msg = rx.recv() => msg,
+            _ = &mut dead_rx => return Ok(ProcessExit::Crashed),
         };
 
-        let mut stdin = lsp.stdin.take().context("take stdin")?;
-        let mut reader = tokio::io::BufReader::new(lsp.stdout.take().context("take stdout")?);
-
-        // send_request::<_, lsp_types::request::Initialize>(&mut stdin, ID_INIT, init)?;
-
-        let (init_tx, mut init_rx) = tokio::sync::mpsc::unbounded_channel();
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-        let (c_tx, mut c_rx) = tokio::sync::mpsc::unbounded_channel::<(String, Cursor)>();
-        tokio::spawn(async move {
-            send_request_async::<_, lsp_types::request::Initialize>(&mut stdin, ID_INIT, init)
-                .await?;
-            // Wait initialize
-            init_rx.recv().await.unwrap();
-            let file_url =
-                lsp_types::Url::parse(&format!("file://localhost/main.{}", extension)).unwrap();
-
-            while let Some((src, cursor)) = c_rx.recv().await {
-                let open = lsp_types::DidOpenTextDocumentParams {
-                    text_document: lsp_types::TextDocumentItem {
-                        uri: file_url.clone(),
-                        language_id: extension.clone(),
-                        version: 0,
-                        text: src,
+        match msg {
+            None => {
+                shutdown_process(child, stdin, &pending, next_id).await;
+                return Ok(ProcessExit::Closed);
+            }
+            Some(ServerMessage::Shutdown(done)) => {
+                shutdown_process(child, stdin, &pending, next_id).await;
+                let _ = done.send(());
+                return Ok(ProcessExit::Closed);
+            }
+            Some(ServerMessage::Restart) => {
+                shutdown_process(child, stdin, &pending, next_id).await;
+                return Ok(ProcessExit::Restart);
+            }
+            Some(ServerMessage::Open(id, document)) => {
+                documents.insert(id, document);
+            }
+            Some(ServerMessage::Close(id)) => {
+                if let Some(document) = documents.remove(&id) {
+                    if document.version.is_some() {
+                        let params = lsp_types::DidCloseTextDocumentParams {
+                            text_document: lsp_types::TextDocumentIdentifier { uri: document.url },
+                        };
+                        send_notify_async::<_, lsp_types::notification::DidCloseTextDocument>(
+                            &mut stdin, params,
+                        )
+                        .await?;
+                    }
+                }
+            }
+            Some(ServerMessage::Request(id, req)) => {
+                if let Some(document) = documents.get_mut(&id) {
+                    send_client_request(
+                        &mut stdin,
+                        document,
+                        req,
+                        &pending,
+                        &features,
+                        &mut next_id,
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
+}
+
+async fn shutdown_process(
+    mut child: tokio::process::Child,
+    mut stdin: tokio::process::ChildStdin,
+    pending: &PendingMap,
+    id: u64,
+) {
+    let (tx, rx) = oneshot::channel();
+    pending.lock().unwrap().insert(id, Pending::Shutdown(tx));
+    if send_request_async::<_, lsp_types::request::Shutdown>(&mut stdin, id, ())
+        .await
+        .is_ok()
+    {
+        let _ = tokio::time::timeout(SHUTDOWN_TIMEOUT, rx).await;
+        let _ = send_notify_async::<_, lsp_types::notification::Exit>(&mut stdin, ()).await;
+    }
+    drop(stdin);
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut child)
+        .await
+        .is_err()
+    {
+        let _ = child.kill();
+    }
+}
+
+// Send the current content with didOpen or didChange
+async fn sync_document<T: AsyncWrite + std::marker::Unpin>(
+    t: &mut T,
+    document: &mut Document,
+    src: String,
+) -> anyhow::Result<()> {
+    if let Some(version) = document.version.as_mut() {
+        *version += 1;
+        let params = lsp_types::DidChangeTextDocumentParams {
+            text_document: lsp_types::VersionedTextDocumentIdentifier {
+                uri: document.url.clone(),
+                version: Some(*version),
+            },
+            content_changes: vec![lsp_types::TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: src,
+            }],
+        };
+        send_notify_async::<_, lsp_types::notification::DidChangeTextDocument>(t, params).await
+    } else {
+        document.version = Some(0);
+        let params = lsp_types::DidOpenTextDocumentParams {
+            text_document: lsp_types::TextDocumentItem {
+                uri: document.url.clone(),
+                language_id: document.language_id.clone(),
+                version: 0,
+                text: src,
+            },
+        };
+        send_notify_async::<_, lsp_types::notification::DidOpenTextDocument>(t, params).await
+    }
+}
+
+fn end_position(src: &str) -> lsp_types::Position {
+    let last = src.rsplit('\n').next().unwrap_or_default();
+    lsp_types::Position {
+        line: src.matches('\n').count() as u64,
+        character: last.chars().count() as u64,
+    }
+}
+
+async fn send_client_request<T: AsyncWrite + std::marker::Unpin>(
+    stdin: &mut T,
+    document: &mut Document,
+    req: LSPRequest,
+    pending: &PendingMap,
+    features: &ServerFeatures,
+    next_id: &mut u64,
+) -> anyhow::Result<()> {
+    let text_document = lsp_types::TextDocumentIdentifier {
+        uri: document.url.clone(),
+    };
+    // Allocate an id for a response to this document
+    let mut register = |kind| {
+        let id = *next_id;
+        *next_id += 1;
+        pending.lock().unwrap().insert(
+            id,
+            Pending::Client {
+                kind,
+                sender: document.sender.clone(),
+                url: document.url.clone(),
+            },
+        );
+        id
+    };
+
+    match req {
+        LSPRequest::Completion(src, cursor) => {
+            let id = register(ResponseKind::Completion);
+            sync_document(stdin, document, src).await?;
+            let completion = lsp_types::CompletionParams {
+                text_document_position: lsp_types::TextDocumentPositionParams {
+                    text_document,
+                    position: cursor_to_position(cursor),
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: None,
+            };
+            send_request_async::<_, lsp_types::request::Completion>(stdin, id, completion).await
+        }
+        LSPRequest::Resolve(item) => {
+            let id = register(ResponseKind::Resolve);
+            send_request_async::<_, lsp_types::request::ResolveCompletionItem>(stdin, id, *item)
+                .await
+        }
+        LSPRequest::DocumentSymbol(src) => {
+            let id = register(ResponseKind::DocumentSymbol);
+            sync_document(stdin, document, src).await?;
+            let params = lsp_types::DocumentSymbolParams { text_document };
+            send_request_async::<_, lsp_types::request::DocumentSymbolRequest>(stdin, id, params)
+                .await
+        }
+        LSPRequest::WorkspaceSymbol(query) => {
+            let id = register(ResponseKind::WorkspaceSymbol);
+            let params = lsp_types::WorkspaceSymbolParams {
+                partial_result_params: Default::default(),
+                work_done_progress_params: Default::default(),
+                query,
+            };
+            send_request_async::<_, lsp_types::request::WorkspaceSymbol>(stdin, id, params).await
+        }
+        LSPRequest::Formatting(src, options, range) => {
+            let id = register(ResponseKind::Formatting);
+            sync_document(stdin, document, src).await?;
+            if let Some(range) = range {
+                let params = lsp_types::DocumentRangeFormattingParams {
+                    text_document,
+                    range: lsp_types::Range {
+                        start: cursor_to_position(range.start),
+                        end: cursor_to_position(range.end),
                     },
+                    options,
+                    work_done_progress_params: Default::default(),
                 };
-                send_notify_async::<_, lsp_types::notification::DidOpenTextDocument>(
-                    &mut stdin, open,
-                )
-                .await?;
-                let completion = lsp_types::CompletionParams {
-                    text_document_position: lsp_types::TextDocumentPositionParams {
-                        text_document: lsp_types::TextDocumentIdentifier {
-                            uri: file_url.clone(),
-                        },
-                        position: lsp_types::Position {
-                            line: cursor.row as u64,
-                            character: cursor.col as u64,
+                send_request_async::<_, lsp_types::request::RangeFormatting>(stdin, id, params)
+                    .await
+            } else {
+                let params = lsp_types::DocumentFormattingParams {
+                    text_document,
+                    options,
+                    work_done_progress_params: Default::default(),
+                };
+                send_request_async::<_, lsp_types::request::Formatting>(stdin, id, params).await
+            }
+        }
+        LSPRequest::Decoration(src) => {
+            if features.semantic_tokens_legend.is_none() && !features.inlay_hint {
+                return Ok(());
+            }
+            let semantic_tokens_id = features
+                .semantic_tokens_legend
+                .clone()
+                .map(|legend| register(ResponseKind::SemanticTokens(legend)));
+            let inlay_hint_id = if features.inlay_hint {
+                Some(register(ResponseKind::InlayHint))
+            } else {
+                None
+            };
+            let end = end_position(&src);
+            sync_document(stdin, document, src).await?;
+            if let Some(id) = semantic_tokens_id {
+                let params = proposed::SemanticTokensParams {
+                    text_document: text_document.clone(),
+                };
+                send_request_async::<_, proposed::SemanticTokensFull>(stdin, id, params).await?;
+            }
+            if let Some(id) = inlay_hint_id {
+                let params = proposed::InlayHintParams {
+                    text_document,
+                    range: lsp_types::Range {
+                        start: lsp_types::Position {
+                            line: 0,
+                            character: 0,
                         },
+                        end,
                     },
-                    work_done_progress_params: Default::default(),
-                    partial_result_params: Default::default(),
-                    context: None,
                 };
-                send_request_async::<_, lsp_types::request::Completion>(&mut stdin, 1, completion)
-                    .await?;
+                send_request_async::<_, proposed::InlayHintRequest>(stdin, id, params).await?;
             }
-            Ok::<(), anyhow::Error>(())
-        });
+            Ok(())
+        }
+    }
+}
 
-        tokio::spawn(async move {
-            let mut headers = HashMap::new();
-            loop {
-                headers.clear();
-                loop {
-                    let mut header = String::new();
-                    if reader.read_line(&mut header).await? == 0 {
-                        return Ok::<(), anyhow::Error>(());
-                    }
-                    let header = header.trim();
-                    if header.is_empty() {
-                        break;
-                    }
-                    let parts: Vec<&str> = header.split(": ").collect();
-                    assert_eq!(parts.len(), 2);
-                    headers.insert(parts[0].to_string(), parts[1].to_string());
-                }
-                let content_len = headers["Content-Length"].parse()?;
-                let mut content = vec![0; content_len];
-                reader.read_exact(&mut content).await?;
-                let msg = String::from_utf8(content)?;
-                let output: serde_json::Result<Output> = serde_json::from_str(&msg);
-                if let Ok(Output::Success(suc)) = output {
-                    if suc.id == jsonrpc_core::id::Id::Num(ID_INIT) {
-                        init_tx.send(())?;
-                    } else if suc.id == jsonrpc_core::id::Id::Num(ID_COMPLETION) {
-                        let completion =
-                            serde_json::from_value::<lsp_types::CompletionResponse>(suc.result)?;
-
-                        let completion = extract_completion(completion);
-                        tx.send(completion)?;
-                    }
-                }
+async fn read_responses<R: AsyncBufRead + std::marker::Unpin>(
+    mut reader: R,
+    pending: PendingMap,
+) -> anyhow::Result<()> {
+    let mut headers = HashMap::new();
+    loop {
+        headers.clear();
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 {
+                return Ok(());
             }
-        });
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            let parts: Vec<&str> = header.split(": ").collect();
+            assert_eq!(parts.len(), 2);
+            headers.insert(parts[0].to_string(), parts[1].to_string());
+        }
+        let content_len = headers["Content-Length"].parse()?;
+        let mut content = vec![0; content_len];
+        reader.read_exact(&mut content).await?;
+        let msg = String::from_utf8(content)?;
+        let output = if let Ok(output) = serde_json::from_str::<Output>(&msg) {
+            output
+        } else {
+            // Notifications and requests from the server
+            continue;
+        };
+        let id = match output.id() {
+            jsonrpc_core::id::Id::Num(id) => *id,
+            _ => continue,
+        };
+        let pending = if let Some(pending) = pending.lock().unwrap().remove(&id) {
+            pending
+        } else {
+            continue;
+        };
 
-        Ok(Self {
-            process: lsp,
-            completion_recv: rx,
-            completion_req: c_tx,
-        })
+        match (pending, output) {
+            (Pending::Init(tx), Output::Success(suc)) => {
+                let _ = tx.send(suc.result);
+            }
+            (Pending::Shutdown(tx), _) => {
+                let _ = tx.send(());
+            }
+            (Pending::Init(_), Output::Failure(_)) => {}
+            (
+                Pending::Client {
+                    kind: ResponseKind::Formatting,
+                    sender,
+                    ..
+                },
+                Output::Failure(fail),
+            ) => {
+                let _ = sender.format.send(Err(fail.error.message));
+            }
+            (Pending::Client { .. }, Output::Failure(_)) => {}
+            (Pending::Client { kind, sender, url }, Output::Success(suc)) => {
+                let _ = dispatch_response(kind, &sender, &url, suc.result);
+            }
+        }
+    }
+}
+
+fn dispatch_response(
+    kind: ResponseKind,
+    sender: &ClientSender,
+    url: &lsp_types::Url,
+    result: serde_json::Value,
+) -> anyhow::Result<()> {
+    match kind {
+        ResponseKind::Completion => {
+            let completion = serde_json::from_value::<lsp_types::CompletionResponse>(result)?;
+            sender.completion.send(extract_completion(completion))?;
+        }
+        ResponseKind::Resolve => {
+            let item = serde_json::from_value::<lsp_types::CompletionItem>(result)?;
+            sender.resolve.send(convert_completion_item(item))?;
+        }
+        ResponseKind::DocumentSymbol => {
+            let symbols =
+                serde_json::from_value::<Option<lsp_types::DocumentSymbolResponse>>(result)?;
+            sender.symbol.send(extract_document_symbol(symbols, url))?;
+        }
+        ResponseKind::WorkspaceSymbol => {
+            let symbols =
+                serde_json::from_value::<Option<Vec<lsp_types::SymbolInformation>>>(result)?;
+            sender
+                .symbol
+                .send(extract_symbol_information(symbols.unwrap_or_default(), url))?;
+        }
+        ResponseKind::Formatting => {
+            let edits = serde_json::from_value::<Option<Vec<lsp_types::TextEdit>>>(result)?;
+            sender.format.send(Ok(edits
+                .unwrap_or_default()
+                .into_iter()
+                .map(|edit| TextEdit {
+                    range: position_to_cursor(edit.range.start)..position_to_cursor(edit.range.end),
+                    text: edit.new_text,
+                })
+                .collect()))?;
+        }
+        ResponseKind::SemanticTokens(legend) => {
+            let tokens = serde_json::from_value::<Option<proposed::SemanticTokens>>(result)?;
+            sender.semantic_tokens.send(decode_semantic_tokens(
+                &tokens.map(|t| t.data).unwrap_or_default(),
+                &legend,
+            ))?;
+        }
+        ResponseKind::InlayHint => {
+            let hints = serde_json::from_value::<Option<Vec<proposed::InlayHint>>>(result)?;
+            sender.inlay_hints.send(
+                hints
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(convert_inlay_hint)
+                    .collect(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn decode_semantic_tokens(data: &[u32], legend: &[String]) -> Vec<LSPSemanticToken> {
+    let mut cursor = Cursor { row: 0, col: 0 };
+    let mut res = Vec::new();
+    for token in data.chunks_exact(5) {
+        if token[0] > 0 {
+            cursor.row += token[0] as usize;
+            cursor.col = token[1] as usize;
+        } else {
+            cursor.col += token[1] as usize;
+        }
+        if let Some(token_type) = legend.get(token[3] as usize) {
+            res.push(LSPSemanticToken {
+                cursor,
+                len: token[2] as usize,
+                token_type: token_type.clone(),
+            });
+        }
+    }
+    res
+}
+
+fn convert_inlay_hint(hint: proposed::InlayHint) -> LSPInlayHint {
+    let mut label = match hint.label {
+        proposed::InlayHintLabel::String(s) => s,
+        proposed::InlayHintLabel::Parts(parts) => parts.into_iter().map(|p| p.value).collect(),
+    };
+    if hint.padding_left {
+        label.insert(0, ' ');
+    }
+    if hint.padding_right {
+        label.push(' ');
+    }
+    LSPInlayHint {
+        cursor: position_to_cursor(hint.position),
+        label,
+    }
+}
+
+impl LSPClient {
+    fn send(&self, req: LSPRequest) -> bool {
+        self.server
+            .tx
+            .send(ServerMessage::Request(self.id, req))
+            .is_ok()
+    }
+
+    /// Restart the server process shared with other documents
+    pub fn restart_server(&self) {
+        let _ = self.server.tx.send(ServerMessage::Restart);
     }
 
     pub fn request_completion(&self, src: String, cursor: Cursor) {
-        let _ = self.completion_req.send((src, cursor));
+        self.send(LSPRequest::Completion(src, cursor));
+    }
+
+    /// Request completionItem/resolve to fill documentation
+    pub fn request_resolve(&self, completion: &LSPCompletion) {
+        self.send(LSPRequest::Resolve(Box::new(completion.item.clone())));
+    }
+
+    pub fn poll_resolve(&mut self) -> Option<LSPCompletion> {
+        let mut res = None;
+        while let Ok(completion) = self.resolve_recv.try_recv() {
+            res = Some(completion);
+        }
+        res
+    }
+
+    pub fn request_document_symbol(&self, src: String) {
+        self.send(LSPRequest::DocumentSymbol(src));
+    }
+
+    pub fn request_workspace_symbol(&self, query: String) {
+        self.send(LSPRequest::WorkspaceSymbol(query));
+    }
+
+    /// Request textDocument/formatting (or rangeFormatting if `range` is given) and wait for the edits
+    pub async fn format(
+        &mut self,
+        src: String,
+        tab_size: usize,
+        insert_spaces: bool,
+        range: Option<Range<Cursor>>,
+    ) -> anyhow::Result<Vec<TextEdit>> {
+        // Discard stale responses
+        while self.format_recv.try_recv().is_ok() {}
+
+        let options = lsp_types::FormattingOptions {
+            tab_size: tab_size as u64,
+            insert_spaces,
+            ..Default::default()
+        };
+        if !self.send(LSPRequest::Formatting(src, options, range)) {
+            anyhow::bail!("LSP is not running");
+        }
+
+        tokio::time::timeout(FORMAT_TIMEOUT, self.format_recv.recv())
+            .await
+            .context("Timeout")?
+            .context("LSP is not running")?
+            .map_err(|msg| anyhow::anyhow!(msg))
+    }
+
+    /// Request semantic tokens and inlay hints of `src`
+    pub fn request_decoration(&self, src: String) {
+        self.send(LSPRequest::Decoration(src));
     }
 
-    pub fn poll(&mut self) -> Option<Vec<LSPCompletion>> {
+    pub fn poll_semantic_tokens(&mut self) -> Option<Vec<LSPSemanticToken>> {
+        let mut res = None;
+        while let Ok(tokens) = self.semantic_tokens_recv.try_recv() {
+            res = Some(tokens);
+        }
+        res
+    }
+
+    pub fn poll_inlay_hints(&mut self) -> Option<Vec<LSPInlayHint>> {
+        let mut res = None;
+        while let Ok(hints) = self.inlay_hints_recv.try_recv() {
+            res = Some(hints);
+        }
+        res
+    }
+
+    pub fn poll_symbol(&mut self) -> Option<Vec<LSPSymbol>> {
+        let mut res = None;
+        while let Ok(symbols) = self.symbol_recv.try_recv() {
+            res = Some(symbols);
+        }
+        res
+    }
+
+    pub fn poll(&mut self) -> Option<LSPCompletionList> {
         let mut res = None;
         while let Ok(completion) = self.completion_recv.try_recv() {
             res = Some(completion);
@@ -160,6 +970,15 @@ impl LSPClient {
     }
 }
 
+// `()` params (e.g. shutdown) are omitted
+fn to_params<P: serde::Serialize>(params: P) -> anyhow::Result<Option<jsonrpc_core::Params>> {
+    Ok(match serde_json::to_value(params)? {
+        serde_json::value::Value::Object(params) => Some(jsonrpc_core::Params::Map(params)),
+        serde_json::value::Value::Null => Some(jsonrpc_core::Params::None),
+        _ => None,
+    })
+}
+
 async fn send_request_async<T: AsyncWrite + std::marker::Unpin, R: lsp_types::request::Request>(
     t: &mut T,
     id: u64,
@@ -168,11 +987,11 @@ async fn send_request_async<T: AsyncWrite + std::marker::Unpin, R: lsp_types::re
 where
     R::Params: serde::Serialize,
 {
-    if let serde_json::value::Value::Object(params) = serde_json::to_value(params)? {
+    if let Some(params) = to_params(params)? {
         let req = jsonrpc_core::Call::MethodCall(jsonrpc_core::MethodCall {
             jsonrpc: Some(jsonrpc_core::Version::V2),
             method: R::METHOD.to_string(),
-            params: jsonrpc_core::Params::Map(params),
+            params,
             id: jsonrpc_core::Id::Num(id),
         });
         let request = serde_json::to_string(&req)?;
@@ -200,11 +1019,11 @@ async fn send_notify_async<
 where
     R::Params: serde::Serialize,
 {
-    if let serde_json::value::Value::Object(params) = serde_json::to_value(params)? {
+    if let Some(params) = to_params(params)? {
         let req = jsonrpc_core::Notification {
             jsonrpc: Some(jsonrpc_core::Version::V2),
             method: R::METHOD.to_string(),
-            params: jsonrpc_core::Params::Map(params),
+            params,
         };
         let request = serde_json::to_string(&req)?;
         let mut buf: Vec<u8> = Vec::new();
@@ -221,22 +1040,380 @@ where
     }
 }
 
-fn extract_completion(completion: lsp_types::CompletionResponse) -> Vec<LSPCompletion> {
-    match completion {
-        lsp_types::CompletionResponse::Array(array) => array
-            .into_iter()
-            .map(|item| LSPCompletion {
-                keyword: item.label,
-                doc: item.detail.unwrap_or_default(),
-            })
-            .collect(),
-        lsp_types::CompletionResponse::List(list) => list
-            .items
+fn documentation_to_string(documentation: lsp_types::Documentation) -> String {
+    match documentation {
+        lsp_types::Documentation::String(s) => s,
+        lsp_types::Documentation::MarkupContent(m) => m.value,
+    }
+}
+
+pub fn convert_completion_item(item: lsp_types::CompletionItem) -> LSPCompletion {
+    let (text_edit, insert_text) = if let Some(edit) = item.text_edit.as_ref() {
+        (
+            Some(position_to_cursor(edit.range.start)..position_to_cursor(edit.range.end)),
+            edit.new_text.clone(),
+        )
+    } else {
+        (
+            None,
+            item.insert_text
+                .clone()
+                .unwrap_or_else(|| item.label.clone()),
+        )
+    };
+
+    LSPCompletion {
+        keyword: item.label.clone(),
+        doc: item.detail.clone().unwrap_or_default(),
+        kind: item.kind,
+        filter_text: item
+            .filter_text
+            .clone()
+            .unwrap_or_else(|| item.label.clone()),
+        sort_text: item.sort_text.clone().unwrap_or_else(|| item.label.clone()),
+        insert_text,
+        is_snippet: item.insert_text_format == Some(lsp_types::InsertTextFormat::Snippet),
+        text_edit,
+        additional_edits: item
+            .additional_text_edits
+            .clone()
+            .unwrap_or_default()
             .into_iter()
-            .map(|item| LSPCompletion {
-                keyword: item.label,
-                doc: item.detail.unwrap_or_default(),
+            .map(|edit| TextEdit {
+                range: position_to_cursor(edit.range.start)..position_to_cursor(edit.range.end),
+                text: edit.new_text,
             })
             .collect(),
+        documentation: item.documentation.clone().map(documentation_to_string),
+        item,
+    }
+}
+
+fn extract_completion(completion: lsp_types::CompletionResponse) -> LSPCompletionList {
+    let (items, is_incomplete) = match completion {
+        lsp_types::CompletionResponse::Array(array) => (array, false),
+        lsp_types::CompletionResponse::List(list) => (list.items, list.is_incomplete),
+    };
+    LSPCompletionList {
+        items: items.into_iter().map(convert_completion_item).collect(),
+        is_incomplete,
+    }
+}
+
+fn cursor_to_position(cursor: Cursor) -> lsp_types::Position {
+    lsp_types::Position {
+        line: cursor.row as u64,
+        character: cursor.col as u64,
+    }
+}
+
+fn position_to_cursor(position: lsp_types::Position) -> Cursor {
+    Cursor {
+        row: position.line as usize,
+        col: position.character as usize,
+    }
+}
+
+fn flatten_document_symbol(
+    symbols: Vec<lsp_types::DocumentSymbol>,
+    container: Option<&str>,
+    result: &mut Vec<LSPSymbol>,
+) {
+    for symbol in symbols {
+        result.push(LSPSymbol {
+            name: symbol.name.clone(),
+            kind: symbol.kind,
+            container: container.map(String::from),
+            path: None,
+            cursor: position_to_cursor(symbol.selection_range.start),
+        });
+        if let Some(children) = symbol.children {
+            flatten_document_symbol(children, Some(&symbol.name), result);
+        }
+    }
+}
+
+fn extract_symbol_information(
+    symbols: Vec<lsp_types::SymbolInformation>,
+    document_url: &lsp_types::Url,
+) -> Vec<LSPSymbol> {
+    symbols
+        .into_iter()
+        .map(|symbol| LSPSymbol {
+            name: symbol.name,
+            kind: symbol.kind,
+            container: symbol.container_name,
+            path: if &symbol.location.uri == document_url {
+                None
+            } else {
+                symbol.location.uri.to_file_path().ok()
+            },
+            cursor: position_to_cursor(symbol.location.range.start),
+        })
+        .collect()
+}
+
+fn extract_document_symbol(
+    symbols: Option<lsp_types::DocumentSymbolResponse>,
+    document_url: &lsp_types::Url,
+) -> Vec<LSPSymbol> {
+    match symbols {
+        None => Vec::new(),
+        Some(lsp_types::DocumentSymbolResponse::Flat(symbols)) => {
+            extract_symbol_information(symbols, document_url)
+        }
+        Some(lsp_types::DocumentSymbolResponse::Nested(symbols)) => {
+            let mut result = Vec::new();
+            flatten_document_symbol(symbols, None, &mut result);
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        convert_inlay_hint, decode_semantic_tokens, extract_document_symbol, proposed, LSPPool,
+    };
+    use crate::config::types::Command;
+    use crate::core::Cursor;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    // Answers initialize, completion and shutdown. Methods of received messages are appended to $1.
+    const MOCK_SERVER: &str = r#"#!/bin/sh
+while :; do
+    len=
+    while IFS= read -r line; do
+        line=$(printf '%s' "$line" | tr -d '\r')
+        [ -z "$line" ] && break
+        case "$line" in Content-Length:*) len=${line#Content-Length: } ;; esac
+    done
+    [ -z "$len" ] && exit 0
+    body=$(dd bs=1 count="$len" 2>/dev/null)
+    method=$(printf '%s' "$body" | sed -n 's/.*"method":"\([^"]*\)".*/\1/p')
+    id=$(printf '%s' "$body" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    echo "$method" >> "$1"
+    case "$method" in
+        initialize) result='{"capabilities":{}}' ;;
+        textDocument/completion) result='[{"label":"mock"}]' ;;
+        shutdown) result='null' ;;
+        exit) exit 0 ;;
+        *) continue ;;
+    esac
+    msg="{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":$result}"
+    printf 'Content-Length: %s\r\n\r\n%s' "${#msg}" "$msg"
+done
+"#;
+
+    #[test]
+    fn test_decode_semantic_tokens() {
+        let legend = ["variable", "function"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        // (delta line, delta start, length, type, modifiers) -> (row, col, len, type)
+        type Case<'a> = (&'a [u32], &'a [(usize, usize, usize, &'a str)]);
+        let table: &[Case] = &[
+            (&[], &[]),
+            (&[0, 4, 3, 1, 0], &[(0, 4, 3, "function")]),
+            // Starts are relative to the previous token on the same line
+            (
+                &[1, 2, 1, 0, 0, 0, 5, 2, 1, 0],
+                &[(1, 2, 1, "variable"), (1, 7, 2, "function")],
+            ),
+            // and absolute on a new line
+            (
+                &[0, 8, 1, 0, 0, 2, 3, 1, 0, 0],
+                &[(0, 8, 1, "variable"), (2, 3, 1, "variable")],
+            ),
+            // Modifier bits don't change the type
+            (
+                &[0, 0, 1, 0, 0b1, 0, 2, 1, 1, 0b1010],
+                &[(0, 0, 1, "variable"), (0, 2, 1, "function")],
+            ),
+            // Types out of the legend are skipped but still move the position
+            (&[0, 1, 1, 7, 0, 0, 2, 1, 0, 0], &[(0, 3, 1, "variable")]),
+            // A broken trailing token is ignored
+            (&[0, 1, 1, 0, 0, 1], &[(0, 1, 1, "variable")]),
+        ];
+        for (data, expected) in table {
+            let tokens = decode_semantic_tokens(data, &legend)
+                .into_iter()
+                .map(|t| (t.cursor.row, t.cursor.col, t.len, t.token_type))
+                .collect::<Vec<_>>();
+            let expected = expected
+                .iter()
+                .map(|&(row, col, len, t)| (row, col, len, t.to_string()))
+                .collect::<Vec<_>>();
+            assert_eq!(tokens, expected, "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn test_convert_inlay_hint() {
+        let table = [
+            (
+                r#"{"position":{"line":1,"character":5},"label":": i32"}"#,
+                ": i32",
+            ),
+            (
+                r#"{"position":{"line":1,"character":5},"label":"n:","paddingRight":true}"#,
+                "n: ",
+            ),
+            (
+                r#"{"position":{"line":1,"character":5},"label":[{"value":"->"},{"value":" Vec"}],"paddingLeft":true}"#,
+                " -> Vec",
+            ),
+        ];
+        for (json, label) in table.iter() {
+            let hint = serde_json::from_str::<proposed::InlayHint>(json).unwrap();
+            let hint = convert_inlay_hint(hint);
+            assert_eq!(hint.cursor, Cursor { row: 1, col: 5 });
+            assert_eq!(hint.label, *label);
+        }
+    }
+
+    #[test]
+    fn test_extract_document_symbol() {
+        let document_url = lsp_types::Url::parse("file:///src/main.rs").unwrap();
+        let range = |line: u32, character: u32| {
+            format!(
+                r#"{{"start":{{"line":{0},"character":{1}}},"end":{{"line":{0},"character":{1}}}}}"#,
+                line, character
+            )
+        };
+        // (response, [(name, container, path, row, col)])
+        type Case<'a> = (
+            String,
+            &'a [(&'a str, Option<&'a str>, Option<&'a str>, usize, usize)],
+        );
+        let table: &[Case] = &[
+            ("null".to_string(), &[]),
+            // Nested DocumentSymbol. Children are listed after their parent with its name.
+            (
+                format!(
+                    r#"[{{"name":"Foo","kind":23,"range":{0},"selectionRange":{1},"children":[
+                        {{"name":"new","kind":6,"range":{2},"selectionRange":{3},"children":[
+                            {{"name":"x","kind":13,"range":{4},"selectionRange":{4}}}]}}]}},
+                       {{"name":"main","kind":12,"range":{5},"selectionRange":{6}}}]"#,
+                    range(0, 0),
+                    range(0, 7),
+                    range(2, 4),
+                    range(2, 11),
+                    range(3, 12),
+                    range(8, 0),
+                    range(8, 3)
+                ),
+                &[
+                    ("Foo", None, None, 0, 7),
+                    ("new", Some("Foo"), None, 2, 11),
+                    ("x", Some("new"), None, 3, 12),
+                    ("main", None, None, 8, 3),
+                ],
+            ),
+            // Flat SymbolInformation. Symbols in other documents have paths.
+            (
+                format!(
+                    r#"[{{"name":"main","kind":12,"location":{{"uri":"file:///src/main.rs","range":{0}}}}},
+                       {{"name":"helper","kind":12,"containerName":"util","location":{{"uri":"file:///src/util.rs","range":{1}}}}}]"#,
+                    range(4, 3),
+                    range(1, 7)
+                ),
+                &[
+                    ("main", None, None, 4, 3),
+                    ("helper", Some("util"), Some("/src/util.rs"), 1, 7),
+                ],
+            ),
+        ];
+        for (json, expected) in table {
+            let response = serde_json::from_str(json).unwrap();
+            let symbols = extract_document_symbol(response, &document_url)
+                .into_iter()
+                .map(|s| (s.name, s.container, s.path, s.cursor.row, s.cursor.col))
+                .collect::<Vec<_>>();
+            let expected = expected
+                .iter()
+                .map(|&(name, container, path, row, col)| {
+                    (
+                        name.to_string(),
+                        container.map(String::from),
+                        path.map(PathBuf::from),
+                        row,
+                        col,
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(symbols, expected, "{}", json);
+        }
+    }
+
+    fn methods(log: &Path) -> Vec<String> {
+        fs::read_to_string(log)
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    fn count(log: &Path, method: &str) -> usize {
+        methods(log).iter().filter(|m| m.as_str() == method).count()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pool() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("lsp");
+        let mock = dir.join("mock-lsp");
+        fs::write(&mock, MOCK_SERVER).unwrap();
+        fs::set_permissions(&mock, fs::Permissions::from_mode(0o755)).unwrap();
+        let log = dir.join("log");
+        let command = Command {
+            program: mock.to_string_lossy().into_owned(),
+            args: vec![log.to_string_lossy().into_owned()],
+        };
+
+        let pool = LSPPool::default();
+        let mut client = pool
+            .client(&command, "rs".to_string(), Some(&dir.join("a.rs")))
+            .unwrap();
+        // Documents of the same workspace share the process
+        let _other = pool
+            .client(&command, "rs".to_string(), Some(&dir.join("b.rs")))
+            .unwrap();
+
+        async fn complete(client: &mut super::LSPClient) -> Option<String> {
+            client.request_completion("fn main() {}\n".to_string(), Cursor { row: 0, col: 0 });
+            for _ in 0..100 {
+                if let Some(list) = client.poll() {
+                    return list.items.first().map(|item| item.keyword.clone());
+                }
+                tokio::time::delay_for(Duration::from_millis(50)).await;
+            }
+            None
+        }
+
+        assert_eq!(complete(&mut client).await.as_deref(), Some("mock"));
+        assert_eq!(count(&log, "initialize"), 1);
+        assert_eq!(count(&log, "textDocument/didOpen"), 1);
+
+        // The document is opened again in the new process
+        client.restart_server();
+        assert_eq!(complete(&mut client).await.as_deref(), Some("mock"));
+        assert_eq!(count(&log, "initialize"), 2);
+        assert_eq!(count(&log, "textDocument/didOpen"), 2);
+        assert_eq!(count(&log, "shutdown"), 1);
+
+        pool.shutdown().await;
+        let methods = methods(&log);
+        assert_eq!(
+            methods[methods.len() - 2..],
+            ["shutdown".to_string(), "exit".to_string()]
+        );
+        assert_eq!(count(&log, "exit"), 2);
     }
 }
@@ -0,0 +1,101 @@
+// Protocol extensions newer than lsp-types (LSP 3.16 / 3.17)
+
+use lsp_types::request::Request;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+
+// initialize with client capabilities unknown to lsp-types
+pub enum Initialize {}
+
+impl Request for Initialize {
+    type Params = serde_json::Value;
+    type Result = serde_json::Value;
+    const METHOD: &'static str = "initialize";
+}
+
+pub fn initialize_params(
+    params: lsp_types::InitializeParams,
+) -> serde_json::Result<serde_json::Value> {
+    let mut params = serde_json::to_value(params)?;
+    let text_document = &mut params["capabilities"]["textDocument"];
+    text_document["semanticTokens"] = json!({
+        "requests": { "full": true },
+        "tokenTypes": [],
+        "tokenModifiers": [],
+        "formats": ["relative"],
+    });
+    text_document["inlayHint"] = json!({});
+    Ok(params)
+}
+
+// Returns token types of the legend if the server supports semanticTokens/full
+pub fn semantic_tokens_legend(init_result: &serde_json::Value) -> Option<Vec<String>> {
+    let provider = &init_result["capabilities"]["semanticTokensProvider"];
+    if provider["full"].is_null() || provider["full"] == json!(false) {
+        return None;
+    }
+    serde_json::from_value(provider["legend"]["tokenTypes"].clone()).ok()
+}
+
+pub fn has_inlay_hint(init_result: &serde_json::Value) -> bool {
+    let provider = &init_result["capabilities"]["inlayHintProvider"];
+    !provider.is_null() && provider != &json!(false)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensParams {
+    pub text_document: lsp_types::TextDocumentIdentifier,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SemanticTokens {
+    // (deltaLine, deltaStart, length, tokenType, tokenModifiers) * n
+    pub data: Vec<u32>,
+}
+
+pub enum SemanticTokensFull {}
+
+impl Request for SemanticTokensFull {
+    type Params = SemanticTokensParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens/full";
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintParams {
+    pub text_document: lsp_types::TextDocumentIdentifier,
+    pub range: lsp_types::Range,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InlayHintLabelPart {
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum InlayHintLabel {
+    String(String),
+    Parts(Vec<InlayHintLabelPart>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHint {
+    pub position: lsp_types::Position,
+    pub label: InlayHintLabel,
+    #[serde(default)]
+    pub padding_left: bool,
+    #[serde(default)]
+    pub padding_right: bool,
+}
+
+pub enum InlayHintRequest {}
+
+impl Request for InlayHintRequest {
+    type Params = InlayHintParams;
+    type Result = Option<Vec<InlayHint>>;
+    const METHOD: &'static str = "textDocument/inlayHint";
+}
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 use std::fs;
-use std::io::{stdin, stdout, Write};
-use std::path::PathBuf;
+use std::io::{stdin, stdout, Read, Write};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -14,8 +13,12 @@ use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 
 use accepted::buffer_tab::BufferTab;
+use accepted::cli::{self, Command, Source};
 use accepted::config;
+use accepted::core::Cursor;
 use accepted::draw::DoubleBuffer;
+use accepted::session;
+use accepted::ssh::SshStorage;
 use anyhow::Context;
 
 #[derive(BuildInfo)]
@@ -52,9 +55,58 @@ async fn main() -> anyhow::Result<()> {
         .after_help(after_help.as_str())
         .bin_name("acc")
         .arg(Arg::with_name("config").long("config"))
-        .arg(Arg::with_name("file").multiple(true))
+        .arg(
+            Arg::with_name("readonly")
+                .short("R")
+                .long("readonly")
+                .help("Open files read-only"),
+        )
+        .arg(
+            Arg::with_name("session")
+                .long("session")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("name")
+                .help("Restore the session of the name (--session=name) or of the working directory, and save it on exit (SPACE -> Q saves all tabs)"),
+        )
+        .arg(
+            Arg::with_name("command")
+                .short("c")
+                .takes_value(true)
+                .help("Search /pattern or go to a line after opening files"),
+        )
+        .arg(
+            Arg::with_name("file")
+                .multiple(true)
+                .help("Files to open. path:line:col and +line open at the position. - reads the standard input."),
+        )
         .get_matches();
 
+    // Tabs are saved to the session of the working directory unless a name is given
+    let session_path = match matches.value_of("session") {
+        Some(name) => Some(session::session_path(Some(name)).context("Invalid session name")?),
+        None => session::session_path(None),
+    };
+    let files = matches
+        .values_of_os("file")
+        .map(cli::parse_files)
+        .unwrap_or_default();
+    let command = match matches.value_of("command") {
+        Some(c) => Some(Command::parse(c).context("-c takes /pattern or a line number")?),
+        None => None,
+    };
+    // Keys are read from the terminal when the standard input is a file
+    let stdin_text = if files.iter().any(|f| f.source == Source::Stdin) {
+        let mut bytes = Vec::new();
+        stdin()
+            .read_to_end(&mut bytes)
+            .context("Read the standard input")?;
+        Some(accepted::file_format::FileFormat::decode(&bytes).0)
+    } else {
+        None
+    };
+
     let config = config_path
         .as_ref()
         .and_then(|config_path| fs::read_to_string(config_path).ok())
@@ -75,14 +127,18 @@ async fn main() -> anyhow::Result<()> {
         })
         .unwrap_or_default();
 
-    let stdin = stdin();
+    let input: Box<dyn Read + Send> = if stdin_text.is_some() {
+        Box::new(termion::get_tty().context("Open the terminal")?)
+    } else {
+        Box::new(stdin())
+    };
     let mut stdout = MouseTerminal::from(AlternateScreen::from(stdout()).into_raw_mode().unwrap());
     // let mut stdout = MouseTerminal::from(stdout().into_raw_mode().unwrap());
 
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
 
     thread::spawn(move || {
-        for c in stdin.events() {
+        for c in input.events() {
             if let Ok(evt) = c {
                 tx.send(evt).unwrap();
             }
@@ -102,12 +158,59 @@ async fn main() -> anyhow::Result<()> {
         state.open(config_path.clone());
     }
 
-    let files = matches.values_of_os("file");
-    if let Some(files) = files {
-        for path in files {
-            state.open(PathBuf::from(path));
+    // Restored tabs are read-only too
+    state.set_read_only(matches.is_present("readonly"));
+    if matches.is_present("session") {
+        if let Some(session) = session_path
+            .as_ref()
+            .and_then(|path| session::Session::load(path).ok())
+        {
+            state.restore_session(session);
+        }
+    }
+    if let Some(path) = session_path.as_ref() {
+        state.set_session_path(path);
+    }
+    let mut stdin_text = stdin_text;
+    // The tab of the last file is active
+    for file in files {
+        match (file.source, file.cursor) {
+            (Source::Path(path), cursor) => {
+                match (path.to_str().and_then(SshStorage::parse), cursor) {
+                    (Some(ssh), Some(cursor)) => state.open_at(ssh, cursor),
+                    (Some(ssh), None) => state.switch_or_open(ssh),
+                    (None, _) if path.is_dir() => {
+                        state.browse(path);
+                        true
+                    }
+                    (None, Some(cursor)) => state.open_at(path, cursor),
+                    (None, None) => state.switch_or_open(path),
+                };
+            }
+            (Source::Stdin, cursor) => {
+                if let Some(text) = stdin_text.take() {
+                    state.open_text(text, cursor);
+                }
+            }
+        }
+    }
+    match command {
+        Some(Command::Search(pattern)) => {
+            let buffer_mode = state.buffer_mode_mut();
+            buffer_mode.buf.search = pattern.chars().collect();
+            if !buffer_mode.buf.find_next() {
+                buffer_mode.show_message(format!("Pattern not found: {}", pattern));
+            }
+        }
+        Some(Command::Line(line)) => {
+            state.buffer_mode_mut().buf.jump_to(Cursor {
+                row: line.saturating_sub(1),
+                col: 0,
+            });
         }
+        None => {}
     }
+    state.offer_recovery();
 
     let mut draw = DoubleBuffer::default();
 
@@ -116,6 +219,7 @@ async fn main() -> anyhow::Result<()> {
     loop {
         let start_frame = Instant::now();
         state.buffer_mode_mut().background_task_duration(frame);
+        state.poll_session();
         let now = Instant::now();
 
         let evt = if (now - start_frame) > frame {
@@ -132,6 +236,7 @@ async fn main() -> anyhow::Result<()> {
                 draw.redraw();
             }
             if state.event(evt).await {
+                state.shutdown().await;
                 return Ok(());
             }
         }
@@ -1,6 +1,9 @@
 use std;
+use std::borrow::Cow;
 use std::cmp::{max, min};
+use std::collections::{HashMap, VecDeque};
 use std::ops::Bound;
+use std::ops::Range;
 use std::ops::RangeBounds;
 use std::path::{Path, PathBuf};
 use std::process;
@@ -14,22 +17,36 @@ use tokio::prelude::*;
 use crate::buffer::Buffer;
 use crate::buffer::Yank;
 use crate::clipboard;
+use crate::compiler::{self, CompileProfile};
 use crate::config::types::keys;
 use crate::core::Core;
 use crate::core::CoreBuffer;
 use crate::core::Cursor;
 use crate::core::Id;
+use crate::core::TextEdit;
 use crate::draw;
 use crate::indent;
 use crate::lsp::LSPCompletion;
 use crate::parenthesis;
+use crate::snippet;
 use crate::tabnine::TabNineCompletion;
 use crate::text_object::{self, Action};
 
+mod diagnostics;
+mod disk_change;
+mod file_format;
+mod file_tree;
 mod fuzzy;
+mod recent;
+mod recovery;
+mod symbol;
 
 use async_trait::async_trait;
 
+pub use disk_change::DiskChangePrompt;
+pub use file_tree::FileTree;
+pub use recovery::RecoveryPicker;
+
 pub struct TransitionReturn {
     pub message: Option<String>,
     pub is_commit_dot_macro: bool,
@@ -43,10 +60,14 @@ pub enum Transition<B: CoreBuffer> {
     // Message, is commit dot macro?
     Return(TransitionReturn),
     Exit,
+    // Close all tabs
+    ExitAll,
     CreateNewTab,
     // 1-indexed
     ChangeTab(usize),
     StartRmate,
+    // Open the file (or switch to its tab) and move to the position
+    OpenFile(PathBuf, Option<Cursor>),
 }
 
 #[async_trait(?Send)]
@@ -58,6 +79,10 @@ pub trait Mode<B: CoreBuffer>: Sync {
         event: termion::event::Event,
     ) -> Transition<B>;
     fn draw(&mut self, buf: &mut Buffer<B>, view: draw::TermView) -> draw::CursorState;
+    /// Normal mode waits for a command. Other modes are in the middle of something.
+    fn is_normal(&self) -> bool {
+        false
+    }
     fn into_transition(self) -> Transition<B>
     where
         Self: Sized + 'static,
@@ -73,10 +98,24 @@ pub struct Normal {
 
 struct Prefix;
 
+// Lines of documentation shown below completions
+const COMPLETION_DOC_LINES: usize = 5;
+
 struct Insert {
     completion_index: Option<usize>,
     buf_update: Id,
+    // Filtered and sorted `lsp_items`
     completions: Vec<LSPCompletion>,
+    lsp_items: Vec<LSPCompletion>,
+    lsp_incomplete: bool,
+    lsp_token_start: Option<Cursor>,
+    resolve_requested: Option<String>,
+    // Remaining snippet tabstops as byte ranges, moved along with edits
+    tabstops: VecDeque<Range<usize>>,
+    // Selected placeholder of the current tabstop. Typing replaces it.
+    placeholder: Option<Range<usize>>,
+    // Length of the buffer and the cursor in bytes when tabstops were moved last
+    tabstop_sync: (usize, usize),
     tabnine_completions: Vec<TabNineCompletion>,
     snippet_completions: Vec<String>,
 }
@@ -86,6 +125,13 @@ impl Default for Insert {
         Insert {
             completion_index: None,
             completions: Vec::new(),
+            lsp_items: Vec::new(),
+            lsp_incomplete: false,
+            lsp_token_start: None,
+            resolve_requested: None,
+            tabstops: VecDeque::new(),
+            placeholder: None,
+            tabstop_sync: (0, 0),
             snippet_completions: Vec::new(),
             tabnine_completions: Vec::new(),
             buf_update: Id::default(),
@@ -101,6 +147,11 @@ struct Find {
     to_right: bool,
 }
 
+// ]e / [e
+struct Bracket {
+    forward: bool,
+}
+
 struct TextObjectOperation {
     parser: text_object::TextObjectParser,
 }
@@ -127,7 +178,20 @@ struct Visual {
 struct ViewProcess {
     row_offset: usize,
     buf: Vec<String>,
-    reader: tokio::sync::mpsc::UnboundedReceiver<String>,
+    // Lines with whether they are from stderr
+    reader: tokio::sync::mpsc::UnboundedReceiver<(bool, String)>,
+    stderr: String,
+    // Number of runtime reports in stderr after the process exited
+    report_count: Option<usize>,
+    // Source locations (e.g. of panics and backtraces) in each line of `buf`
+    locations: Vec<Option<(PathBuf, Cursor)>>,
+    location_parser: compiler::LocationParser,
+    // Resolved paths of file names in the output
+    source_files: HashMap<PathBuf, Option<PathBuf>>,
+    // Index of the selected line with a location
+    selected: Option<usize>,
+    // Rows to show output lines in
+    output_height: usize,
     timer_rx: tokio::sync::oneshot::Receiver<(
         tokio::io::Result<std::process::ExitStatus>,
         std::time::Duration,
@@ -170,7 +234,7 @@ impl ViewProcess {
             loop {
                 line.clear();
                 if stdout.read_line(&mut line).await.is_ok() && !line.is_empty() {
-                    if tx1.send(line.trim_end().to_string()).is_err() {
+                    if tx1.send((false, line.trim_end().to_string())).is_err() {
                         return;
                     }
                 } else {
@@ -184,7 +248,7 @@ impl ViewProcess {
             loop {
                 line.clear();
                 if stderr.read_line(&mut line).await.is_ok() && !line.is_empty() {
-                    if tx2.send(line.trim_end().to_string()).is_err() {
+                    if tx2.send((true, line.trim_end().to_string())).is_err() {
                         return;
                     }
                 } else {
@@ -207,12 +271,109 @@ impl ViewProcess {
             row_offset: 0,
             buf: Vec::new(),
             reader: rx,
+            stderr: String::new(),
+            report_count: None,
+            locations: Vec::new(),
+            location_parser: compiler::LocationParser::default(),
+            source_files: HashMap::new(),
+            selected: None,
+            output_height: 0,
             timer_rx,
             exit_status: None,
             title,
             kill_tx: Some(kill_tx),
         })
     }
+
+    fn title_rows(&self) -> usize {
+        if self.title.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    // Select the next (or previous) line with a location and scroll to it
+    fn select_location(&mut self, forward: bool) {
+        let has_location = |i: &usize| self.locations[*i].is_some();
+        let selected = if forward {
+            let start = self.selected.map(|i| i + 1).unwrap_or(self.row_offset);
+            (start..self.locations.len()).find(has_location)
+        } else {
+            let end = self.selected.unwrap_or(self.locations.len());
+            (0..end).rev().find(has_location)
+        };
+        if let Some(i) = selected {
+            self.selected = Some(i);
+            let visible = self.output_height.saturating_sub(self.title_rows()).max(1);
+            if i < self.row_offset {
+                self.row_offset = i;
+            } else if i >= self.row_offset + visible {
+                self.row_offset = i + 1 - visible;
+            }
+        }
+    }
+
+    fn open_location<B: CoreBuffer>(&self, i: usize) -> Transition<B> {
+        match self.locations.get(i) {
+            Some(Some((path, cursor))) => Transition::OpenFile(path.clone(), Some(*cursor)),
+            _ => Transition::Nothing,
+        }
+    }
+
+    // Characters of lines with a location are tagged with the line index
+    fn draw_output(&self, view: &mut draw::TermView) {
+        if let Some(title) = self.title.as_ref() {
+            view.puts(&title, draw::styles::HIGHLIGHT);
+            view.newline();
+        }
+        for (i, line) in self.buf.iter().enumerate().skip(self.row_offset) {
+            let has_location = self.locations[i].is_some();
+            let style = if self.selected == Some(i) {
+                draw::styles::FOOTER
+            } else if has_location {
+                draw::styles::LINK
+            } else {
+                draw::styles::DEFAULT
+            };
+            for (col, c) in line.chars().enumerate() {
+                let pos = if has_location {
+                    Some(Cursor { row: i, col })
+                } else {
+                    None
+                };
+                view.put(c, style, pos);
+            }
+            view.newline();
+            if view.is_out() {
+                break;
+            }
+        }
+        if let Some(duration) = self.exit_status.as_ref().map(|t| t.1) {
+            view.puts(&format!("{:?}", duration), draw::styles::HIGHLIGHT);
+        }
+        if let Some(count) = self.report_count.filter(|&n| n > 0) {
+            view.newline();
+            view.puts(
+                &format!("{} runtime errors reported in this file", count),
+                draw::styles::HIGHLIGHT,
+            );
+        }
+    }
+}
+
+// Programs print paths relative to the working directory or their project root
+fn find_source_file(path: &Path, buffer_path: Option<&Path>) -> Option<PathBuf> {
+    std::iter::once(PathBuf::new())
+        .chain(
+            buffer_path
+                .and_then(Path::parent)
+                .into_iter()
+                .flat_map(Path::ancestors)
+                .map(Path::to_path_buf),
+        )
+        .map(|dir| dir.join(path))
+        .find(|p| p.is_file())
 }
 
 impl Default for Normal {
@@ -232,6 +393,10 @@ impl Normal {
 
 #[async_trait(?Send)]
 impl<B: CoreBuffer> Mode<B> for Normal {
+    fn is_normal(&self) -> bool {
+        true
+    }
+
     async fn event(
         &mut self,
         buf: &mut Buffer<'_, B>,
@@ -366,6 +531,12 @@ impl<B: CoreBuffer> Mode<B> for Normal {
                 buf.core.set_cursor(Cursor { row: 0, col: 0 });
                 buf.show_cursor();
             }
+            Event::Key(Key::Char(']')) => {
+                return Bracket { forward: true }.into_transition();
+            }
+            Event::Key(Key::Char('[')) => {
+                return Bracket { forward: false }.into_transition();
+            }
             Event::Key(Key::Char('G')) => {
                 let row = buf.core.core_buffer().len_lines() - 1;
                 let col = buf.core.core_buffer().len_line(row);
@@ -373,42 +544,7 @@ impl<B: CoreBuffer> Mode<B> for Normal {
                 buf.show_cursor();
             }
             Event::Key(Key::Char('n')) => {
-                if !buf.search.is_empty() {
-                    let mut pos = buf.core.cursor();
-
-                    let search = buf.search.iter().collect::<String>();
-                    let ac = aho_corasick::AhoCorasick::new(vec![search]);
-
-                    if let Some(p) = buf.core.next_cursor(pos) {
-                        pos = p;
-                    } else {
-                        pos = Cursor { row: 0, col: 0 };
-                    }
-
-                    let pos_bytes = if let Some(Ok(m)) = ac
-                        .stream_find_iter(iter_read::IterRead::new(
-                            buf.core.core_buffer().bytes_range(pos..),
-                        ))
-                        .next()
-                    {
-                        Some(buf.core.core_buffer().cursor_to_bytes(pos) + m.start())
-                    } else if let Some(Ok(m)) = ac
-                        .stream_find_iter(iter_read::IterRead::new(
-                            buf.core.core_buffer().bytes_range(..pos),
-                        ))
-                        .next()
-                    {
-                        Some(m.start())
-                    } else {
-                        None
-                    };
-
-                    if let Some(start) = pos_bytes {
-                        buf.core
-                            .set_cursor(buf.core.core_buffer().bytes_to_cursor(start));
-                        buf.show_cursor();
-                    }
-                }
+                buf.find_next();
             }
             Event::Key(Key::Char('N')) => {
                 // TODO: Use aho-corasick. Waiting reverse iterator of ropey.
@@ -572,16 +708,23 @@ impl<B: CoreBuffer> Mode<B> for Normal {
 
         let mut footer = view.view((height - 1, 0), 1, width);
         if let Some(message) = buf.compiler_message_on_cursor() {
-            footer.puts(message, draw::styles::FOOTER);
+            footer.puts(&message, draw::styles::FOOTER);
         } else {
             footer.puts(
                 &format!(
-                    "[Normal] ({} {}) [{}]",
+                    "[Normal] ({} {}) [{}] [{}]{}{}",
                     buf.core.cursor().row + 1,
                     buf.core.cursor().col + 1,
                     buf.path()
                         .map(Path::to_string_lossy)
                         .unwrap_or_else(|| "*".into()),
+                    buf.format,
+                    if buf.core.is_read_only() { " [RO]" } else { "" },
+                    if buf.is_large_file() {
+                        " [Large file]"
+                    } else {
+                        ""
+                    },
                 ),
                 draw::styles::FOOTER,
             );
@@ -592,7 +735,14 @@ impl<B: CoreBuffer> Mode<B> for Normal {
             if buf.is_compiling() {
                 let animation = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
                 let a = animation[self.frame % animation.len()];
-                footer.puts(&format!(" {}Compiling ...", a), draw::styles::FOOTER);
+                let elapsed = buf
+                    .compile_elapsed()
+                    .map(|d| format!(" {:.1}s", d.as_secs_f64()))
+                    .unwrap_or_default();
+                footer.puts(
+                    &format!(" {}Compiling ...{}", a, elapsed),
+                    draw::styles::FOOTER,
+                );
             } else if let Some(success) = buf.last_compile_success() {
                 let msg = if success {
                     " [Compile: Success]"
@@ -626,6 +776,8 @@ mod test_insert {
     use super::Insert;
     use crate::core::buffer::RopeyCoreBuffer;
     use crate::core::Cursor;
+    use crate::lsp::convert_completion_item;
+    use termion::event::{Event, Key};
 
     #[test]
     fn test_token() {
@@ -644,10 +796,63 @@ mod test_insert {
         Insert::remove_token(&mut core);
         assert_eq!(core.get_string(), "".to_string());
     }
+
+    // Keys handled like Insert::event does for tabstops
+    fn type_keys(insert: &mut Insert, core: &mut Core<RopeyCoreBuffer>, keys: &str) {
+        for c in keys.chars() {
+            insert.sync_tabstops(core);
+            let event = Event::Key(match c {
+                '\x08' => Key::Backspace,
+                c => Key::Char(c),
+            });
+            if insert.replace_placeholder(core, &event) {
+                continue;
+            }
+            match c {
+                '\t' => assert!(insert.jump_tabstop(core)),
+                '\x08' => {
+                    core.cursor_dec();
+                    core.delete();
+                }
+                c => core.insert(c),
+            }
+        }
+    }
+
+    #[test]
+    fn test_tabstops() {
+        let mut core = Core::<RopeyCoreBuffer>::from_reader("x = fo\n".as_bytes()).unwrap();
+        core.set_cursor(Cursor { row: 0, col: 6 });
+        let mut insert = Insert::default();
+        let item = lsp_types::CompletionItem {
+            label: "foo".to_string(),
+            insert_text: Some("foo(${1:a}, ${2:b})$0".to_string()),
+            insert_text_format: Some(lsp_types::InsertTextFormat::Snippet),
+            additional_text_edits: Some(vec![lsp_types::TextEdit {
+                range: lsp_types::Range::default(),
+                new_text: "use foo;\n".to_string(),
+            }]),
+            ..Default::default()
+        };
+        insert.apply_lsp_completion(&mut core, convert_completion_item(item));
+        assert_eq!(core.get_string(), "use foo;\nx = foo(a, b)\n");
+        // The first placeholder is selected
+        assert_eq!(core.cursor(), Cursor { row: 1, col: 8 });
+        assert!(insert.placeholder.is_some());
+
+        // Typing replaces the placeholder and later tabstops follow
+        type_keys(&mut insert, &mut core, "long\t");
+        assert_eq!(core.get_string(), "use foo;\nx = foo(long, b)\n");
+        assert_eq!(core.cursor(), Cursor { row: 1, col: 14 });
+        type_keys(&mut insert, &mut core, "\x08\t");
+        assert_eq!(core.get_string(), "use foo;\nx = foo(long, )\n");
+        assert_eq!(core.cursor(), Cursor { row: 1, col: 15 });
+        assert!(insert.tabstops.is_empty());
+    }
 }
 
 impl Insert {
-    fn token<B: CoreBuffer>(core: &Core<B>) -> String {
+    fn token_start<B: CoreBuffer>(core: &Core<B>) -> Cursor {
         let mut cursor = core.cursor();
 
         while cursor.col > 0
@@ -663,7 +868,11 @@ impl Insert {
             cursor.col -= 1;
         }
 
-        core.get_string_range(cursor..core.cursor())
+        cursor
+    }
+
+    fn token<B: CoreBuffer>(core: &Core<B>) -> String {
+        core.get_string_range(Self::token_start(core)..core.cursor())
     }
 
     fn remove_token<B: CoreBuffer>(core: &mut Core<B>) {
@@ -736,12 +945,178 @@ impl Insert {
         }
     }
 
+    fn filter_lsp_items<B: CoreBuffer>(&mut self, core: &Core<B>) {
+        let token = Self::token(core);
+        let mut completions: Vec<LSPCompletion> = self
+            .lsp_items
+            .iter()
+            .filter(|c| {
+                c.keyword != token
+                    && (token.is_empty() || fuzzy::fuzzy_match(&c.filter_text, &token).is_some())
+            })
+            .cloned()
+            .collect();
+        completions.sort_by(|a, b| (&a.sort_text, &a.keyword).cmp(&(&b.sort_text, &b.keyword)));
+        self.completions = completions;
+    }
+
+    fn apply_lsp_completion<B: CoreBuffer>(
+        &mut self,
+        core: &mut Core<B>,
+        completion: LSPCompletion,
+    ) {
+        let cursor = core.cursor();
+        let range = if let Some(range) = completion.text_edit {
+            // Cover characters typed after the request
+            if range.end.row == cursor.row && range.end < cursor {
+                range.start..cursor
+            } else {
+                range
+            }
+        } else {
+            Self::token_start(core)..cursor
+        };
+
+        let snippet = if completion.is_snippet {
+            snippet::parse(&completion.insert_text)
+        } else {
+            snippet::Snippet {
+                text: completion.insert_text,
+                tabstops: Vec::new(),
+            }
+        };
+
+        let start = core
+            .core_buffer()
+            .cursor_to_bytes(min(range.start, core.core_buffer().end_cursor()));
+        let char_to_byte = |i: usize| {
+            snippet
+                .text
+                .char_indices()
+                .nth(i)
+                .map(|(b, _)| b)
+                .unwrap_or_else(|| snippet.text.len())
+        };
+        let tabstops = snippet
+            .tabstops
+            .iter()
+            .map(|r| start + char_to_byte(r.start)..start + char_to_byte(r.end))
+            .collect::<Vec<_>>();
+
+        core.apply_edits(vec![TextEdit {
+            range,
+            text: snippet.text.clone(),
+        }]);
+        let len = core.core_buffer().len_bytes();
+        // Additional edits (e.g. imports) are placed before the completion
+        core.apply_edits(completion.additional_edits);
+        let new_len = core.core_buffer().len_bytes();
+        let shift = |p: usize| (p + new_len).saturating_sub(len);
+        self.tabstops = tabstops
+            .into_iter()
+            .map(|r| shift(r.start)..shift(r.end))
+            .collect();
+        self.placeholder = None;
+        self.tabstop_sync = Self::tabstop_state(core);
+        self.jump_tabstop(core);
+    }
+
+    fn tabstop_state<B: CoreBuffer>(core: &Core<B>) -> (usize, usize) {
+        (
+            core.core_buffer().len_bytes(),
+            core.core_buffer().cursor_to_bytes(core.cursor()),
+        )
+    }
+
+    // Move tabstops along with edits since the last call.
+    // Insert mode inserts text at the cursor and deletes text after the moved cursor.
+    fn sync_tabstops<B: CoreBuffer>(&mut self, core: &Core<B>) {
+        let (len, cursor) = Self::tabstop_state(core);
+        let (old_len, old_cursor) = std::mem::replace(&mut self.tabstop_sync, (len, cursor));
+        let map = |p: usize, is_end: bool| {
+            if len >= old_len {
+                // Text typed at the end of a placeholder belongs to it
+                if p > old_cursor || (is_end && p == old_cursor) {
+                    p + len - old_len
+                } else {
+                    p
+                }
+            } else if p >= cursor + old_len - len {
+                p - (old_len - len)
+            } else {
+                min(p, cursor)
+            }
+        };
+        for range in self.tabstops.iter_mut().chain(self.placeholder.iter_mut()) {
+            *range = map(range.start, false)..map(range.end, true);
+        }
+    }
+
+    fn jump_tabstop<B: CoreBuffer>(&mut self, core: &mut Core<B>) -> bool {
+        self.sync_tabstops(core);
+        if let Some(range) = self.tabstops.pop_front() {
+            if range.end <= core.core_buffer().len_bytes() {
+                core.set_cursor(core.core_buffer().bytes_to_cursor(range.start));
+                if !range.is_empty() {
+                    self.placeholder = Some(range);
+                }
+            }
+            self.sync_tabstops(core);
+            true
+        } else {
+            false
+        }
+    }
+
+    // Typing on the selected placeholder replaces it and Backspace / Delete remove it.
+    // true if the key is consumed.
+    fn replace_placeholder<B: CoreBuffer>(&mut self, core: &mut Core<B>, event: &Event) -> bool {
+        let placeholder = match self.placeholder.take() {
+            Some(placeholder) => placeholder,
+            None => return false,
+        };
+        let is_delete = matches!(event, Event::Key(Key::Backspace) | Event::Key(Key::Delete));
+        let is_char = matches!(event, Event::Key(Key::Char(c)) if !c.is_control());
+        if !(is_char || is_delete) || Self::tabstop_state(core).1 != placeholder.start {
+            return false;
+        }
+        let buffer = core.core_buffer();
+        let range =
+            buffer.bytes_to_cursor(placeholder.start)..buffer.bytes_to_cursor(placeholder.end);
+        core.delete_range(range);
+        self.sync_tabstops(core);
+        is_delete
+    }
+
+    fn request_resolve<B: CoreBuffer>(&mut self, buf: &Buffer<B>) {
+        if let Some(index) = self.completion_index {
+            if let (Some(lsp), Some(completion)) = (buf.lsp.as_ref(), self.completions.get(index)) {
+                if completion.documentation.is_none()
+                    && self.resolve_requested.as_ref() != Some(&completion.keyword)
+                {
+                    self.resolve_requested = Some(completion.keyword.clone());
+                    lsp.request_resolve(completion);
+                }
+            }
+        }
+    }
+
     fn poll<B: CoreBuffer>(&mut self, buf: &mut Buffer<B>) {
         if let Some(lsp) = buf.lsp.as_mut() {
-            if let Some(mut completions) = lsp.poll() {
-                let token = Self::token(&buf.core);
-                completions.retain(|s| s.keyword != token);
-                self.completions = completions;
+            if let Some(list) = lsp.poll() {
+                self.lsp_items = list.items;
+                self.lsp_incomplete = list.is_incomplete;
+                self.filter_lsp_items(&buf.core);
+            }
+            if let Some(resolved) = lsp.poll_resolve() {
+                for c in self
+                    .lsp_items
+                    .iter_mut()
+                    .chain(self.completions.iter_mut())
+                    .filter(|c| c.keyword == resolved.keyword)
+                {
+                    c.documentation = resolved.documentation.clone();
+                }
             }
         }
 
@@ -779,14 +1154,22 @@ impl Insert {
         };
         if !prefix.is_empty() || start_completion {
             if let Some(lsp) = buf.lsp.as_ref() {
-                // LSP
-                lsp.request_completion(buf.core.get_string(), buf.core.cursor());
+                // LSP. Filter the previous result locally while it stays valid.
+                let token_start = Self::token_start(&buf.core);
+                if self.lsp_incomplete || self.lsp_token_start != Some(token_start) {
+                    lsp.request_completion(buf.core.get_string(), buf.core.cursor());
+                    self.lsp_token_start = Some(token_start);
+                }
             }
             if let Some(tabnine) = buf.tabnine.as_ref() {
                 // TabNine
                 tabnine.request_completion(buf);
             }
+        } else {
+            self.lsp_items.clear();
+            self.lsp_token_start = None;
         }
+        self.filter_lsp_items(&buf.core);
         // snippet
         let prefix = Self::token(&buf.core);
         self.snippet_completions.clear();
@@ -821,6 +1204,12 @@ impl<B: CoreBuffer> Mode<B> for Insert {
         buf: &mut Buffer<'_, B>,
         event: termion::event::Event,
     ) -> Transition<B> {
+        self.sync_tabstops(&buf.core);
+        if self.replace_placeholder(&mut buf.core, &event) {
+            self.build_completion(buf);
+            buf.show_cursor();
+            return Transition::Nothing;
+        }
         match event {
             Event::Key(Key::Esc) => {
                 buf.core.commit();
@@ -857,12 +1246,15 @@ impl<B: CoreBuffer> Mode<B> for Insert {
                 buf.show_cursor();
             }
             Event::Key(Key::Char('\t')) => {
-                if self.completion_len() > 0 {
+                if self.completion_index.is_none() && self.jump_tabstop(&mut buf.core) {
+                    buf.show_cursor();
+                } else if self.completion_len() > 0 {
                     if let Some(index) = self.completion_index {
                         self.completion_index = Some((index + 1) % self.completion_len());
                     } else {
                         self.completion_index = Some(0);
                     }
+                    self.request_resolve(buf);
                 } else {
                     if buf.hard_tab() {
                         buf.core.insert('\t');
@@ -884,11 +1276,20 @@ impl<B: CoreBuffer> Mode<B> for Insert {
                     } else {
                         self.completion_index = Some(self.completion_len() - 1);
                     }
+                    self.request_resolve(buf);
                 }
                 return Transition::Nothing;
             }
             Event::Key(Key::Char('\n')) => {
-                if self.completion_index.is_some() {
+                if let Some(completion) = self
+                    .completion_index
+                    .and_then(|index| self.completions.get(index))
+                    .cloned()
+                {
+                    self.apply_lsp_completion(&mut buf.core, completion);
+                    buf.show_cursor();
+                    self.completion_index = None;
+                } else if self.completion_index.is_some() {
                     let body = &self.get_completion(buf).unwrap();
                     self.remove_old_prefix(&mut buf.core);
                     for c in body.chars() {
@@ -956,9 +1357,17 @@ impl<B: CoreBuffer> Mode<B> for Insert {
 
     fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
         self.poll(buf);
+        self.sync_tabstops(&buf.core);
         let height = view.height();
         let width = view.width();
-        let mut cursor = buf.draw(view.view((0, 0), height, width));
+        let mut cursor = if let Some(placeholder) = self.placeholder.as_ref() {
+            let buffer = buf.core.core_buffer();
+            let range =
+                buffer.bytes_to_cursor(placeholder.start)..buffer.bytes_to_cursor(placeholder.end);
+            buf.draw_with_selected(view.view((0, 0), height, width), Some(range))
+        } else {
+            buf.draw(view.view((0, 0), height, width))
+        };
         let res = cursor
             .map(|c| draw::CursorState::Show(c, draw::CursorShape::Bar))
             .unwrap_or(draw::CursorState::Hide);
@@ -976,6 +1385,8 @@ impl<B: CoreBuffer> Mode<B> for Insert {
                     let is_selected = Some(i) == self.completion_index;
                     if i < self.completions.len() {
                         let c = &self.completions[i];
+                        view.put_inline(c.kind_icon(), draw::styles::SELECTED, None);
+                        view.put_inline(' ', draw::styles::DEFAULT, None);
                         for c in c.keyword.chars() {
                             if is_selected {
                                 view.put_inline(c, draw::styles::HIGHLIGHT, None);
@@ -1015,6 +1426,20 @@ impl<B: CoreBuffer> Mode<B> for Insert {
                     }
                     view.newline();
                 }
+
+                // Documentation of the selected LSP item
+                if let Some(doc) = self
+                    .completion_index
+                    .and_then(|i| self.completions.get(i))
+                    .and_then(|c| c.documentation.as_ref())
+                {
+                    let rest = completion_height
+                        .saturating_sub(min(completion_height, self.completion_len()));
+                    for line in doc.lines().take(min(rest, COMPLETION_DOC_LINES)) {
+                        view.puts(line, draw::styles::UI);
+                        view.newline();
+                    }
+                }
             }
         }
 
@@ -1128,10 +1553,9 @@ impl<B: CoreBuffer> Mode<B> for Save {
                 if c == '\n' {
                     let path: String = shellexpand::tilde(&self.path).to_string();
                     buf.set_storage(PathBuf::from(path.clone()));
-                    let message = if buf.save(false) {
-                        format!("Saved to {}", path)
-                    } else {
-                        format!("Failed to save {}", path)
+                    let message = match buf.save(CompileProfile::Normal) {
+                        Ok(()) => format!("Saved to {}", path),
+                        Err(err) => format!("Failed to save {}: {}", path, err),
                     };
                     return Normal::with_message(message).into_transition();
                 }
@@ -1181,7 +1605,7 @@ impl<B: CoreBuffer> Mode<B> for Prefix {
                 });
             }
             Event::Key(Key::Char(' ')) => {
-                if let Err(msg) = buf.format() {
+                if let Err(msg) = buf.format().await {
                     return Transition::Return(TransitionReturn {
                         message: Some(msg.into_owned()),
                         is_commit_dot_macro: false,
@@ -1195,16 +1619,21 @@ impl<B: CoreBuffer> Mode<B> for Prefix {
             Event::Key(Key::Char('q')) => {
                 return Transition::Exit;
             }
+            Event::Key(Key::Char('Q')) => {
+                return Transition::ExitAll;
+            }
             Event::Key(Key::Char('g')) => {
                 return Goto::default().into_transition();
             }
             Event::Key(Key::Char('s')) => {
                 if let Some(path) = buf.path().map(|p| p.to_string_lossy().into_owned()) {
-                    let _ = buf.format();
-                    let message = if buf.save(false) {
-                        format!("Saved to {}", path)
-                    } else {
-                        format!("Failed to save {}", path)
+                    if buf.is_changed_on_disk() {
+                        return DiskChangePrompt::default().into_transition();
+                    }
+                    let _ = buf.format().await;
+                    let message = match buf.save(CompileProfile::Normal) {
+                        Ok(()) => format!("Saved to {}", path),
+                        Err(err) => format!("Failed to save {}: {}", path, err),
                     };
                     return Transition::Return(TransitionReturn {
                         message: Some(message),
@@ -1244,8 +1673,33 @@ impl<B: CoreBuffer> Mode<B> for Prefix {
                     is_commit_dot_macro: false,
                 });
             }
+            Event::Key(Key::Char('x')) => {
+                let message = match buf.apply_suggestion_on_cursor() {
+                    Ok(message) => format!("Applied: {}", message),
+                    Err(err) => err.into_owned(),
+                };
+                return Transition::Return(TransitionReturn {
+                    message: Some(message),
+                    is_commit_dot_macro: false,
+                });
+            }
+            Event::Key(Key::Char('X')) => {
+                let message = match buf.apply_machine_applicable_suggestions() {
+                    Ok(n) => format!("Applied {} suggestions", n),
+                    Err(err) => err.into_owned(),
+                };
+                return Transition::Return(TransitionReturn {
+                    message: Some(message),
+                    is_commit_dot_macro: false,
+                });
+            }
             Event::Key(Key::Char('l')) => {
-                buf.restart_completer();
+                // Restart the running server in place. Connect a new one only if there is none.
+                if let Some(lsp) = buf.lsp.as_ref() {
+                    lsp.restart_server();
+                } else {
+                    buf.restart_completer();
+                }
                 return Transition::Return(TransitionReturn {
                     message: Some(
                         if buf.lsp.is_some() {
@@ -1258,18 +1712,34 @@ impl<B: CoreBuffer> Mode<B> for Prefix {
                     is_commit_dot_macro: false,
                 });
             }
-            Event::Key(Key::Char('t')) | Event::Key(Key::Char('T')) => {
-                let is_optimize = event == Event::Key(Key::Char('T'));
+            Event::Key(Key::Char('t'))
+            | Event::Key(Key::Char('T'))
+            | Event::Key(Key::Char('d')) => {
+                let profile = match event {
+                    Event::Key(Key::Char('T')) => CompileProfile::Optimize,
+                    Event::Key(Key::Char('d')) => CompileProfile::Debug,
+                    _ => CompileProfile::Normal,
+                };
+                if let Some(path) = buf.path().map(|p| p.to_string_lossy().into_owned()) {
+                    let _ = buf.format().await;
+                    // Don't run a stale executable
+                    if let Err(err) = buf.save(profile) {
+                        return Normal::with_message(format!("Failed to save {}: {}", path, err))
+                            .into_transition();
+                    }
+                }
                 let result: Result<(tokio::process::Child, Option<String>), &'static str> = async {
-                    let _ = buf.format();
-                    buf.save(is_optimize);
                     buf.wait_compile_message().await;
                     let path = buf.path().ok_or("Save First")?;
                     crate::env::set_env(path);
                     let test_command = buf
-                        .get_config::<keys::TestCommand>()
+                        .project_run_command(profile)
                         .ok_or("test_command is undefined")
-                        .map(|c| c.clone())
+                        .or_else(|_| {
+                            buf.get_config::<keys::TestCommand>()
+                                .ok_or("test_command is undefined")
+                                .map(|c| c.clone())
+                        })
                         .or_else(|e| {
                             // Detect shebang
                             let first_line = buf.core.core_buffer().get_range(
@@ -1348,6 +1818,39 @@ impl<B: CoreBuffer> Mode<B> for Prefix {
             Event::Key(Key::Char('f')) => {
                 return fuzzy::FuzzyOpen::default().into_transition();
             }
+            Event::Key(Key::Char('e')) => {
+                return diagnostics::DiagnosticsPanel::default().into_transition();
+            }
+            Event::Key(Key::Char('E')) => {
+                return file_format::FileFormatMenu.into_transition();
+            }
+            Event::Key(Key::Char('b')) => {
+                return FileTree::reveal(buf.path()).into_transition();
+            }
+            Event::Key(Key::Char('h')) => {
+                return recent::RecentPicker::default().into_transition();
+            }
+            Event::Key(Key::Char('R')) => {
+                let read_only = !buf.core.is_read_only();
+                buf.core.set_read_only(read_only);
+                return Transition::Return(TransitionReturn {
+                    message: Some(if read_only { "Read-only" } else { "Writable" }.to_string()),
+                    is_commit_dot_macro: false,
+                });
+            }
+            Event::Key(Key::Char('o')) | Event::Key(Key::Char('O')) => {
+                if buf.lsp.is_none() {
+                    return Transition::Return(TransitionReturn {
+                        message: Some("LSP is not running".to_string()),
+                        is_commit_dot_macro: false,
+                    });
+                }
+                if event == Event::Key(Key::Char('O')) {
+                    return symbol::SymbolPicker::workspace().into_transition();
+                } else {
+                    return symbol::SymbolPicker::document().into_transition();
+                }
+            }
             _ => {}
         }
         Transition::Nothing
@@ -1540,6 +2043,25 @@ impl<B: CoreBuffer> Mode<B> for Visual {
                 let range = self.get_range(buf.core.cursor(), buf.core.core_buffer());
                 return S(range).into_transition();
             }
+            Event::Key(Key::Char('=')) => {
+                let l = min(self.cursor, buf.core.cursor());
+                let r = max(self.cursor, buf.core.cursor());
+                let range = if self.line_mode {
+                    Cursor { row: l.row, col: 0 }..Cursor {
+                        row: r.row,
+                        col: buf.core.core_buffer().len_line(r.row),
+                    }
+                } else {
+                    l..buf.core.next_cursor(r).unwrap_or(r)
+                };
+                buf.core.set_cursor(l);
+                let message = buf.format_range(range).await.err().map(Cow::into_owned);
+                buf.show_cursor();
+                return Transition::Return(TransitionReturn {
+                    message,
+                    is_commit_dot_macro: false,
+                });
+            }
             Event::Mouse(MouseEvent::Press(MouseButton::Left, x, y)) => {
                 let col = x as usize - 1;
                 let row = y as usize - 1;
@@ -1605,47 +2127,85 @@ impl<B: CoreBuffer> Mode<B> for ViewProcess {
                 self.row_offset = min(self.buf.len() - 1, self.row_offset + 3);
                 Transition::Nothing
             }
+            Event::Key(Key::Char('j')) | Event::Key(Key::Down) => {
+                self.select_location(true);
+                Transition::Nothing
+            }
+            Event::Key(Key::Char('k')) | Event::Key(Key::Up) => {
+                self.select_location(false);
+                Transition::Nothing
+            }
+            Event::Key(Key::Char('\n')) => match self.selected {
+                Some(i) => self.open_location(i),
+                None => Transition::Nothing,
+            },
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, x, y)) => {
+                let mut term = draw::Term::default();
+                let height = term.height;
+                let width = term.width;
+                self.draw_output(&mut term.view((0, 0), height - 2, width));
+                match term.pos(Cursor {
+                    row: y as usize - 1,
+                    col: x as usize - 1,
+                }) {
+                    Some(c) => self.open_location(c.row),
+                    None => Transition::Nothing,
+                }
+            }
             _ => Transition::Nothing,
         }
     }
 
-    fn draw(&mut self, _buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
         if self.exit_status.is_none() {
             if let Ok(status) = self.timer_rx.try_recv() {
                 self.exit_status = Some(status);
             }
         }
         let mut read_cnt = 32;
-        while let Ok(line) = self.reader.try_recv() {
+        loop {
             if read_cnt == 0 {
                 break;
             }
-            self.buf.push(line);
-            read_cnt -= 1;
-        }
-
-        let height = view.height();
-        let width = view.width();
-        {
-            let mut view = view.view((0, 0), height - 1, width);
-            if let Some(title) = self.title.as_ref() {
-                view.puts(&title, draw::styles::HIGHLIGHT);
-                view.newline();
-            }
-            for line in &self.buf[self.row_offset..] {
-                view.puts(line, draw::styles::DEFAULT);
-                view.newline();
-                if view.is_out() {
+            match self.reader.try_recv() {
+                Ok((is_stderr, line)) => {
+                    if is_stderr {
+                        self.stderr.push_str(&line);
+                        self.stderr.push('\n');
+                    }
+                    let buffer_path = buf.path();
+                    let source_files = &mut self.source_files;
+                    self.locations.push(self.location_parser.parse(&line, |p| {
+                        source_files
+                            .entry(p.to_path_buf())
+                            .or_insert_with(|| find_source_file(p, buffer_path))
+                            .clone()
+                    }));
+                    self.buf.push(line);
+                    read_cnt -= 1;
+                }
+                Err(tokio::sync::mpsc::error::TryRecvError::Closed) => {
+                    // All output is read. Show sanitizer reports as diagnostics.
+                    if self.exit_status.is_some() && self.report_count.is_none() {
+                        self.report_count =
+                            Some(buf.set_runtime_messages(compiler::parse_sanitizer(&self.stderr)));
+                    }
                     break;
                 }
-            }
-            if let Some(duration) = self.exit_status.as_ref().map(|t| t.1) {
-                view.puts(&format!("{:?}", duration), draw::styles::HIGHLIGHT);
+                Err(_) => break,
             }
         }
+
+        let height = view.height();
+        let width = view.width();
+        self.output_height = height - 1;
+        self.draw_output(&mut view.view((0, 0), height - 1, width));
         {
             let mut view = view.view((height - 1, 0), 1, width);
-            view.puts("Esc to return", draw::styles::FOOTER);
+            view.puts(
+                "Esc to return / j, k: select location / Enter or click: open",
+                draw::styles::FOOTER,
+            );
         }
         draw::CursorState::Hide
     }
@@ -2010,6 +2570,44 @@ impl<B: CoreBuffer> Mode<B> for Find {
     }
 }
 
+#[async_trait(?Send)]
+impl<B: CoreBuffer> Mode<B> for Bracket {
+    async fn event(
+        &mut self,
+        buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        let message = match event {
+            Event::Key(Key::Char('e')) => {
+                if buf.jump_next_diagnostic(self.forward) {
+                    None
+                } else {
+                    Some("No diagnostics".to_string())
+                }
+            }
+            _ => None,
+        };
+        Transition::Return(TransitionReturn {
+            message,
+            is_commit_dot_macro: false,
+        })
+    }
+
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        let height = view.height();
+        let width = view.width();
+        let cursor = buf
+            .draw(view.view((0, 0), height - 1, width))
+            .map(|c| draw::CursorState::Show(c, draw::CursorShape::Block))
+            .unwrap_or(draw::CursorState::Hide);
+
+        let mut footer = view.view((height - 1, 0), 1, width);
+        footer.puts(if self.forward { "]" } else { "[" }, draw::styles::FOOTER);
+
+        cursor
+    }
+}
+
 #[async_trait(?Send)]
 impl<B: CoreBuffer> Mode<B> for Goto {
     async fn event(
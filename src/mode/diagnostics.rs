@@ -0,0 +1,176 @@
+use super::Mode;
+use super::Transition;
+use crate::buffer::Buffer;
+use crate::core::CoreBuffer;
+use crate::draw;
+use std::cmp::min;
+use termion::event::{Event, Key};
+
+use async_trait::async_trait;
+
+// Quickfix style list of compiler messages
+#[derive(Default)]
+pub struct DiagnosticsPanel {
+    index: usize,
+    row_offset: usize,
+    // Show the full text of the selected message instead of the list
+    expanded: bool,
+    text_offset: usize,
+}
+
+impl DiagnosticsPanel {
+    const MAX_HEIGHT: usize = 10;
+
+    fn jump<B: CoreBuffer>(&self, buf: &mut Buffer<B>) {
+        if let Some(cursor) = buf.diagnostics().get(self.index).map(|r| *r.span.start()) {
+            buf.jump_to(cursor);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<B: CoreBuffer + 'static> Mode<B> for DiagnosticsPanel {
+    async fn event(
+        &mut self,
+        buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        let len = buf.diagnostics().len();
+        match event {
+            Event::Key(Key::Char('\n')) => {
+                self.jump(buf);
+                return super::Normal::default().into_transition();
+            }
+            Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => {
+                return super::Normal::default().into_transition();
+            }
+            Event::Key(Key::Char('\t')) => {
+                buf.diagnostic_filter = buf.diagnostic_filter.next();
+                self.index = 0;
+                self.expanded = false;
+            }
+            Event::Key(Key::Char(' ')) | Event::Key(Key::Char('o')) => {
+                self.expanded = !self.expanded;
+                self.text_offset = 0;
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Char('j')) if self.expanded => {
+                self.text_offset += 1;
+            }
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) if self.expanded => {
+                self.text_offset = self.text_offset.saturating_sub(1);
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Char('j')) if self.index + 1 < len => {
+                self.index += 1;
+                // Preview
+                self.jump(buf);
+            }
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) if self.index > 0 => {
+                self.index -= 1;
+                self.jump(buf);
+            }
+            _ => {}
+        }
+        Transition::Nothing
+    }
+
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        buf.poll_compile_message();
+        let rendered = buf
+            .diagnostics()
+            .get(self.index)
+            .map(|r| r.rendered())
+            .unwrap_or_default();
+        let diagnostics = buf
+            .diagnostics()
+            .into_iter()
+            .map(|r| {
+                (
+                    format!(
+                        "{}:{} {}: {}",
+                        r.span.start().row + 1,
+                        r.span.start().col + 1,
+                        r.level,
+                        r.message
+                    ),
+                    r.severity(),
+                )
+            })
+            .collect::<Vec<_>>();
+        self.index = min(self.index, diagnostics.len().saturating_sub(1));
+
+        let height = view.height();
+        let width = view.width();
+        let rendered_lines = rendered.lines().collect::<Vec<_>>();
+        let list_height = if self.expanded {
+            min(2 * Self::MAX_HEIGHT, rendered_lines.len())
+        } else {
+            min(Self::MAX_HEIGHT, diagnostics.len())
+        }
+        .min(height.saturating_sub(2));
+        let cursor = buf
+            .draw(view.view((0, 0), height - 1 - list_height, width))
+            .map(|c| draw::CursorState::Show(c, draw::CursorShape::Block))
+            .unwrap_or(draw::CursorState::Hide);
+
+        if self.index < self.row_offset {
+            self.row_offset = self.index;
+        } else if self.index >= self.row_offset + list_height {
+            self.row_offset = self.index + 1 - list_height;
+        }
+
+        let mut list_view = view.view((height - 1 - list_height, 0), list_height, width);
+        if self.expanded {
+            self.text_offset = min(
+                self.text_offset,
+                rendered_lines.len().saturating_sub(list_height),
+            );
+            for line in rendered_lines
+                .iter()
+                .skip(self.text_offset)
+                .take(list_height)
+            {
+                for c in line.chars() {
+                    list_view.put_inline(c, draw::styles::DEFAULT, None);
+                }
+                list_view.newline();
+            }
+        }
+        for (i, (line, severity)) in diagnostics
+            .iter()
+            .enumerate()
+            .skip(self.row_offset)
+            .take(if self.expanded { 0 } else { list_height })
+        {
+            let mut style = match severity {
+                2 => draw::styles::HIGHLIGHT,
+                1 => draw::styles::DEFAULT,
+                _ => draw::styles::UI,
+            };
+            style.bg = if i == self.index {
+                draw::Color::Rgb {
+                    r: 0x44,
+                    g: 0x44,
+                    b: 0x44,
+                }
+            } else {
+                draw::Color::Reset
+            };
+            for c in line.chars() {
+                list_view.put_inline(c, style, None);
+            }
+            list_view.newline();
+        }
+
+        let mut footer = view.view((height - 1, 0), 1, width);
+        footer.puts(
+            &format!(
+                "[Diagnostics: {}] {} messages (Tab: filter, Space: expand, Enter: jump)",
+                buf.diagnostic_filter,
+                diagnostics.len()
+            ),
+            draw::styles::FOOTER,
+        );
+
+        cursor
+    }
+}
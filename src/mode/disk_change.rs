@@ -0,0 +1,79 @@
+use super::Mode;
+use super::Transition;
+use super::TransitionReturn;
+use crate::buffer::Buffer;
+use crate::compiler::CompileProfile;
+use crate::core::CoreBuffer;
+use crate::draw;
+use std::time::{Duration, Instant};
+use termion::event::{Event, Key};
+
+use async_trait::async_trait;
+
+// Keys typed right after the prompt appears were meant for the previous mode
+const IGNORE_KEYS_FOR: Duration = Duration::from_millis(500);
+
+// Ask what to do with a file changed by another program while the buffer has modifications
+pub struct DiskChangePrompt {
+    shown: Instant,
+}
+
+impl Default for DiskChangePrompt {
+    fn default() -> Self {
+        Self {
+            shown: Instant::now(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<B: CoreBuffer + 'static> Mode<B> for DiskChangePrompt {
+    async fn event(
+        &mut self,
+        buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        if self.shown.elapsed() < IGNORE_KEYS_FOR {
+            return Transition::Nothing;
+        }
+        let message = match event {
+            Event::Key(Key::Char('r')) => match buf.reload() {
+                Ok(()) => "Reloaded (u to undo)".to_string(),
+                Err(err) => format!("Failed to reload: {}", err),
+            },
+            Event::Key(Key::Char('o')) => match buf.overwrite(CompileProfile::Normal) {
+                Ok(()) => "Overwritten with the buffer".to_string(),
+                Err(err) => format!("Failed to save: {}", err),
+            },
+            Event::Key(Key::Char('k')) | Event::Key(Key::Esc) => {
+                "Kept the buffer. Saving asks again.".to_string()
+            }
+            _ => return Transition::Nothing,
+        };
+        Transition::Return(TransitionReturn {
+            message: Some(message),
+            is_commit_dot_macro: false,
+        })
+    }
+
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        let height = view.height() - 1;
+        let width = view.width();
+        let cursor = buf
+            .draw(view.view((0, 0), height, width))
+            .map(|c| draw::CursorState::Show(c, draw::CursorShape::Block))
+            .unwrap_or(draw::CursorState::Hide);
+
+        let mut footer = view.view((height, 0), 1, width);
+        footer.puts(
+            "The file is changed on disk",
+            draw::styles::FOOTER_HIGHLIGHT,
+        );
+        footer.puts(
+            " ... [r: Reload] [o: Overwrite with the buffer] [k: Keep the buffer]",
+            draw::styles::FOOTER,
+        );
+
+        cursor
+    }
+}
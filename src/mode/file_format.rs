@@ -0,0 +1,69 @@
+use super::Mode;
+use super::Transition;
+use super::TransitionReturn;
+use crate::buffer::Buffer;
+use crate::core::CoreBuffer;
+use crate::draw;
+use crate::file_format::LineEnding;
+use encoding_rs::{EUC_JP, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+use termion::event::{Event, Key};
+
+use async_trait::async_trait;
+
+// Change encoding and line endings the buffer is saved with
+pub struct FileFormatMenu;
+
+#[async_trait(?Send)]
+impl<B: CoreBuffer + 'static> Mode<B> for FileFormatMenu {
+    async fn event(
+        &mut self,
+        buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        let mut format = buf.format;
+        match event {
+            Event::Key(Key::Char('l')) => format.line_ending = LineEnding::Lf,
+            Event::Key(Key::Char('c')) => format.line_ending = LineEnding::CrLf,
+            Event::Key(Key::Char('u')) => format.encoding = UTF_8,
+            Event::Key(Key::Char('1')) => format.encoding = UTF_16LE,
+            Event::Key(Key::Char('2')) => format.encoding = UTF_16BE,
+            Event::Key(Key::Char('s')) => format.encoding = SHIFT_JIS,
+            Event::Key(Key::Char('e')) => format.encoding = EUC_JP,
+            Event::Key(Key::Char('b')) => format.bom = !format.bom,
+            Event::Key(Key::Esc) => {
+                return Transition::Return(TransitionReturn {
+                    message: None,
+                    is_commit_dot_macro: false,
+                });
+            }
+            _ => return Transition::Nothing,
+        }
+        // Only Unicode encodings have a BOM
+        if ![UTF_8, UTF_16LE, UTF_16BE].contains(&format.encoding) {
+            format.bom = false;
+        }
+        buf.format = format;
+        Transition::Return(TransitionReturn {
+            message: Some(format!("File format: {} (Save to convert)", format)),
+            is_commit_dot_macro: false,
+        })
+    }
+
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        let height = view.height() - 1;
+        let width = view.width();
+        let cursor = buf
+            .draw(view.view((0, 0), height, width))
+            .map(|c| draw::CursorState::Show(c, draw::CursorShape::Block))
+            .unwrap_or(draw::CursorState::Hide);
+
+        let mut footer = view.view((height, 0), 1, width);
+        footer.puts(&format!("{}", buf.format), draw::styles::FOOTER_HIGHLIGHT);
+        footer.puts(
+            " ... [l: LF] [c: CRLF] [u: UTF-8] [b: BOM] [1: UTF-16LE] [2: UTF-16BE] [s: Shift_JIS] [e: EUC-JP]",
+            draw::styles::FOOTER,
+        );
+
+        cursor
+    }
+}
@@ -0,0 +1,466 @@
+use super::Mode;
+use super::Transition;
+use crate::buffer::Buffer;
+use crate::core::CoreBuffer;
+use crate::draw;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use termion::event::{Event, Key};
+
+use async_trait::async_trait;
+
+/// Walk files respecting .gitignore. Hidden files are included but `.git` is not.
+pub(super) fn walk_builder(root: &Path) -> ignore::WalkBuilder {
+    let mut builder = ignore::WalkBuilder::new(root);
+    builder
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git");
+    builder
+}
+
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+    is_dir: bool,
+}
+
+// Children of `dir` with directories first
+fn list_dir(dir: &Path) -> Vec<(PathBuf, bool)> {
+    let mut children = walk_builder(dir)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.depth() == 1)
+        .map(|entry| {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            (entry.into_path(), is_dir)
+        })
+        .collect::<Vec<_>>();
+    children.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    children
+}
+
+enum Prompt {
+    Create(Vec<char>),
+    Rename(Vec<char>),
+    Delete,
+}
+
+// Browse files under a directory
+pub struct FileTree {
+    root: PathBuf,
+    expanded: HashSet<PathBuf>,
+    entries: Vec<Entry>,
+    index: usize,
+    row_offset: usize,
+    prompt: Option<Prompt>,
+    message: String,
+}
+
+impl FileTree {
+    pub fn new(root: PathBuf) -> Self {
+        let mut tree = Self {
+            root,
+            expanded: HashSet::new(),
+            entries: Vec::new(),
+            index: 0,
+            row_offset: 0,
+            prompt: None,
+            message: String::new(),
+        };
+        tree.refresh();
+        tree
+    }
+
+    /// Tree of the working directory with `path` revealed
+    pub fn reveal(path: Option<&Path>) -> Self {
+        let root = PathBuf::from(".");
+        let mut tree = Self::new(root.clone());
+        let cwd = std::env::current_dir().unwrap_or_default();
+        if let Some(path) = path.and_then(|p| {
+            if p.is_absolute() {
+                p.strip_prefix(&cwd).ok()
+            } else {
+                Some(p)
+            }
+        }) {
+            let path = root.join(path);
+            tree.expanded
+                .extend(path.ancestors().skip(1).map(Path::to_path_buf));
+            tree.refresh();
+            tree.select(&path);
+        }
+        tree
+    }
+
+    fn refresh(&mut self) {
+        fn visit(tree: &mut FileTree, dir: &Path, depth: usize) {
+            for (path, is_dir) in list_dir(dir) {
+                let expanded = is_dir && tree.expanded.contains(&path);
+                tree.entries.push(Entry {
+                    path: path.clone(),
+                    depth,
+                    is_dir,
+                });
+                if expanded {
+                    visit(tree, &path, depth + 1);
+                }
+            }
+        }
+        self.entries.clear();
+        let root = self.root.clone();
+        visit(self, &root, 0);
+        self.index = self.index.min(self.entries.len().saturating_sub(1));
+    }
+
+    fn select(&mut self, path: &Path) {
+        if let Some(i) = self.entries.iter().position(|e| e.path == path) {
+            self.index = i;
+        }
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.index)
+    }
+
+    // Directory new files are created in
+    fn target_dir(&self) -> PathBuf {
+        match self.selected() {
+            Some(entry) if entry.is_dir && self.expanded.contains(&entry.path) => {
+                entry.path.clone()
+            }
+            Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+
+    fn create(&mut self, name: &str) -> io::Result<PathBuf> {
+        let path = self.target_dir().join(name);
+        if name.ends_with('/') {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+        }
+        let path = path.components().collect::<PathBuf>();
+        self.expanded
+            .extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        Ok(path)
+    }
+
+    fn rename(&mut self, name: &str) -> io::Result<PathBuf> {
+        let from = match self.selected() {
+            Some(entry) => entry.path.clone(),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "No file")),
+        };
+        let to = from.parent().unwrap_or(&self.root).join(name);
+        if to.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists", to.display()),
+            ));
+        }
+        fs::rename(&from, &to)?;
+        if self.expanded.remove(&from) {
+            self.expanded.insert(to.clone());
+        }
+        Ok(to)
+    }
+
+    fn delete(&mut self) -> io::Result<PathBuf> {
+        let entry = match self.selected() {
+            Some(entry) => entry,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "No file")),
+        };
+        if entry.is_dir {
+            fs::remove_dir_all(&entry.path)?;
+        } else {
+            fs::remove_file(&entry.path)?;
+        }
+        Ok(entry.path.clone())
+    }
+
+    fn event_prompt(&mut self, event: Event) {
+        let prompt = match self.prompt.as_mut() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        let line = match prompt {
+            Prompt::Create(line) | Prompt::Rename(line) => line,
+            Prompt::Delete => {
+                if event == Event::Key(Key::Char('y')) {
+                    self.message = match self.delete() {
+                        Ok(path) => format!("Deleted {}", path.display()),
+                        Err(err) => format!("Failed to delete: {}", err),
+                    };
+                    self.refresh();
+                }
+                self.prompt = None;
+                return;
+            }
+        };
+        match event {
+            Event::Key(Key::Char('\n')) => {
+                let name = line.iter().collect::<String>();
+                let result = match self.prompt.take() {
+                    Some(Prompt::Create(_)) if !name.is_empty() => self.create(&name),
+                    Some(Prompt::Rename(_)) if !name.is_empty() => self.rename(&name),
+                    _ => return,
+                };
+                match result {
+                    Ok(path) => {
+                        self.refresh();
+                        self.select(&path);
+                    }
+                    Err(err) => self.message = err.to_string(),
+                }
+            }
+            Event::Key(Key::Esc) => {
+                self.prompt = None;
+            }
+            Event::Key(Key::Backspace) => {
+                line.pop();
+            }
+            Event::Key(Key::Char(c)) if !c.is_control() => {
+                line.push(c);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<B: CoreBuffer + 'static> Mode<B> for FileTree {
+    async fn event(
+        &mut self,
+        _buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        if self.prompt.is_some() {
+            self.event_prompt(event);
+            return Transition::Nothing;
+        }
+        self.message.clear();
+        match event {
+            Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => {
+                return super::Normal::default().into_transition();
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Char('j'))
+                if self.index + 1 < self.entries.len() =>
+            {
+                self.index += 1;
+            }
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) if self.index > 0 => {
+                self.index -= 1;
+            }
+            Event::Key(Key::Char('\n')) | Event::Key(Key::Right) | Event::Key(Key::Char('l')) => {
+                match self.selected() {
+                    Some(entry) if entry.is_dir => {
+                        let path = entry.path.clone();
+                        if !self.expanded.remove(&path) {
+                            self.expanded.insert(path);
+                        }
+                        self.refresh();
+                    }
+                    Some(entry) => return Transition::OpenFile(entry.path.clone(), None),
+                    None => {}
+                }
+            }
+            Event::Key(Key::Left) | Event::Key(Key::Char('h')) => {
+                if let Some(entry) = self.selected() {
+                    let path = entry.path.clone();
+                    if !self.expanded.remove(&path) {
+                        // Go to the parent directory entry
+                        if let Some(parent) = path.parent() {
+                            let parent = parent.to_path_buf();
+                            self.select(&parent);
+                        }
+                    }
+                    self.refresh();
+                }
+            }
+            Event::Key(Key::Char('-')) | Event::Key(Key::Backspace) => {
+                let root = fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+                if let Some(parent) = root.parent() {
+                    let old_root = root.clone();
+                    self.root = parent.to_path_buf();
+                    self.expanded.clear();
+                    self.expanded.insert(old_root.clone());
+                    self.refresh();
+                    self.select(&old_root);
+                }
+            }
+            Event::Key(Key::Char('n')) => {
+                self.prompt = Some(Prompt::Create(Vec::new()));
+            }
+            Event::Key(Key::Char('r')) => {
+                if let Some(entry) = self.selected() {
+                    let name = entry
+                        .path
+                        .file_name()
+                        .map(|s| s.to_string_lossy().chars().collect())
+                        .unwrap_or_default();
+                    self.prompt = Some(Prompt::Rename(name));
+                }
+            }
+            Event::Key(Key::Char('D')) if self.selected().is_some() => {
+                self.prompt = Some(Prompt::Delete);
+            }
+            Event::Key(Key::Char('R')) => {
+                self.refresh();
+            }
+            _ => {}
+        }
+        Transition::Nothing
+    }
+
+    fn draw(&mut self, _buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        let height = view.height() - 1;
+        let width = view.width();
+
+        if self.index < self.row_offset {
+            self.row_offset = self.index;
+        } else if self.index >= self.row_offset + height {
+            self.row_offset = self.index + 1 - height;
+        }
+        let mut list_view = view.view((0, 0), height, width);
+        for (i, entry) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(self.row_offset)
+            .take(height)
+        {
+            let mut style = draw::styles::DEFAULT;
+            if i == self.index {
+                style.bg = draw::Color::Rgb {
+                    r: 0x44,
+                    g: 0x44,
+                    b: 0x44,
+                };
+            }
+            let name = entry
+                .path
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let line = if entry.is_dir {
+                let mark = if self.expanded.contains(&entry.path) {
+                    '▾'
+                } else {
+                    '▸'
+                };
+                format!("{}{} {}/", "  ".repeat(entry.depth), mark, name)
+            } else {
+                format!("{}  {}", "  ".repeat(entry.depth), name)
+            };
+            for c in line.chars() {
+                list_view.put_inline(c, style, None);
+            }
+            list_view.newline();
+        }
+
+        let mut footer = view.view((height, 0), 1, width);
+        match self.prompt.as_ref() {
+            Some(Prompt::Create(line)) => {
+                footer.puts(
+                    "New file (end with / for a directory): ",
+                    draw::styles::FOOTER,
+                );
+                footer.puts(&line.iter().collect::<String>(), draw::styles::FOOTER);
+                return draw::CursorState::Show(footer.cursor, draw::CursorShape::Bar);
+            }
+            Some(Prompt::Rename(line)) => {
+                footer.puts("Rename to: ", draw::styles::FOOTER);
+                footer.puts(&line.iter().collect::<String>(), draw::styles::FOOTER);
+                return draw::CursorState::Show(footer.cursor, draw::CursorShape::Bar);
+            }
+            Some(Prompt::Delete) => {
+                if let Some(entry) = self.selected() {
+                    footer.puts(
+                        &format!(
+                            "Delete {}{}? (y/N)",
+                            entry.path.display(),
+                            if entry.is_dir {
+                                " and its contents"
+                            } else {
+                                ""
+                            }
+                        ),
+                        draw::styles::FOOTER_HIGHLIGHT,
+                    );
+                }
+            }
+            None if !self.message.is_empty() => {
+                footer.puts(&self.message, draw::styles::FOOTER);
+            }
+            None => {
+                footer.puts(
+                    &format!("[Tree] {}", self.root.display()),
+                    draw::styles::FOOTER_HIGHLIGHT,
+                );
+                footer.puts(
+                    " ... [Enter: Open] [n: New] [r: Rename] [D: Delete] [-: Parent] [q: Quit]",
+                    draw::styles::FOOTER,
+                );
+            }
+        }
+        draw::CursorState::Hide
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileTree;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_file_tree() {
+        let dir = TempDir::new("tree");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        fs::write(dir.join("b.rs"), "").unwrap();
+        fs::write(dir.join("src").join("a.rs"), "").unwrap();
+
+        let names = |tree: &FileTree| {
+            tree.entries
+                .iter()
+                .map(|e| e.path.strip_prefix(&dir).unwrap().display().to_string())
+                .collect::<Vec<_>>()
+        };
+        let mut tree = FileTree::new(dir.to_path_buf());
+        // Directories first. Ignored files and .git are hidden.
+        assert_eq!(names(&tree), vec!["src", ".gitignore", "b.rs"]);
+
+        tree.expanded.insert(dir.join("src"));
+        tree.refresh();
+        assert_eq!(names(&tree), vec!["src", "src/a.rs", ".gitignore", "b.rs"]);
+
+        tree.index = 1;
+        let path = tree.create("c.rs").unwrap();
+        tree.refresh();
+        tree.select(&path);
+        assert_eq!(
+            names(&tree),
+            vec!["src", "src/a.rs", "src/c.rs", ".gitignore", "b.rs"]
+        );
+        let path = tree.rename("d.rs").unwrap();
+        assert!(path.ends_with("src/d.rs"));
+        tree.refresh();
+        tree.select(&path);
+        tree.delete().unwrap();
+        tree.refresh();
+        assert_eq!(names(&tree), vec!["src", "src/a.rs", ".gitignore", "b.rs"]);
+    }
+}
@@ -1,3 +1,5 @@
+use super::file_tree::walk_builder;
+use super::FileTree;
 use super::Mode;
 use super::Transition;
 use crate::buffer::Buffer;
@@ -8,19 +10,17 @@ use rayon::prelude::*;
 use std::cmp::Ordering;
 use std::cmp::Reverse;
 use std::collections::{BTreeSet, HashSet};
-use std::io::{BufRead, BufReader};
 use std::path;
-use std::process;
 use termion::event::{Event, Key};
 
 use async_trait::async_trait;
 
 #[derive(Eq)]
-struct MatchedItem {
-    score: i64,
-    index: usize,
-    line: String,
-    match_indices: HashSet<usize>,
+pub(super) struct MatchedItem {
+    pub(super) score: i64,
+    pub(super) index: usize,
+    pub(super) line: String,
+    pub(super) match_indices: HashSet<usize>,
 }
 
 impl MatchedItem {
@@ -56,7 +56,7 @@ pub struct FuzzyOpen {
     result: BTreeSet<MatchedItem>,
 }
 
-fn fuzzy_match(line: &str, query: &str) -> Option<(i64, HashSet<usize>)> {
+pub(super) fn fuzzy_match(line: &str, query: &str) -> Option<(i64, HashSet<usize>)> {
     let mut maxi = std::i64::MIN;
     let mut set = HashSet::new();
 
@@ -76,24 +76,18 @@ impl Default for FuzzyOpen {
     fn default() -> Self {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
 
-        tokio::spawn(async move {
-            let mut child = process::Command::new("find")
-                .arg(".")
-                .stdout(process::Stdio::piped())
-                .stderr(process::Stdio::piped())
-                .spawn()?;
-            if let Some(stdout) = child.stdout.take() {
-                let reader = BufReader::new(stdout);
-                for line in reader.lines() {
-                    if let Ok(line) = line {
-                        if tx.send(line.trim_end().to_string()).is_err() {
-                            child.kill()?;
-                            break;
-                        }
-                    }
+        tokio::task::spawn_blocking(move || {
+            for entry in walk_builder(path::Path::new(".")).build().flatten() {
+                if entry.depth() == 0 {
+                    continue;
+                }
+                if tx
+                    .send(entry.path().to_string_lossy().into_owned())
+                    .is_err()
+                {
+                    break;
                 }
             }
-            Ok::<(), anyhow::Error>(())
         });
 
         Self {
@@ -165,7 +159,16 @@ impl<B: CoreBuffer + 'static> Mode<B> for FuzzyOpen {
         match event {
             Event::Key(Key::Char('\n')) => {
                 if let Some(item) = self.result.iter().nth(self.index) {
-                    buf.open(path::PathBuf::from(&item.line));
+                    if path::Path::new(&item.line).is_dir() {
+                        return FileTree::new(path::PathBuf::from(&item.line)).into_transition();
+                    }
+                    if let Err(err) = buf.open(path::PathBuf::from(&item.line)) {
+                        return super::Normal::with_message(format!(
+                            "Failed to open {}: {}",
+                            item.line, err
+                        ))
+                        .into_transition();
+                    }
                 }
                 return super::Normal::default().into_transition();
             }
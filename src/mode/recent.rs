@@ -0,0 +1,184 @@
+use super::fuzzy::{fuzzy_match, MatchedItem};
+use super::Mode;
+use super::Transition;
+use crate::buffer::Buffer;
+use crate::core::CoreBuffer;
+use crate::draw;
+use crate::recent::{recent_files, RecentFile};
+use std::cmp::min;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use termion::event::{Event, Key};
+
+use async_trait::async_trait;
+
+// Fuzzy picker of recently opened or saved files. Ties are broken by recency.
+pub struct RecentPicker {
+    files: Vec<RecentFile>,
+    // Paths relative to the working directory if possible
+    lines: Vec<String>,
+    line_buf: Vec<char>,
+
+    index: usize,
+    result: BTreeSet<MatchedItem>,
+}
+
+impl Default for RecentPicker {
+    fn default() -> Self {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let files = recent_files()
+            .into_iter()
+            .filter(|f| f.path.is_file())
+            .collect::<Vec<_>>();
+        let lines = files
+            .iter()
+            .map(|f| {
+                f.path
+                    .strip_prefix(&cwd)
+                    .unwrap_or(&f.path)
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        let mut picker = Self {
+            files,
+            lines,
+            line_buf: Vec::new(),
+
+            index: 0,
+            result: Default::default(),
+        };
+        picker.update();
+        picker
+    }
+}
+
+impl RecentPicker {
+    fn update(&mut self) {
+        let query: String = self.line_buf.iter().collect();
+
+        self.result = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                if query.is_empty() {
+                    Some(MatchedItem {
+                        score: 0,
+                        index: i,
+                        line: line.clone(),
+                        match_indices: Default::default(),
+                    })
+                } else {
+                    fuzzy_match(line, query.as_str()).map(|(score, indices)| MatchedItem {
+                        score,
+                        index: i,
+                        line: line.clone(),
+                        match_indices: indices,
+                    })
+                }
+            })
+            .collect();
+        self.index = min(self.index, self.result.len().saturating_sub(1));
+    }
+
+    fn selected_path(&self) -> Option<PathBuf> {
+        let item = self.result.iter().nth(self.index)?;
+        Some(self.files[item.index].path.clone())
+    }
+}
+
+#[async_trait(?Send)]
+impl<B: CoreBuffer + 'static> Mode<B> for RecentPicker {
+    async fn event(
+        &mut self,
+        _buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        match event {
+            Event::Key(Key::Char('\n')) => {
+                // The cursor is restored on open
+                return match self.selected_path() {
+                    Some(path) => Transition::OpenFile(path, None),
+                    None => super::Normal::default().into_transition(),
+                };
+            }
+            Event::Key(Key::Char(c)) if !c.is_control() => {
+                self.line_buf.push(c);
+                self.update();
+            }
+            Event::Key(Key::Backspace) if !self.line_buf.is_empty() => {
+                self.line_buf.pop();
+                self.update();
+            }
+            Event::Key(Key::Esc) => {
+                return super::Normal::default().into_transition();
+            }
+            Event::Key(Key::Up) if !self.result.is_empty() => {
+                self.index = min(self.index + 1, self.result.len() - 1);
+            }
+            Event::Key(Key::Down) if self.index > 0 => {
+                self.index -= 1;
+            }
+            _ => {}
+        }
+        Transition::Nothing
+    }
+
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        let height = view.height();
+        {
+            let mut sub = view.view((0, 0), height - 1, view.width());
+            let buf_view_len = sub.height().saturating_sub(self.result.len());
+
+            if buf_view_len > 0 {
+                let view_buf = sub.view((0, 0), buf_view_len, sub.width());
+                buf.draw(view_buf);
+            }
+
+            let mut result_view =
+                sub.view((buf_view_len, 0), sub.height() - buf_view_len, sub.width());
+            for (i, item) in self
+                .result
+                .iter()
+                .take(result_view.height())
+                .enumerate()
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+            {
+                for (j, c) in item.line.chars().enumerate() {
+                    let mut style = if item.match_indices.contains(&j) {
+                        draw::styles::HIGHLIGHT
+                    } else {
+                        draw::styles::DEFAULT
+                    };
+                    if i == self.index {
+                        style.bg = draw::Color::Rgb {
+                            r: 0x44,
+                            g: 0x44,
+                            b: 0x44,
+                        };
+                    }
+                    result_view.put_inline(c, style, None);
+                }
+                let cursor = self.files[item.index].cursor();
+                for c in format!(" {}:{}", cursor.row + 1, cursor.col + 1).chars() {
+                    result_view.put_inline(c, draw::styles::SELECTED, None);
+                }
+                result_view.newline();
+            }
+        }
+        let mut query_view = view.view((view.height() - 1, 0), 1, view.width());
+        query_view.puts(
+            &format!("Recent> {}", self.line_buf.iter().collect::<String>()),
+            draw::styles::DEFAULT,
+        );
+
+        if query_view.is_out() {
+            draw::CursorState::Hide
+        } else {
+            draw::CursorState::Show(query_view.cursor, draw::CursorShape::Bar)
+        }
+    }
+}
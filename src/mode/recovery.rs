@@ -0,0 +1,176 @@
+use super::Mode;
+use super::Transition;
+use super::TransitionReturn;
+use crate::buffer::Buffer;
+use crate::core::CoreBuffer;
+use crate::draw;
+use crate::swap::{swap_dir, Leftover};
+use std::cmp::min;
+use std::path::PathBuf;
+use termion::event::{Event, Key};
+
+use async_trait::async_trait;
+
+fn name(leftover: &Leftover) -> String {
+    leftover
+        .path
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "[unnamed]".to_string())
+}
+
+// List of swap files left by editors that didn't exit normally
+pub struct RecoveryPicker {
+    leftovers: Vec<Leftover>,
+    index: usize,
+}
+
+impl RecoveryPicker {
+    const MAX_HEIGHT: usize = 10;
+
+    /// None if there is nothing to recover
+    pub fn find() -> Option<Self> {
+        let leftovers = Leftover::find(&swap_dir()?);
+        if leftovers.is_empty() {
+            None
+        } else {
+            Some(Self {
+                leftovers,
+                index: 0,
+            })
+        }
+    }
+
+    fn recover<B: CoreBuffer>(&self, buf: &mut Buffer<B>) -> Result<String, String> {
+        let leftover = &self.leftovers[self.index];
+        let name = name(leftover);
+        let recovery = leftover
+            .recover::<B>()
+            .map_err(|err| format!("Failed to read the swap file: {}", err))?;
+        match leftover.path.as_ref() {
+            Some(path) => {
+                let is_open = buf
+                    .path()
+                    .map(|p| crate::buffer::same_file(p, path))
+                    .unwrap_or(false);
+                if !is_open {
+                    buf.open(PathBuf::from(path))
+                        .map_err(|err| format!("Failed to open {}: {}", name, err))?;
+                }
+            }
+            // Don't overwrite another buffer
+            None if buf.path().is_some() || !buf.core.get_string().is_empty() => {
+                return Err("Open a new tab (SPACE c) to recover an unnamed buffer".to_string());
+            }
+            None => {}
+        }
+        buf.recover(&recovery.base, recovery.steps);
+        // Keep the swap file unless the text is really in the buffer
+        if buf.core.get_string() != recovery.text {
+            return Err(format!(
+                "Failed to recover {}. The swap file is kept.",
+                name
+            ));
+        }
+        let _ = leftover.remove();
+        if buf.core.is_read_only() {
+            Ok(format!(
+                "Recovered {}. The buffer is read-only: SPACE R and save to keep it.",
+                name
+            ))
+        } else {
+            Ok(format!("Recovered {}. Save to keep it.", name))
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<B: CoreBuffer + 'static> Mode<B> for RecoveryPicker {
+    async fn event(
+        &mut self,
+        buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        match event {
+            Event::Key(Key::Char('\n')) => {
+                let message = match self.recover(buf) {
+                    Ok(message) => message,
+                    Err(message) => message,
+                };
+                return Transition::Return(TransitionReturn {
+                    message: Some(message),
+                    is_commit_dot_macro: false,
+                });
+            }
+            Event::Key(Key::Char('d')) => {
+                let _ = self.leftovers.remove(self.index).remove();
+                if self.leftovers.is_empty() {
+                    return super::Normal::default().into_transition();
+                }
+                self.index = min(self.index, self.leftovers.len() - 1);
+            }
+            Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => {
+                return super::Normal::default().into_transition();
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Char('j'))
+                if self.index + 1 < self.leftovers.len() =>
+            {
+                self.index += 1;
+            }
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) if self.index > 0 => {
+                self.index -= 1;
+            }
+            _ => {}
+        }
+        Transition::Nothing
+    }
+
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        let height = view.height();
+        let width = view.width();
+        let list_height = min(Self::MAX_HEIGHT, self.leftovers.len()).min(height.saturating_sub(2));
+        let cursor = buf
+            .draw(view.view((0, 0), height - 1 - list_height, width))
+            .map(|c| draw::CursorState::Show(c, draw::CursorShape::Block))
+            .unwrap_or(draw::CursorState::Hide);
+
+        let row_offset = (self.index + 1).saturating_sub(list_height);
+        let mut list_view = view.view((height - 1 - list_height, 0), list_height, width);
+        for (i, leftover) in self
+            .leftovers
+            .iter()
+            .enumerate()
+            .skip(row_offset)
+            .take(list_height)
+        {
+            let age = leftover
+                .modified
+                .and_then(|t| t.elapsed().ok())
+                .map(|d| format!(" ({} min ago)", d.as_secs() / 60))
+                .unwrap_or_default();
+            let mut style = draw::styles::DEFAULT;
+            if i == self.index {
+                style.bg = draw::Color::Rgb {
+                    r: 0x44,
+                    g: 0x44,
+                    b: 0x44,
+                };
+            }
+            for c in format!("{}{}", name(leftover), age).chars() {
+                list_view.put_inline(c, style, None);
+            }
+            list_view.newline();
+        }
+
+        let mut footer = view.view((height - 1, 0), 1, width);
+        footer.puts(
+            &format!(
+                "[Recovery] {} unsaved buffers found (Enter: recover, d: delete, Esc: later)",
+                self.leftovers.len()
+            ),
+            draw::styles::FOOTER,
+        );
+
+        cursor
+    }
+}
@@ -0,0 +1,235 @@
+use super::fuzzy::{fuzzy_match, MatchedItem};
+use super::Mode;
+use super::Transition;
+use crate::buffer::Buffer;
+use crate::core::CoreBuffer;
+use crate::core::Cursor;
+use crate::draw;
+use crate::lsp::LSPSymbol;
+use std::cmp::min;
+use std::collections::BTreeSet;
+use termion::event::{Event, Key};
+
+use async_trait::async_trait;
+
+pub struct SymbolPicker {
+    is_workspace: bool,
+    symbols: Vec<LSPSymbol>,
+    line_buf: Vec<char>,
+
+    index: usize,
+    result: BTreeSet<MatchedItem>,
+}
+
+impl SymbolPicker {
+    pub fn document() -> Self {
+        Self::new(false)
+    }
+
+    pub fn workspace() -> Self {
+        Self::new(true)
+    }
+
+    fn new(is_workspace: bool) -> Self {
+        Self {
+            is_workspace,
+            symbols: Vec::new(),
+            line_buf: Vec::new(),
+
+            index: 0,
+            result: Default::default(),
+        }
+    }
+
+    fn query(&self) -> String {
+        self.line_buf.iter().collect()
+    }
+
+    fn request<B: CoreBuffer>(&self, buf: &Buffer<B>) {
+        if let Some(lsp) = buf.lsp.as_ref() {
+            if self.is_workspace {
+                lsp.request_workspace_symbol(self.query());
+            } else {
+                lsp.request_document_symbol(buf.core.get_string());
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        let query = self.query();
+
+        self.result = self
+            .symbols
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| {
+                if query.is_empty() {
+                    Some(MatchedItem {
+                        score: 0,
+                        index: i,
+                        line: s.name.clone(),
+                        match_indices: Default::default(),
+                    })
+                } else {
+                    fuzzy_match(s.name.as_str(), query.as_str()).map(|(score, indices)| {
+                        MatchedItem {
+                            score,
+                            index: i,
+                            line: s.name.clone(),
+                            match_indices: indices,
+                        }
+                    })
+                }
+            })
+            .collect();
+        self.index = min(self.index, self.result.len().saturating_sub(1));
+    }
+
+    fn jump<B: CoreBuffer>(&self, buf: &mut Buffer<B>) -> Transition<B> {
+        if let Some(item) = self.result.iter().nth(self.index) {
+            let symbol = &self.symbols[item.index];
+            // Symbols in other files are opened in their own tabs
+            if let Some(path) = symbol.path.as_ref() {
+                return Transition::OpenFile(path.clone(), Some(symbol.cursor));
+            }
+            let core_buffer = buf.core.core_buffer();
+            let row = min(symbol.cursor.row, core_buffer.len_lines() - 1);
+            let col = min(symbol.cursor.col, core_buffer.len_line(row));
+            buf.core.set_cursor(Cursor { row, col });
+            buf.show_cursor_middle();
+        }
+        super::Normal::default().into_transition()
+    }
+}
+
+#[async_trait(?Send)]
+impl<B: CoreBuffer + 'static> Mode<B> for SymbolPicker {
+    fn init(&mut self, buf: &mut Buffer<B>) {
+        // Flush old results
+        if let Some(lsp) = buf.lsp.as_mut() {
+            lsp.poll_symbol();
+        }
+        self.request(buf);
+    }
+
+    async fn event(
+        &mut self,
+        buf: &mut Buffer<'_, B>,
+        event: termion::event::Event,
+    ) -> Transition<B> {
+        match event {
+            Event::Key(Key::Char('\n')) => {
+                return self.jump(buf);
+            }
+            Event::Key(Key::Char(c)) if !c.is_control() => {
+                self.line_buf.push(c);
+                if self.is_workspace {
+                    self.request(buf);
+                }
+                self.update();
+            }
+            Event::Key(Key::Backspace) if !self.line_buf.is_empty() => {
+                self.line_buf.pop();
+                if self.is_workspace {
+                    self.request(buf);
+                }
+                self.update();
+            }
+            Event::Key(Key::Esc) => {
+                return super::Normal::default().into_transition();
+            }
+            Event::Key(Key::Up) if !self.result.is_empty() => {
+                self.index = min(self.index + 1, self.result.len() - 1);
+            }
+            Event::Key(Key::Down) if self.index > 0 => {
+                self.index -= 1;
+            }
+            _ => {}
+        }
+        Transition::Nothing
+    }
+
+    fn draw(&mut self, buf: &mut Buffer<B>, mut view: draw::TermView) -> draw::CursorState {
+        if let Some(symbols) = buf.lsp.as_mut().and_then(|lsp| lsp.poll_symbol()) {
+            self.symbols = symbols;
+            self.update();
+        }
+
+        let height = view.height();
+        {
+            let mut sub = view.view((0, 0), height - 1, view.width());
+            let buf_view_len = if sub.height() > self.result.len() {
+                sub.height() - self.result.len()
+            } else {
+                0
+            };
+
+            if buf_view_len > 0 {
+                let view_buf = sub.view((0, 0), buf_view_len, sub.width());
+                buf.draw(view_buf);
+            }
+
+            let mut result_view =
+                sub.view((buf_view_len, 0), sub.height() - buf_view_len, sub.width());
+            for (i, item) in self
+                .result
+                .iter()
+                .take(result_view.height())
+                .enumerate()
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+            {
+                let symbol = &self.symbols[item.index];
+                let mut bg = None;
+                if i == self.index {
+                    bg = Some(draw::Color::Rgb {
+                        r: 0x44,
+                        g: 0x44,
+                        b: 0x44,
+                    });
+                }
+
+                for (j, c) in item.line.chars().enumerate() {
+                    let mut style = if item.match_indices.contains(&j) {
+                        draw::styles::HIGHLIGHT
+                    } else {
+                        draw::styles::DEFAULT
+                    };
+                    if let Some(bg) = bg {
+                        style.bg = bg;
+                    }
+                    result_view.put_inline(c, style, None);
+                }
+
+                let mut detail = format!(" {:?}", symbol.kind);
+                if let Some(container) = symbol.container.as_ref() {
+                    detail.push_str(&format!(" in {}", container));
+                }
+                if let Some(path) = symbol.path.as_ref() {
+                    detail.push_str(&format!(" {}", path.display()));
+                }
+                for c in detail.chars() {
+                    result_view.put_inline(c, draw::styles::SELECTED, None);
+                }
+                result_view.newline();
+            }
+        }
+        let mut query_view = view.view((view.height() - 1, 0), 1, view.width());
+        let prompt = if self.is_workspace {
+            "Workspace Symbol> "
+        } else {
+            "Symbol> "
+        };
+        query_view.puts(
+            &format!("{}{}", prompt, self.query()),
+            draw::styles::DEFAULT,
+        );
+
+        if query_view.is_out() {
+            draw::CursorState::Hide
+        } else {
+            draw::CursorState::Show(query_view.cursor, draw::CursorShape::Bar)
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::core::Cursor;
+
+const MAX_RECENT_FILES: usize = 200;
+
+/// A file opened or saved recently and the last cursor position in it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct RecentFile {
+    pub path: PathBuf,
+    pub cursor: (usize, usize),
+}
+
+impl RecentFile {
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            row: self.cursor.0,
+            col: self.cursor.1,
+        }
+    }
+}
+
+fn recent_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("acc").join("recent.json"))
+}
+
+fn load_from(path: &Path) -> Vec<RecentFile> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+// Re-read the list so that editors running at the same time don't drop each other's files
+fn record_to(recent_path: &Path, path: &Path, cursor: Cursor) -> io::Result<()> {
+    let mut files = load_from(recent_path);
+    files.retain(|f| f.path != path);
+    files.insert(
+        0,
+        RecentFile {
+            path: path.to_path_buf(),
+            cursor: (cursor.row, cursor.col),
+        },
+    );
+    files.truncate(MAX_RECENT_FILES);
+    if let Some(dir) = recent_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_path = recent_path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp_path, serde_json::to_string(&files)?)?;
+    fs::rename(&temp_path, recent_path)
+}
+
+/// Most recent first
+pub fn recent_files() -> Vec<RecentFile> {
+    recent_path()
+        .map(|path| load_from(&path))
+        .unwrap_or_default()
+}
+
+/// Move `path` to the top of the list with the cursor position
+pub fn record(path: &Path, cursor: Cursor) {
+    if let Some(recent_path) = recent_path() {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let _ = record_to(&recent_path, &path, cursor);
+    }
+}
+
+/// Cursor position when `path` was left last time
+pub fn last_cursor(path: &Path) -> Option<Cursor> {
+    let path = path.canonicalize().ok()?;
+    recent_files()
+        .into_iter()
+        .find(|f| f.path == path)
+        .map(|f| f.cursor())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{load_from, record_to, RecentFile};
+    use crate::core::Cursor;
+    use crate::temp_dir::TempDir;
+    use std::path::Path;
+
+    #[test]
+    fn test_record() {
+        let dir = TempDir::new("recent");
+        let recent_path = dir.join("recent.json");
+        assert!(load_from(&recent_path).is_empty());
+        record_to(&recent_path, Path::new("/a"), Cursor { row: 1, col: 2 }).unwrap();
+        record_to(&recent_path, Path::new("/b"), Cursor { row: 3, col: 4 }).unwrap();
+        record_to(&recent_path, Path::new("/a"), Cursor { row: 5, col: 6 }).unwrap();
+        assert_eq!(
+            load_from(&recent_path),
+            vec![
+                RecentFile {
+                    path: "/a".into(),
+                    cursor: (5, 6)
+                },
+                RecentFile {
+                    path: "/b".into(),
+                    cursor: (3, 4)
+                },
+            ]
+        );
+    }
+}
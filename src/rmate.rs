@@ -1,8 +1,9 @@
 use crate::core::Core;
 use crate::core::CoreBuffer;
+use crate::file_format::FileFormat;
 use crate::storage::Storage;
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::path::Path;
 use std::path::PathBuf;
@@ -34,17 +35,18 @@ impl From<RmateSave> for RmateStorage {
 }
 
 impl<B: CoreBuffer> Storage<B> for RmateStorage {
-    fn load(&mut self) -> Core<B> {
+    // rmate sends and receives UTF-8 text
+    fn load(&mut self) -> io::Result<(Core<B>, FileFormat)> {
         let mut core = Core::default();
         core.set_string(self.rmate_save.rmate.data.clone(), true);
-        core
+        Ok((core, FileFormat::default()))
     }
-    fn save(&mut self, core: &Core<B>) -> bool {
+    fn save(&mut self, core: &Core<B>, _format: &FileFormat) -> io::Result<()> {
         let data = core.get_string();
         self.rmate_save
             .sender
             .send((self.rmate_save.rmate.token.clone(), data))
-            .is_ok()
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "rmate connection is closed"))
     }
     fn path(&self) -> &Path {
         self.path.as_path()
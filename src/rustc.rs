@@ -3,14 +3,31 @@
 use serde_derive::Deserialize;
 use serde_json;
 use serde_json::Value;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
 
 use crate::compiler::CompilerOutput;
+use crate::compiler::SpanLabel;
+use crate::compiler::Suggestion;
 use crate::core::Cursor;
+use crate::core::TextEdit;
 
 pub fn parse_rustc_json(json: &str) -> Option<CompilerOutput> {
-    let d: Diagnostic = serde_json::from_str(json).ok()?;
-    let span = d.spans.iter().find(|s| s.is_primary)?;
-    let line = span.line_start - 1;
+    parse_diagnostic(serde_json::from_str(json).ok()?)
+}
+
+pub fn parse_rustc_value(value: Value) -> Option<CompilerOutput> {
+    parse_diagnostic(serde_json::from_value(value).ok()?)
+}
+
+fn parse_diagnostic(d: Diagnostic) -> Option<CompilerOutput> {
+    let primary = d.spans.iter().find(|s| s.is_primary)?;
+    let span = to_span(primary);
+    let file_name = primary.file_name.clone();
+    Some(to_output(d, span, &file_name))
+}
+
+fn to_span(span: &DiagnosticSpan) -> RangeInclusive<Cursor> {
     let start = Cursor {
         row: span.line_start - 1,
         col: span.column_start - 1,
@@ -24,14 +41,84 @@ pub fn parse_rustc_json(json: &str) -> Option<CompilerOutput> {
         end.col -= 1;
     }
 
-    let span = start..=end;
+    start..=end
+}
+
+// Children without spans are located at `span` of the parent
+fn to_output(d: Diagnostic, span: RangeInclusive<Cursor>, file_name: &str) -> CompilerOutput {
+    let span = d
+        .spans
+        .iter()
+        .find(|s| s.is_primary && s.file_name == file_name)
+        .map(to_span)
+        .unwrap_or(span);
+    let labels = d
+        .spans
+        .iter()
+        .filter(|s| s.file_name == file_name)
+        .filter_map(|s| {
+            Some(SpanLabel {
+                span: to_span(s),
+                label: s.label.clone()?,
+                is_primary: s.is_primary,
+            })
+        })
+        .collect();
+    let suggestions = suggestions(&d, file_name);
+    let children = d
+        .children
+        .into_iter()
+        .map(|child| to_output(child, span.clone(), file_name))
+        .collect();
 
-    Some(CompilerOutput {
+    CompilerOutput {
+        file: Some(PathBuf::from(file_name)),
+        suggestions,
         message: d.message,
-        line,
+        line: span.start().row,
         level: d.level,
         span,
-    })
+        labels,
+        children,
+        rendered: d.rendered,
+    }
+}
+
+// Fix-its are carried by child diagnostics. Fix-its touching other files are dropped.
+fn suggestions(d: &Diagnostic, file_name: &str) -> Vec<Suggestion> {
+    d.children
+        .iter()
+        .filter_map(|child| {
+            let spans = child
+                .spans
+                .iter()
+                .filter(|s| s.suggested_replacement.is_some())
+                .collect::<Vec<_>>();
+            if spans.is_empty() || spans.iter().any(|s| s.file_name != file_name) {
+                return None;
+            }
+            Some(Suggestion {
+                message: child.message.clone(),
+                edits: spans
+                    .iter()
+                    .map(|s| TextEdit {
+                        range: Cursor {
+                            row: s.line_start - 1,
+                            col: s.column_start - 1,
+                        }..Cursor {
+                            row: s.line_end - 1,
+                            col: s.column_end - 1,
+                        },
+                        text: s.suggested_replacement.clone().unwrap_or_default(),
+                    })
+                    .collect(),
+                machine_applicable: spans.iter().all(|s| {
+                    s.suggestion_applicability.as_ref().and_then(Value::as_str)
+                        == Some("MachineApplicable")
+                }),
+            })
+        })
+        .collect()
 }
 
 #[derive(Deserialize)]
@@ -107,3 +194,49 @@ struct DiagnosticCode {
     /// An explanation for the code.
     explanation: Option<String>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::parse_rustc_json;
+    use crate::core::Cursor;
+
+    #[test]
+    fn test_parse_suggestion() {
+        let json = r#"{"message":"unused variable: `x`","code":null,"level":"warning","spans":[{"file_name":"a.rs","byte_start":16,"byte_end":17,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"if this is intentional, prefix it with an underscore","code":null,"level":"help","spans":[{"file_name":"a.rs","byte_start":16,"byte_end":17,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","expansion":null}],"children":[],"rendered":null}],"rendered":null}"#;
+        let output = parse_rustc_json(json).unwrap();
+        assert_eq!(output.suggestions.len(), 1);
+        let suggestion = &output.suggestions[0];
+        assert!(suggestion.machine_applicable);
+        assert_eq!(
+            suggestion.edits[0].range,
+            Cursor { row: 1, col: 8 }..Cursor { row: 1, col: 9 }
+        );
+        assert_eq!(suggestion.edits[0].text, "_x");
+
+        let json = json.replace(
+            r#""file_name":"a.rs","byte_start":16,"byte_end":17,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":"_x""#,
+            r#""file_name":"b.rs","byte_start":16,"byte_end":17,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":"_x""#,
+        );
+        let output = parse_rustc_json(&json).unwrap();
+        assert!(output.suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        let json = r#"{"message":"cannot borrow `v` as mutable because it is also borrowed as immutable","code":null,"level":"error","spans":[{"file_name":"a.rs","byte_start":0,"byte_end":0,"line_start":3,"line_end":3,"column_start":5,"column_end":14,"is_primary":true,"text":[],"label":"mutable borrow occurs here","suggested_replacement":null,"suggestion_applicability":null,"expansion":null},{"file_name":"a.rs","byte_start":0,"byte_end":0,"line_start":2,"line_end":2,"column_start":13,"column_end":15,"is_primary":false,"text":[],"label":"immutable borrow occurs here","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"for more information, see E0502","code":null,"level":"note","spans":[],"children":[],"rendered":null}],"rendered":"error[E0502]: cannot borrow `v` as mutable\n"}"#;
+        let output = parse_rustc_json(json).unwrap();
+        assert_eq!(output.line, 2);
+        assert_eq!(output.file, Some("a.rs".into()));
+        assert_eq!(output.labels.len(), 2);
+        assert!(output.labels[0].is_primary);
+        assert_eq!(
+            output.labels[1].span,
+            Cursor { row: 1, col: 12 }..=Cursor { row: 1, col: 13 }
+        );
+        assert_eq!(output.children.len(), 1);
+        assert_eq!(output.children[0].span, output.span);
+        // Spanless notes are not drawn in the source
+        assert_eq!(output.all_labels().len(), 2);
+        assert!(output.rendered.is_some());
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Open tabs to restore on the next start
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+pub struct Session {
+    pub tabs: Vec<Tab>,
+    // Active tab
+    pub index: usize,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Tab {
+    pub path: PathBuf,
+    pub cursor: (usize, usize),
+    pub row_offset: usize,
+    pub search: String,
+}
+
+fn session_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("acc").join("session"))
+}
+
+// Named sessions are files in the session directory. Sessions of working directories are in its subdirectory.
+fn session_path_in(dir: &Path, name: Option<&str>, cwd: &Path) -> Option<PathBuf> {
+    match name {
+        Some(name) => {
+            if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+                return None;
+            }
+            Some(dir.join(format!("{}.json", name)))
+        }
+        None => {
+            let mut hasher = DefaultHasher::new();
+            cwd.hash(&mut hasher);
+            let dir_name = cwd
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Some(
+                dir.join("cwd")
+                    .join(format!("{}-{:016x}.json", dir_name, hasher.finish())),
+            )
+        }
+    }
+}
+
+/// File of the session `name`, or of the working directory. None if the name is invalid.
+pub fn session_path(name: Option<&str>) -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    session_path_in(&session_dir()?, name, &cwd)
+}
+
+impl Session {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{session_path_in, Session, Tab};
+    use crate::temp_dir::TempDir;
+    use std::path::Path;
+
+    #[test]
+    fn test_session() {
+        let dir = TempDir::new("session");
+        let cwd = Path::new("/home/user/contest");
+        assert_eq!(
+            session_path_in(&dir, Some("abc"), cwd),
+            Some(dir.join("abc.json"))
+        );
+        assert_eq!(session_path_in(&dir, Some("../x"), cwd), None);
+        let path = session_path_in(&dir, None, cwd).unwrap();
+        assert!(path.starts_with(dir.join("cwd")));
+        assert_ne!(
+            session_path_in(&dir, None, Path::new("/home/other/contest")),
+            Some(path.clone())
+        );
+
+        let session = Session {
+            tabs: vec![Tab {
+                path: "src/main.rs".into(),
+                cursor: (41, 6),
+                row_offset: 20,
+                search: "fn".to_string(),
+            }],
+            index: 0,
+        };
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session);
+    }
+}
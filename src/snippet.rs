@@ -0,0 +1,233 @@
+// Parser for LSP / VSCode snippet syntax
+// https://microsoft.github.io/language-server-protocol/specification#snippet_syntax
+
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// Char ranges of tabstops in `text`, in jump order ($1, $2, ..., $0)
+    pub tabstops: Vec<Range<usize>>,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    text: String,
+    len: usize,
+    // (tabstop number, range)
+    tabstops: Vec<(usize, Range<usize>)>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.len += 1;
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().map(|c| c.is_ascii_digit()) == Some(true) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn variable_name(&mut self) -> bool {
+        let start = self.pos;
+        while self
+            .peek()
+            .map(|c| c.is_ascii_alphanumeric() || c == '_')
+            .unwrap_or(false)
+        {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    // Parse until '}' (if nested) or end
+    fn any(&mut self, nested: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                '}' if nested => return,
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(e) if e == '$' || e == '}' || e == '\\' || e == ',' || e == '|' => {
+                            self.pos += 1;
+                            self.push(e);
+                        }
+                        _ => self.push('\\'),
+                    }
+                }
+                '$' => self.dollar(),
+                c => {
+                    self.pos += 1;
+                    self.push(c);
+                }
+            }
+        }
+    }
+
+    fn dollar(&mut self) {
+        let start_pos = self.pos;
+        self.pos += 1;
+        let start = self.len;
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let n = self.number().unwrap_or(0);
+                self.tabstops.push((n, start..start));
+            }
+            Some('{') => {
+                self.pos += 1;
+                if self.peek().map(|c| c.is_ascii_digit()) == Some(true) {
+                    let n = self.number().unwrap_or(0);
+                    match self.peek() {
+                        Some(':') => {
+                            self.pos += 1;
+                            self.any(true);
+                        }
+                        Some('|') => {
+                            // Choice. Take the first one.
+                            self.pos += 1;
+                            let mut first = true;
+                            while let Some(c) = self.peek() {
+                                self.pos += 1;
+                                match c {
+                                    '|' => break,
+                                    ',' => first = false,
+                                    '\\' => {
+                                        if let Some(e) = self.peek() {
+                                            self.pos += 1;
+                                            if first {
+                                                self.push(e);
+                                            }
+                                        }
+                                    }
+                                    c if first => self.push(c),
+                                    _ => {}
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.tabstops.push((n, start..self.len));
+                } else if self.variable_name() {
+                    // Variables are not supported. Use default value.
+                    if self.peek() == Some(':') {
+                        self.pos += 1;
+                        self.any(true);
+                    } else {
+                        while self.peek().map(|c| c != '}') == Some(true) {
+                            self.pos += 1;
+                        }
+                    }
+                } else {
+                    // Not a snippet syntax. Treat as text.
+                    self.pos = start_pos + 1;
+                    self.push('$');
+                    return;
+                }
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                self.variable_name();
+            }
+            _ => {
+                self.push('$');
+            }
+        }
+    }
+}
+
+pub fn parse(s: &str) -> Snippet {
+    let mut parser = Parser {
+        chars: s.chars().collect(),
+        pos: 0,
+        text: String::new(),
+        len: 0,
+        tabstops: Vec::new(),
+    };
+    parser.any(false);
+
+    let mut tabstops: Vec<(usize, Range<usize>)> = Vec::new();
+    for (n, range) in parser.tabstops {
+        // The first occurrence is the place to jump
+        if let Some(t) = tabstops.iter_mut().find(|t| t.0 == n) {
+            if t.1.start == t.1.end && range.start != range.end {
+                t.1 = range;
+            }
+        } else {
+            tabstops.push((n, range));
+        }
+    }
+    // $0 is the last
+    tabstops.sort_by_key(|t| if t.0 == 0 { usize::MAX } else { t.0 });
+
+    Snippet {
+        text: parser.text,
+        tabstops: tabstops.into_iter().map(|t| t.1).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Snippet};
+    use std::ops::Range;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("foo"),
+            Snippet {
+                text: "foo".to_string(),
+                tabstops: vec![]
+            }
+        );
+        assert_eq!(
+            parse("foo($1)$0"),
+            Snippet {
+                text: "foo()".to_string(),
+                tabstops: vec![4..4, 5..5]
+            }
+        );
+        assert_eq!(
+            parse("foo(${1:a}, ${2:b})"),
+            Snippet {
+                text: "foo(a, b)".to_string(),
+                tabstops: vec![4..5, 7..8]
+            }
+        );
+        assert_eq!(
+            parse("${2:x} ${1:${3:y}z}"),
+            Snippet {
+                text: "x yz".to_string(),
+                tabstops: vec![2..4, 0..1, 2..3]
+            }
+        );
+        assert_eq!(
+            parse("${1|one,two|}"),
+            Snippet {
+                text: "one".to_string(),
+                tabstops: vec![Range { start: 0, end: 3 }]
+            }
+        );
+        assert_eq!(
+            parse("\\$1 \\} $ ${TM_FILENAME:file} $TM_SELECTED_TEXT"),
+            Snippet {
+                text: "$1 } $ file ".to_string(),
+                tabstops: vec![]
+            }
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use crate::core::Core;
+use crate::core::CoreBuffer;
+use crate::file_format::FileFormat;
+use crate::storage::Storage;
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::OsString;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// A file on another host edited through the `ssh` command
+pub struct SshStorage {
+    // ssh://[user@]host[:port]/path
+    url: PathBuf,
+    destination: String,
+    port: Option<String>,
+    remote_path: String,
+    program: OsString,
+    // Copy of the file at the last load or save
+    cache: Option<PathBuf>,
+}
+
+// Quote for the POSIX shell on the remote host
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+// The URL may contain `..`. Only the file name is kept to tell cached files apart.
+fn cache_name(url: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let file_name = Path::new(url)
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!("{:016x}-{}", hasher.finish(), file_name)
+}
+
+impl SshStorage {
+    /// None unless `url` is `ssh://[user@]host[:port]/path`. `/~/path` is relative to the home directory.
+    pub fn parse(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix("ssh://")?;
+        let slash = rest.find('/')?;
+        let (authority, path) = rest.split_at(slash);
+        let (destination, port) = match authority.rsplit_once(':') {
+            Some((destination, port)) if port.parse::<u16>().is_ok() => {
+                (destination, Some(port.to_string()))
+            }
+            _ => (authority, None),
+        };
+        // ssh would take `-...` as an option
+        if destination.is_empty() || destination.starts_with('-') || path.len() <= 1 {
+            return None;
+        }
+        let remote_path = path.strip_prefix("/~/").unwrap_or(path).to_string();
+        let cache = dirs::cache_dir().map(|dir| dir.join("acc").join("ssh").join(cache_name(url)));
+        Some(Self {
+            url: PathBuf::from(url),
+            destination: destination.to_string(),
+            port,
+            remote_path,
+            program: "ssh".into(),
+            cache,
+        })
+    }
+
+    /// Use another command with the arguments of `ssh`
+    pub fn with_program<S: Into<OsString>>(mut self, program: S) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    fn run(&self, script: &str, input: Option<&[u8]>) -> io::Result<Vec<u8>> {
+        let mut command = Command::new(&self.program);
+        // Never ask for a password on the editor's terminal. Don't freeze on unreachable hosts.
+        command.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+        if let Some(port) = self.port.as_ref() {
+            command.args(["-p", port]);
+        }
+        command
+            .arg(&self.destination)
+            .arg(script)
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = command.spawn().map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Failed to run {}: {}", self.program.to_string_lossy(), err),
+            )
+        })?;
+        if let Some(input) = input {
+            // Dropping stdin closes it
+            child.stdin.take().unwrap().write_all(input)?;
+        }
+        let Output {
+            status,
+            stdout,
+            stderr,
+        } = child.wait_with_output()?;
+        if status.success() {
+            return Ok(stdout);
+        }
+        let stderr = String::from_utf8_lossy(&stderr);
+        let reason = stderr.trim().lines().last().unwrap_or("").to_string();
+        // ssh exits with 255 when it can't connect or authenticate
+        let err = if status.code() == Some(255) {
+            let hint = self
+                .cache
+                .as_ref()
+                .filter(|cache| cache.exists())
+                .map(|cache| format!(" (The last copy is in {})", cache.display()))
+                .unwrap_or_default();
+            let kind = if reason.contains("Permission denied") {
+                io::ErrorKind::PermissionDenied
+            } else {
+                io::ErrorKind::ConnectionRefused
+            };
+            io::Error::new(
+                kind,
+                format!("ssh {}: {}{}", self.destination, reason, hint),
+            )
+        } else {
+            io::Error::other(format!("{}: {}", self.destination, reason))
+        };
+        Err(err)
+    }
+
+    fn update_cache(&self, bytes: &[u8]) {
+        if let Some(cache) = self.cache.as_ref() {
+            if let Some(dir) = cache.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            let _ = fs::write(cache, bytes);
+        }
+    }
+}
+
+impl<B: CoreBuffer> Storage<B> for SshStorage {
+    fn load(&mut self) -> io::Result<(Core<B>, FileFormat)> {
+        let path = quote(&self.remote_path);
+        // A missing file is a new file
+        let bytes = self.run(&format!("[ ! -e {0} ] || cat -- {0}", path), None)?;
+        self.update_cache(&bytes);
+        let (text, format) = FileFormat::decode(&bytes);
+        Ok((Core::from_reader(text.as_bytes())?, format))
+    }
+
+    // Write to a temporary file on the host and rename it, keeping the permissions
+    fn save(&mut self, core: &Core<B>, format: &FileFormat) -> io::Result<()> {
+        let bytes = format.encode(&core.get_string())?;
+        let path = quote(&self.remote_path);
+        let script = format!(
+            "t={0}.acc-$$.tmp && cat > \"$t\" && \
+             {{ [ ! -e {0} ] || chmod \"$(stat -c %a {0} 2>/dev/null || stat -f %Lp {0})\" \"$t\"; }} && \
+             mv -f \"$t\" {0} || {{ rm -f \"$t\"; exit 1; }}",
+            path
+        );
+        self.run(&script, Some(&bytes))?;
+        self.update_cache(&bytes);
+        Ok(())
+    }
+
+    fn path(&self) -> &Path {
+        &self.url
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SshStorage;
+    use crate::core::buffer::RopeyCoreBuffer;
+    use crate::core::Core;
+    use crate::file_format::FileFormat;
+    use crate::storage::Storage;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::io;
+
+    #[test]
+    fn test_parse() {
+        let s = SshStorage::parse("ssh://user@example.com:2222/home/user/a.rs").unwrap();
+        assert_eq!(s.destination, "user@example.com");
+        assert_eq!(s.port.as_deref(), Some("2222"));
+        assert_eq!(s.remote_path, "/home/user/a.rs");
+        let s = SshStorage::parse("ssh://example.com/~/a b.rs").unwrap();
+        assert_eq!(s.port, None);
+        assert_eq!(s.remote_path, "a b.rs");
+        assert!(SshStorage::parse("ssh://example.com/").is_none());
+        assert!(SshStorage::parse("ssh://-oProxyCommand=sh/x").is_none());
+        let s = SshStorage::parse("ssh://example.com/~/../../../../.bashrc").unwrap();
+        if let Some(cache) = s.cache {
+            assert_eq!(
+                cache.parent(),
+                dirs::cache_dir()
+                    .map(|d| d.join("acc").join("ssh"))
+                    .as_deref()
+            );
+            assert!(cache
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .ends_with("-.bashrc"));
+        }
+        assert!(SshStorage::parse("/tmp/a.rs").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mock_ssh() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("ssh");
+        // Runs the command locally: mock -o BatchMode=yes -o ConnectTimeout=10 host command
+        let mock = dir.join("mock-ssh");
+        fs::write(&mock, "#!/bin/sh\nshift 5\nexec sh -c \"$1\"\n").unwrap();
+        fs::set_permissions(&mock, fs::Permissions::from_mode(0o755)).unwrap();
+        let file = dir.join("it's.txt");
+        fs::write(&file, "remote\n").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let mut storage = SshStorage::parse(&format!("ssh://host{}", file.display()))
+            .unwrap()
+            .with_program(&mock);
+        storage.cache = Some(dir.join("cache"));
+        let (mut core, format): (Core<RopeyCoreBuffer>, _) = storage.load().unwrap();
+        assert_eq!(core.get_string(), "remote\n");
+        core.set_string("edited\n".to_string(), true);
+        Storage::<RopeyCoreBuffer>::save(&mut storage, &core, &format).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "edited\n");
+        assert_eq!(
+            fs::metadata(&file).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+        assert_eq!(fs::read_to_string(dir.join("cache")).unwrap(), "edited\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        // A new file
+        let mut storage = SshStorage::parse(&format!("ssh://host{}/new.txt", dir.display()))
+            .unwrap()
+            .with_program(&mock);
+        storage.cache = None;
+        let (core, _): (Core<RopeyCoreBuffer>, _) = storage.load().unwrap();
+        assert_eq!(core.get_string(), "");
+
+        // Authentication errors
+        let denied = dir.join("denied-ssh");
+        fs::write(
+            &denied,
+            "#!/bin/sh\necho 'user@host: Permission denied (publickey).' >&2\nexit 255\n",
+        )
+        .unwrap();
+        fs::set_permissions(&denied, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut storage = storage.with_program(&denied);
+        let err = Storage::<RopeyCoreBuffer>::save(&mut storage, &core, &FileFormat::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("Permission denied (publickey)"));
+    }
+}
@@ -1,32 +1,231 @@
 use crate::core::Core;
 use crate::core::CoreBuffer;
+use crate::file_format::FileFormat;
+use std::collections::hash_map::DefaultHasher;
 use std::fs;
-use std::io::{BufReader, BufWriter};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufWriter, Read, Write};
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Size and modification time of a file to notice changes by other programs
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// Size in bytes
+    pub fn size(&self) -> u64 {
+        self.len
+    }
+}
 
 pub trait Storage<B: CoreBuffer> {
-    fn load(&mut self) -> Core<B>;
-    fn save(&mut self, core: &Core<B>) -> bool;
+    fn load(&mut self) -> io::Result<(Core<B>, FileFormat)>;
+    fn save(&mut self, core: &Core<B>, format: &FileFormat) -> io::Result<()>;
     fn path(&self) -> &Path;
+    /// Stamp of the file on disk. None if it doesn't exist or can't change behind us.
+    fn stamp(&self) -> Option<FileStamp> {
+        None
+    }
+    /// Hash of the content on disk
+    fn content_hash(&self) -> Option<u64> {
+        None
+    }
+}
+
+fn write_core<B: CoreBuffer>(
+    core: &Core<B>,
+    format: &FileFormat,
+    file: fs::File,
+) -> io::Result<fs::File> {
+    let mut writer = BufWriter::new(file);
+    if format.is_plain() {
+        core.core_buffer().write_to(&mut writer)?;
+    } else {
+        writer.write_all(&format.encode(&core.get_string())?)?;
+    }
+    writer.flush()?;
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    Ok(file)
+}
+
+// Temporary file next to `path`. Returns None if the directory is not writable.
+fn create_temp_file(path: &Path) -> io::Result<Option<(PathBuf, fs::File)>> {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for i in 0..100 {
+        let temp_path = dir.join(format!(".{}.{}.{}.tmp", file_name, std::process::id(), i));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok(Some((temp_path, file))),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => return Ok(None),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "Failed to create a temporary file",
+    ))
+}
+
+// Give the new file the permissions and ownership of the file it replaces
+fn copy_metadata(file: &fs::File, metadata: &fs::Metadata) -> io::Result<()> {
+    file.set_permissions(metadata.permissions())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{fchown, MetadataExt};
+        // Only root can give files to others. Keep the file ours then.
+        let _ = fchown(file, Some(metadata.uid()), Some(metadata.gid()));
+    }
+    Ok(())
 }
 
 impl<B: CoreBuffer> Storage<B> for PathBuf {
-    fn load(&mut self) -> Core<B> {
-        fs::File::open(self)
-            .and_then(|f| Core::<B>::from_reader(BufReader::new(f)))
-            .unwrap_or_default()
+    fn load(&mut self) -> io::Result<(Core<B>, FileFormat)> {
+        let mut bytes = Vec::new();
+        match fs::File::open(self) {
+            Ok(mut f) => f.read_to_end(&mut bytes)?,
+            // New file
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok((Core::default(), FileFormat::default()))
+            }
+            Err(err) => return Err(err),
+        };
+        let (text, format) = FileFormat::decode(&bytes);
+        Ok((Core::from_reader(text.as_bytes())?, format))
     }
 
-    fn save(&mut self, core: &Core<B>) -> bool {
-        if let Ok(f) = fs::File::create(self) {
-            core.core_buffer().write_to(&mut BufWriter::new(f)).is_ok()
-        } else {
-            false
+    // Write to a temporary file and rename it so that a crash never leaves a broken file
+    fn save(&mut self, core: &Core<B>, format: &FileFormat) -> io::Result<()> {
+        // Replace the target of a symbolic link, not the link
+        let path = match fs::canonicalize(self.as_path()) {
+            Ok(path) => path,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => self.clone(),
+            Err(err) => return Err(err),
+        };
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => Some(metadata),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
+        let (temp_path, file) = match create_temp_file(&path)? {
+            Some(temp) => temp,
+            None => {
+                // The file is writable but its directory is not. Overwrite in place.
+                write_core(core, format, fs::File::create(&path)?)?;
+                return Ok(());
+            }
+        };
+        let result = metadata
+            .as_ref()
+            .map(|metadata| copy_metadata(&file, metadata))
+            .unwrap_or(Ok(()))
+            .and_then(|_| write_core(core, format, file))
+            .and_then(|_| fs::rename(&temp_path, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result?;
+
+        #[cfg(unix)]
+        {
+            // Persist the rename
+            if let Some(dir) = path.parent().filter(|dir| dir != &Path::new("")) {
+                if let Ok(dir) = fs::File::open(dir) {
+                    let _ = dir.sync_all();
+                }
+            }
         }
+        Ok(())
     }
 
     fn path(&self) -> &Path {
         self.as_ref()
     }
+
+    fn stamp(&self) -> Option<FileStamp> {
+        let metadata = fs::metadata(self).ok()?;
+        Some(FileStamp {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn content_hash(&self) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        fs::read(self).ok()?.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Storage;
+    use crate::core::buffer::RopeyCoreBuffer;
+    use crate::core::Core;
+    use crate::file_format::{FileFormat, LineEnding};
+    use crate::temp_dir::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_atomic_save() {
+        let dir = TempDir::new("storage");
+        let mut path = dir.join("a.txt");
+        fs::write(&path, "old").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        }
+
+        let mut core: Core<RopeyCoreBuffer> = Core::default();
+        core.set_string("new\n".to_string(), true);
+        Storage::<RopeyCoreBuffer>::save(&mut path, &core, &FileFormat::default()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o750);
+        }
+        // No temporary file is left
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let (loaded, _) = Storage::<RopeyCoreBuffer>::load(&mut path).unwrap();
+        assert_eq!(loaded.get_string(), "new\n");
+
+        let stamp = Storage::<RopeyCoreBuffer>::stamp(&path).unwrap();
+        let hash = Storage::<RopeyCoreBuffer>::content_hash(&path);
+        fs::write(&path, "a\r\nb\r\n").unwrap();
+        assert_ne!(Storage::<RopeyCoreBuffer>::stamp(&path), Some(stamp));
+        assert_ne!(Storage::<RopeyCoreBuffer>::content_hash(&path), hash);
+        let (loaded, format) = Storage::<RopeyCoreBuffer>::load(&mut path).unwrap();
+        assert_eq!(loaded.get_string(), "a\nb\n");
+        assert_eq!(format.line_ending, LineEnding::CrLf);
+        Storage::<RopeyCoreBuffer>::save(&mut path, &loaded, &format).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"a\r\nb\r\n");
+
+        let mut missing = dir.join("missing.txt");
+        assert!(Storage::<RopeyCoreBuffer>::load(&mut missing).is_ok());
+        let mut not_dir = path.join("a.txt");
+        assert!(
+            Storage::<RopeyCoreBuffer>::save(&mut not_dir, &core, &FileFormat::default()).is_err()
+        );
+    }
 }
@@ -0,0 +1,300 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::core::{Core, CoreBuffer, Cursor, TextEdit};
+use crate::formatter;
+
+// Swap files of unnamed buffers are told apart by this
+static UNNAMED_ID: AtomicUsize = AtomicUsize::new(0);
+
+// First line of a swap file
+#[derive(Serialize, Deserialize)]
+struct Header {
+    // None for unnamed buffers
+    path: Option<PathBuf>,
+    pid: u32,
+    base: String,
+}
+
+// Following lines are batches of edits applied one after another to the base
+#[derive(Serialize, Deserialize)]
+struct Edit {
+    start: (usize, usize),
+    end: (usize, usize),
+    text: String,
+}
+
+impl From<TextEdit> for Edit {
+    fn from(edit: TextEdit) -> Self {
+        Self {
+            start: (edit.range.start.row, edit.range.start.col),
+            end: (edit.range.end.row, edit.range.end.col),
+            text: edit.text,
+        }
+    }
+}
+
+impl From<Edit> for TextEdit {
+    fn from(edit: Edit) -> Self {
+        Self {
+            range: Cursor {
+                row: edit.start.0,
+                col: edit.start.1,
+            }..Cursor {
+                row: edit.end.0,
+                col: edit.end.1,
+            },
+            text: edit.text,
+        }
+    }
+}
+
+/// Directory to keep swap files in
+pub fn swap_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("acc").join("swap"))
+}
+
+fn write_header(swap_path: &Path, header: &Header) -> io::Result<()> {
+    let temp_path = swap_path.with_extension("tmp");
+    let mut json = serde_json::to_string(header)?;
+    json.push('\n');
+    fs::write(&temp_path, json)?;
+    fs::rename(&temp_path, swap_path)
+}
+
+/// Unsaved changes of a buffer kept as a base text and a log of edits to it.
+/// Batches of the log are undo steps after recovery until the log is compacted.
+pub struct SwapFile {
+    swap_path: PathBuf,
+    path: Option<PathBuf>,
+    // Text at the end of the log
+    text: String,
+    log_len: usize,
+}
+
+impl SwapFile {
+    pub fn create(dir: &Path, path: Option<&Path>, text: String) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let name = match path {
+            Some(path) => {
+                let mut hasher = DefaultHasher::new();
+                path.hash(&mut hasher);
+                let file_name = path
+                    .file_name()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                format!("{}-{:016x}", file_name, hasher.finish())
+            }
+            None => format!("unnamed-{}", UNNAMED_ID.fetch_add(1, Ordering::Relaxed)),
+        };
+        let swap_path = dir.join(format!("{}-{}.swap", name, std::process::id()));
+        let mut swap = Self {
+            swap_path,
+            path: path.map(Path::to_path_buf),
+            text: String::new(),
+            log_len: 0,
+        };
+        swap.rewrite(text)?;
+        Ok(swap)
+    }
+
+    fn rewrite(&mut self, text: String) -> io::Result<()> {
+        write_header(
+            &self.swap_path,
+            &Header {
+                path: self.path.clone(),
+                pid: std::process::id(),
+                base: text.clone(),
+            },
+        )?;
+        self.text = text;
+        self.log_len = 0;
+        Ok(())
+    }
+
+    /// Append the difference from the last update. The file is rewritten if the log gets long.
+    pub fn update(&mut self, text: String) -> io::Result<()> {
+        let edits = formatter::diff_edits(&self.text, &text)
+            .into_iter()
+            .map(Edit::from)
+            .collect::<Vec<_>>();
+        if edits.is_empty() {
+            return Ok(());
+        }
+        let mut line = serde_json::to_string(&edits)?;
+        line.push('\n');
+        if self.log_len + line.len() > self.text.len().max(4096) {
+            return self.rewrite(text);
+        }
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&self.swap_path)?
+            .write_all(line.as_bytes())?;
+        self.log_len += line.len();
+        self.text = text;
+        Ok(())
+    }
+
+    pub fn remove(self) {
+        let _ = fs::remove_file(&self.swap_path);
+    }
+}
+
+// Signal 0 only checks the process. EPERM means it belongs to another user.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let alive = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    alive || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(pid: u32) -> bool {
+    pid == std::process::id()
+}
+
+/// Swap file left by an editor which didn't exit normally
+pub struct Leftover {
+    pub swap_path: PathBuf,
+    // None for unnamed buffers
+    pub path: Option<PathBuf>,
+    pub modified: Option<SystemTime>,
+}
+
+/// Content of a swap file
+pub struct Recovery {
+    /// Text when the swap file was (re)written
+    pub base: String,
+    /// Batches of edits written after that. Each of them is an undo step.
+    pub steps: Vec<Vec<TextEdit>>,
+    /// Text at the last update
+    pub text: String,
+}
+
+impl Leftover {
+    pub fn find(dir: &Path) -> Vec<Self> {
+        let mut leftovers = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let swap_path = entry.ok()?.path();
+                if swap_path.extension()? != "swap" {
+                    return None;
+                }
+                let mut first_line = String::new();
+                BufReader::new(fs::File::open(&swap_path).ok()?)
+                    .read_line(&mut first_line)
+                    .ok()?;
+                let header: Header = serde_json::from_str(&first_line).ok()?;
+                if is_running(header.pid) {
+                    return None;
+                }
+                Some(Self {
+                    modified: fs::metadata(&swap_path).and_then(|m| m.modified()).ok(),
+                    swap_path,
+                    path: header.path,
+                })
+            })
+            .collect::<Vec<_>>();
+        leftovers.sort_by_key(|l| std::cmp::Reverse(l.modified));
+        leftovers
+    }
+
+    pub fn recover<B: CoreBuffer>(&self) -> io::Result<Recovery> {
+        let mut lines = BufReader::new(fs::File::open(&self.swap_path)?).lines();
+        let header: Header = serde_json::from_str(&lines.next().transpose()?.unwrap_or_default())?;
+        let mut core = Core::<B>::from_reader(header.base.as_bytes())?;
+        let mut steps = Vec::new();
+        for line in lines {
+            // The last line may be broken by a crash
+            let edits: Vec<Edit> = match serde_json::from_str(&line?) {
+                Ok(edits) => edits,
+                Err(_) => break,
+            };
+            let edits = edits.into_iter().map(TextEdit::from).collect::<Vec<_>>();
+            core.apply_edits(edits.clone());
+            steps.push(edits);
+        }
+        Ok(Recovery {
+            base: header.base,
+            steps,
+            text: core.get_string(),
+        })
+    }
+
+    pub fn remove(&self) -> io::Result<()> {
+        fs::remove_file(&self.swap_path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_running, Leftover, SwapFile};
+    use crate::core::buffer::RopeyCoreBuffer;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::path::Path;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_running() {
+        assert!(is_running(std::process::id()));
+        // init of the system or the container
+        assert!(is_running(1));
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        assert!(!is_running(pid));
+    }
+
+    #[test]
+    fn test_recover() {
+        let dir = TempDir::new("swap");
+        let mut swap =
+            SwapFile::create(&dir, Some(Path::new("/w/a.rs")), "a\nb\nc\n".to_string()).unwrap();
+        swap.update("a\nB\nc\nd\n".to_string()).unwrap();
+        swap.update("x\na\nB\nc\nd\n".to_string()).unwrap();
+        // A broken line written at a crash is ignored
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&swap.swap_path)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, b"[{\"sta"))
+            .unwrap();
+
+        let leftover = Leftover {
+            swap_path: swap.swap_path.clone(),
+            path: Some("/w/a.rs".into()),
+            modified: None,
+        };
+        let recovery = leftover.recover::<RopeyCoreBuffer>().unwrap();
+        assert_eq!(recovery.base, "a\nb\nc\n");
+        assert_eq!(recovery.steps.len(), 2);
+        assert_eq!(recovery.text, "x\na\nB\nc\nd\n");
+        // Swaps of running editors are not leftovers
+        assert!(Leftover::find(&dir).is_empty());
+
+        // Unnamed buffers
+        let unnamed = SwapFile::create(&dir, None, "stdin\n".to_string()).unwrap();
+        let other = SwapFile::create(&dir, None, "other\n".to_string()).unwrap();
+        assert_ne!(unnamed.swap_path, other.swap_path);
+        let leftover = Leftover {
+            swap_path: unnamed.swap_path.clone(),
+            path: None,
+            modified: None,
+        };
+        assert_eq!(
+            leftover.recover::<RopeyCoreBuffer>().unwrap().text,
+            "stdin\n"
+        );
+
+        unnamed.remove();
+        other.remove();
+        swap.remove();
+    }
+}
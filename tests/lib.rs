@@ -1,6 +1,9 @@
 use termion::event::{Event, Key};
 
-use accepted::{config, core::buffer::RopeyCoreBuffer, core::CoreBuffer, Buffer, BufferMode};
+use accepted::{
+    buffer_tab::BufferTab, config, core::buffer::RopeyCoreBuffer, core::CoreBuffer, core::Cursor,
+    core::TextEdit, Buffer, BufferMode,
+};
 
 use async_trait::async_trait;
 
@@ -254,3 +257,58 @@ async fn test_crlf() {
 async fn fuzz_1() {
     test_from_fuzz(&[0x62, 0x25, 0xff, 0x29, 0x41, 0xff]).await;
 }
+
+#[tokio::test]
+async fn test_read_only() {
+    let syntax_parent = accepted::syntax::SyntaxParent::default();
+    let config = config::ConfigWithDefault::default();
+    let mut buf: Buffer<RopeyCoreBuffer> = Buffer::new(&syntax_parent, &config);
+    buf.core.set_string("abc".into(), true);
+    buf.core.set_read_only(true);
+    let mut buffer = BufferMode::new(buf);
+    // Moving and yanking still work
+    buffer.command_esc("lvly").await;
+    assert_eq!(buffer.buf.yank.content, "bc");
+    buffer.command_esc("xiHello").await;
+    buffer.command_esc("oddpu").await;
+    assert_eq!(buffer.buf.core.get_string(), "abc");
+
+    buffer.buf.core.set_read_only(false);
+    buffer.command_esc("0x").await;
+    assert_eq!(buffer.buf.core.get_string(), "bc");
+}
+
+#[tokio::test]
+async fn test_recover_read_only() {
+    let syntax_parent = accepted::syntax::SyntaxParent::default();
+    let config = config::ConfigWithDefault::default();
+    let mut buf: Buffer<RopeyCoreBuffer> = Buffer::new(&syntax_parent, &config);
+    buf.core.set_string("abc\n".into(), true);
+    buf.core.set_read_only(true);
+    let step = TextEdit {
+        range: Cursor { row: 1, col: 0 }..Cursor { row: 1, col: 1 },
+        text: "y".into(),
+    };
+    buf.recover("abd\nx\n", vec![vec![step]]);
+    assert_eq!(buf.core.get_string(), "abd\ny\n");
+    assert!(buf.core.is_read_only());
+    // Steps are undone one by one
+    buf.core.set_read_only(false);
+    buf.core.undo();
+    assert_eq!(buf.core.get_string(), "abd\nx\n");
+    buf.core.undo();
+    assert_eq!(buf.core.get_string(), "abc\n");
+}
+
+#[tokio::test]
+async fn test_open_text() {
+    let syntax_parent = accepted::syntax::SyntaxParent::default();
+    let config = config::ConfigWithDefault::default();
+    let mut tab: BufferTab<RopeyCoreBuffer> = BufferTab::new(&syntax_parent, &config);
+    tab.open_text("a\nb\nc\n".to_string(), Some(Cursor { row: 1, col: 0 }));
+    tab.open_text("x\ny\n".to_string(), Some(Cursor { row: 1, col: 0 }));
+    // The last one is active
+    let buf = &tab.buffer_mode().buf;
+    assert_eq!(buf.core.get_string(), "x\ny\n");
+    assert_eq!(buf.core.cursor(), Cursor { row: 1, col: 0 });
+}